@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+
+use crate::assembler::lexer::Token;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Expr {
+    Num(i64),
+    Label(String),
+    Here,
+    Neg(Box<Expr>),
+    Binary(char, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Clone, Copy)]
+pub struct EvalContext<'a> {
+    pub labels: &'a HashMap<String, i64>,
+    pub here: i64,
+    /// Undefined labels evaluate to zero while addresses are still being worked out
+    pub strict: bool,
+}
+
+impl Expr {
+    pub fn eval(&self, ctx: &EvalContext) -> Result<i64, String> {
+        match self {
+            Expr::Num(val) => Ok(*val),
+            Expr::Here => Ok(ctx.here),
+            Expr::Label(name) => match ctx.labels.get(name) {
+                Some(addr) => Ok(*addr),
+                None if ctx.strict => Err(format!("undefined label '{}'", name)),
+                None => Ok(0),
+            },
+            Expr::Neg(expr) => expr
+                .eval(ctx)?
+                .checked_neg()
+                .ok_or_else(|| "overflow in expression".to_string()),
+            Expr::Binary(op, left, right) => {
+                let left = left.eval(ctx)?;
+                let right = right.eval(ctx)?;
+                match op {
+                    '+' => Ok(left.wrapping_add(right)),
+                    '-' => Ok(left.wrapping_sub(right)),
+                    '*' => Ok(left.wrapping_mul(right)),
+                    '/' if right == 0 => Err("division by zero".to_string()),
+                    '/' => left
+                        .checked_div(right)
+                        .ok_or_else(|| "overflow in expression".to_string()),
+                    _ => unreachable!("Unknown operator: {}", op),
+                }
+            }
+        }
+    }
+}
+
+/// Parses an expression starting at `pos`, leaving `pos` on the first token it did not consume
+pub fn parse_expr(tokens: &[Token], pos: &mut usize) -> Result<Expr, String> {
+    let mut left = parse_term(tokens, pos)?;
+    while let Some(Token::Symbol(op @ ('+' | '-'))) = tokens.get(*pos) {
+        *pos += 1;
+        let right = parse_term(tokens, pos)?;
+        left = Expr::Binary(*op, Box::new(left), Box::new(right));
+    }
+    Ok(left)
+}
+
+pub fn parse_term(tokens: &[Token], pos: &mut usize) -> Result<Expr, String> {
+    let mut left = parse_unary(tokens, pos)?;
+    while let Some(Token::Symbol(op @ ('*' | '/'))) = tokens.get(*pos) {
+        *pos += 1;
+        let right = parse_unary(tokens, pos)?;
+        left = Expr::Binary(*op, Box::new(left), Box::new(right));
+    }
+    Ok(left)
+}
+
+fn parse_unary(tokens: &[Token], pos: &mut usize) -> Result<Expr, String> {
+    let token = tokens.get(*pos).ok_or("expected expression")?;
+    *pos += 1;
+    match token {
+        Token::Number(val) => Ok(Expr::Num(*val)),
+        Token::Here => Ok(Expr::Here),
+        Token::Ident(name) => Ok(Expr::Label(name.clone())),
+        Token::Symbol('-') => Ok(Expr::Neg(Box::new(parse_unary(tokens, pos)?))),
+        Token::Symbol('+') => parse_unary(tokens, pos),
+        Token::Symbol('(') => {
+            let expr = parse_expr(tokens, pos)?;
+            match tokens.get(*pos) {
+                Some(Token::Symbol(')')) => {
+                    *pos += 1;
+                    Ok(expr)
+                }
+                _ => Err("expected ')'".to_string()),
+            }
+        }
+        Token::Symbol(c) => Err(format!("unexpected '{}' in expression", c)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use crate::assembler::{
+        expr::{parse_expr, EvalContext},
+        lexer::tokenize,
+    };
+
+    fn eval(src: &str, strict: bool) -> Result<i64, String> {
+        let tokens = tokenize(src).unwrap();
+        let mut pos = 0;
+        let expr = parse_expr(&tokens, &mut pos)?;
+        let labels = HashMap::from([("start".to_string(), 6)]);
+        expr.eval(&EvalContext {
+            labels: &labels,
+            here: 20,
+            strict,
+        })
+    }
+
+    #[test]
+    fn test_eval_precedence() {
+        assert_eq!(eval("64*4", true), Ok(256));
+        assert_eq!(eval("1 + 2 * 3", true), Ok(7));
+        assert_eq!(eval("(1 + 2) * 3", true), Ok(9));
+        assert_eq!(eval("10 - 4 - 3", true), Ok(3));
+        assert_eq!(eval("-2 * 3", true), Ok(-6));
+    }
+
+    #[test]
+    fn test_eval_labels() {
+        assert_eq!(eval("start + 2", true), Ok(8));
+        assert_eq!(eval("$ - 6", true), Ok(14));
+        assert_eq!(eval("missing", false), Ok(0));
+        assert_eq!(
+            eval("missing", true),
+            Err("undefined label 'missing'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_eval_errors() {
+        assert_eq!(eval("1 / 0", true), Err("division by zero".to_string()));
+        assert_eq!(
+            eval("-(-9223372036854775807 - 1)", true),
+            Err("overflow in expression".to_string())
+        );
+        assert_eq!(
+            eval("(-9223372036854775807 - 1) / -1", true),
+            Err("overflow in expression".to_string())
+        );
+        assert_eq!(eval("(1 + 2", true), Err("expected ')'".to_string()));
+        assert_eq!(eval("", true), Err("expected expression".to_string()));
+    }
+}
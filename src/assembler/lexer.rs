@@ -0,0 +1,130 @@
+#[derive(Debug, PartialEq, Clone)]
+pub enum Token {
+    Ident(String),
+    Number(i64),
+    Symbol(char),
+    Here,
+}
+
+pub fn tokenize(line: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut chars = line.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            ';' => break,
+            _ if c.is_whitespace() => {
+                chars.next();
+            }
+            '+' | '-' | '*' | '/' | '(' | ')' | '[' | ']' | ',' | ':' => {
+                tokens.push(Token::Symbol(c));
+                chars.next();
+            }
+            '$' => {
+                tokens.push(Token::Here);
+                chars.next();
+            }
+            _ if c.is_ascii_digit() => {
+                let mut text = String::new();
+                while let Some(&c) = chars.peek() {
+                    if !c.is_ascii_alphanumeric() {
+                        break;
+                    }
+                    text.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Number(parse_number(&text)?));
+            }
+            _ if is_ident_start(c) => {
+                let mut text = String::new();
+                while let Some(&c) = chars.peek() {
+                    if !is_ident_start(c) && !c.is_ascii_digit() {
+                        break;
+                    }
+                    text.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Ident(text));
+            }
+            _ => return Err(format!("unexpected character '{}'", c)),
+        }
+    }
+    Ok(tokens)
+}
+
+fn is_ident_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || c == '.'
+}
+
+pub fn parse_number(text: &str) -> Result<i64, String> {
+    let lower = text.to_ascii_lowercase();
+    let parsed = if let Some(hex) = lower.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16)
+    } else if let Some(bin) = lower.strip_prefix("0b") {
+        i64::from_str_radix(bin, 2)
+    } else if let Some(hex) = lower.strip_suffix('h') {
+        i64::from_str_radix(hex, 16)
+    } else if let Some(bin) = lower.strip_suffix('b') {
+        i64::from_str_radix(bin, 2)
+    } else {
+        lower.parse()
+    };
+    parsed.map_err(|_| format!("invalid number '{}'", text))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::assembler::lexer::{parse_number, tokenize, Token};
+
+    #[test]
+    fn test_tokenize_instr() {
+        let tokens = tokenize("mov word [bp + 2], dx ; Blue").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Ident("mov".to_string()),
+                Token::Ident("word".to_string()),
+                Token::Symbol('['),
+                Token::Ident("bp".to_string()),
+                Token::Symbol('+'),
+                Token::Number(2),
+                Token::Symbol(']'),
+                Token::Symbol(','),
+                Token::Ident("dx".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_label() {
+        let tokens = tokenize("x_loop_start: jne $-6").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Ident("x_loop_start".to_string()),
+                Token::Symbol(':'),
+                Token::Ident("jne".to_string()),
+                Token::Here,
+                Token::Symbol('-'),
+                Token::Number(6),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_invalid() {
+        assert_eq!(
+            tokenize("mov ax, #1"),
+            Err("unexpected character '#'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_number() {
+        assert_eq!(parse_number("123"), Ok(123));
+        assert_eq!(parse_number("0x1F"), Ok(31));
+        assert_eq!(parse_number("1Fh"), Ok(31));
+        assert_eq!(parse_number("0b101"), Ok(5));
+        assert_eq!(parse_number("101b"), Ok(5));
+        assert_eq!(parse_number("12z"), Err("invalid number '12z'".to_string()));
+    }
+}
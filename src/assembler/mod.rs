@@ -0,0 +1,649 @@
+use std::{collections::HashMap, fmt::Display};
+
+use crate::{
+    assembler::{
+        expr::{EvalContext, Expr},
+        lexer::tokenize,
+        parser::{parse_line, Distance, Line, Operand, OperandKind, Statement},
+    },
+    decoder::{
+        common::Encoding,
+        instr::Instr,
+        loc::{eac::EffectiveAddress, eac_mode::EffectiveAddressMode, Location, Size},
+        mov::{MoveInstr, AL, AX},
+        op::{OpInstr, OpKind},
+        prefix::{Prefix, PrefixedInstr},
+        string::{StringInstr, StringKind},
+        xchg::XchgInstr,
+    },
+    encoder::{encode, loc::encode_imm},
+    sim::is_byte,
+};
+
+pub mod expr;
+pub mod lexer;
+pub mod parser;
+
+// Instruction sizes depend on label values (displacement and immediate
+// widths), so keep reassigning addresses until the labels stop moving
+const MAX_PASSES: usize = 16;
+
+#[derive(Debug, PartialEq)]
+pub struct AssembleError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for AssembleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+pub fn assemble(src: &str) -> Result<Vec<u8>, AssembleError> {
+    let lines = parse_source(src)?;
+    let mut labels = HashMap::new();
+    let mut previous = HashMap::new();
+    for _ in 0..MAX_PASSES {
+        let (found, _) = run_pass(&lines, &labels, false)?;
+        if found == labels {
+            let (_, bytes) = run_pass(&lines, &labels, true)?;
+            return Ok(bytes);
+        }
+        previous = std::mem::replace(&mut labels, found);
+    }
+    // Some label's address depends on the size of code that depends on it,
+    // report the first one that still moved
+    let (line, label) = lines
+        .iter()
+        .find_map(|(line, parsed)| {
+            let label = parsed.label.as_ref()?;
+            (previous.get(label) != labels.get(label)).then_some((*line, label.as_str()))
+        })
+        .unwrap_or_default();
+    Err(AssembleError {
+        line,
+        message: format!(
+            "label '{}' didn't settle after {} passes",
+            label, MAX_PASSES
+        ),
+    })
+}
+
+fn parse_source(src: &str) -> Result<Vec<(usize, Line)>, AssembleError> {
+    src.lines()
+        .enumerate()
+        .map(|(index, text)| {
+            let line = index + 1;
+            tokenize(text)
+                .and_then(|tokens| parse_line(&tokens))
+                .map(|parsed| (line, parsed))
+                .map_err(|message| AssembleError { line, message })
+        })
+        .collect()
+}
+
+fn run_pass(
+    lines: &[(usize, Line)],
+    labels: &HashMap<String, i64>,
+    strict: bool,
+) -> Result<(HashMap<String, i64>, Vec<u8>), AssembleError> {
+    let mut found = HashMap::new();
+    let mut bytes = vec![];
+    for (line, parsed) in lines {
+        let error = |message| AssembleError {
+            line: *line,
+            message,
+        };
+        let here = bytes.len() as i64;
+        if let Some(label) = &parsed.label {
+            if found.insert(label.clone(), here).is_some() {
+                return Err(error(format!("label '{}' redefined", label)));
+            }
+        }
+        let ctx = Assembler {
+            eval: EvalContext {
+                labels,
+                here,
+                strict,
+            },
+            prefix_len: 0,
+        };
+        match &parsed.statement {
+            None => {}
+            Some(Statement::Bits(bits)) => {
+                let bits = bits.eval(&ctx.eval).map_err(error)?;
+                if bits != 16 {
+                    return Err(error(format!("unsupported bits {}, expected 16", bits)));
+                }
+            }
+            Some(Statement::Data { size, values }) => {
+                for value in values {
+                    let value = ctx.value(value).map_err(error)?;
+                    let imm = ctx.imm(value, *size).map_err(error)?;
+                    bytes.extend(encode_imm(&imm).map_err(|err| error(err.to_string()))?);
+                }
+            }
+            Some(Statement::Instr {
+                prefixes,
+                mnemonic,
+                operands,
+            }) => {
+                let instr = ctx
+                    .build_prefixed(prefixes, mnemonic, operands)
+                    .map_err(error)?;
+                bytes.extend(encode(&instr).map_err(|err| error(err.to_string()))?);
+            }
+        }
+    }
+    Ok((found, bytes))
+}
+
+enum Resolved {
+    Reg(&'static str),
+    Mem(Location),
+    Imm(i64),
+}
+
+struct Assembler<'a> {
+    eval: EvalContext<'a>,
+    /// The number of prefix bytes before the instruction being built
+    prefix_len: i64,
+}
+
+impl Assembler<'_> {
+    // A segment written in a memory operand is emitted after any prefixes
+    // written before the mnemonic
+    fn build_prefixed(
+        &self,
+        prefixes: &[Prefix],
+        mnemonic: &str,
+        operands: &[Operand],
+    ) -> Result<Instr, String> {
+        let mut prefixes = prefixes.to_vec();
+        prefixes.extend(operands.iter().find_map(|operand| match operand.kind {
+            OperandKind::Mem {
+                segment: Some(segment),
+                ..
+            } => Some(Prefix::Segment(segment)),
+            _ => None,
+        }));
+        let inner = Assembler {
+            eval: self.eval,
+            prefix_len: prefixes.len() as i64,
+        };
+        let instr = inner.build_instr(mnemonic, operands)?;
+        if prefixes.is_empty() {
+            return Ok(instr);
+        }
+        Ok(Instr::Prefixed(PrefixedInstr {
+            prefixes,
+            instr: Box::new(instr),
+        }))
+    }
+
+    fn build_instr(&self, mnemonic: &str, operands: &[Operand]) -> Result<Instr, String> {
+        match mnemonic {
+            "mov" => self.build_mov(operands),
+            "add" => self.build_op(OpKind::Add, operands),
+            "sub" => self.build_op(OpKind::Sub, operands),
+            "cmp" => self.build_op(OpKind::Cmp, operands),
+            "je" | "jz" => Ok(Instr::Je(self.build_short_jump(operands)?)),
+            "jne" | "jnz" => Ok(Instr::Jne(self.build_short_jump(operands)?)),
+            "jmp" => self.build_jmp(operands),
+            "call" => Ok(Instr::Call(self.build_near_jump(operands)?)),
+            "xchg" => self.build_xchg(operands),
+            "int" => self.build_int(operands),
+            _ if operands.is_empty() => self.build_no_operands(mnemonic),
+            "ret" | "hlt" | "nop" | "movsb" | "movsw" | "cmpsb" | "cmpsw" | "stosb" | "stosw"
+            | "lodsb" | "lodsw" | "scasb" | "scasw" => Err("expected no operands".to_string()),
+            _ => Err(format!("unknown instruction '{}'", mnemonic)),
+        }
+    }
+
+    fn build_no_operands(&self, mnemonic: &str) -> Result<Instr, String> {
+        let (kind, size) = match mnemonic {
+            "ret" => return Ok(Instr::Ret),
+            "hlt" => return Ok(Instr::Hlt),
+            "nop" => return Ok(Instr::Nop),
+            "movsb" => (StringKind::Movs, Size::Byte),
+            "movsw" => (StringKind::Movs, Size::Word),
+            "cmpsb" => (StringKind::Cmps, Size::Byte),
+            "cmpsw" => (StringKind::Cmps, Size::Word),
+            "stosb" => (StringKind::Stos, Size::Byte),
+            "stosw" => (StringKind::Stos, Size::Word),
+            "lodsb" => (StringKind::Lods, Size::Byte),
+            "lodsw" => (StringKind::Lods, Size::Word),
+            "scasb" => (StringKind::Scas, Size::Byte),
+            "scasw" => (StringKind::Scas, Size::Word),
+            _ => return Err(format!("unknown instruction '{}'", mnemonic)),
+        };
+        Ok(Instr::Str(StringInstr { kind, size }))
+    }
+
+    fn build_int(&self, operands: &[Operand]) -> Result<Instr, String> {
+        let [operand] = operands else {
+            return Err(format!("expected 1 operand, got {}", operands.len()));
+        };
+        match self.resolve(operand)? {
+            Resolved::Imm(vector) => match self.imm(vector, Size::Byte)? {
+                Location::Immediate8(vector) => Ok(Instr::Int(vector)),
+                _ => unreachable!("Byte immediates are 8 bit"),
+            },
+            _ => Err("expected an interrupt number".to_string()),
+        }
+    }
+
+    // The register goes in the reg field, and ax with a register has its own form
+    fn build_xchg(&self, operands: &[Operand]) -> Result<Instr, String> {
+        let (dest, src, _) = self.resolve_pair(operands)?;
+        let (dest, src, encoding) = match (dest, src) {
+            (Resolved::Reg(AX), Resolved::Reg(reg)) | (Resolved::Reg(reg), Resolved::Reg(AX)) => {
+                (Location::Reg(AX), Location::Reg(reg), Encoding::AccWithReg)
+            }
+            (Resolved::Reg(dest), Resolved::Reg(src)) => (
+                Location::Reg(dest),
+                Location::Reg(src),
+                Encoding::RmToFromReg { d: true },
+            ),
+            (Resolved::Reg(reg), Resolved::Mem(mem)) | (Resolved::Mem(mem), Resolved::Reg(reg)) => {
+                (Location::Reg(reg), mem, Encoding::RmToFromReg { d: true })
+            }
+            _ => return Err("invalid combination of operands".to_string()),
+        };
+        Ok(Instr::Xchg(XchgInstr {
+            dest,
+            src,
+            encoding,
+        }))
+    }
+
+    fn build_mov(&self, operands: &[Operand]) -> Result<Instr, String> {
+        let (dest, src, size) = self.resolve_pair(operands)?;
+        let (dest, src, encoding) = match (dest, src) {
+            (Resolved::Reg(dest), Resolved::Reg(src)) => (
+                Location::Reg(dest),
+                Location::Reg(src),
+                Encoding::RmToFromReg { d: false },
+            ),
+            (Resolved::Reg(reg @ (AL | AX)), Resolved::Mem(mem @ Location::Mem(_))) => {
+                (Location::Reg(reg), mem, Encoding::Acc)
+            }
+            (Resolved::Mem(mem @ Location::Mem(_)), Resolved::Reg(reg @ (AL | AX))) => {
+                (mem, Location::Reg(reg), Encoding::Acc)
+            }
+            (Resolved::Reg(dest), Resolved::Mem(src)) => {
+                (Location::Reg(dest), src, Encoding::RmToFromReg { d: true })
+            }
+            (Resolved::Mem(dest), Resolved::Reg(src)) => {
+                (dest, Location::Reg(src), Encoding::RmToFromReg { d: false })
+            }
+            (Resolved::Reg(dest), Resolved::Imm(val)) => (
+                Location::Reg(dest),
+                self.imm(val, size.unwrap())?,
+                Encoding::ImmToReg,
+            ),
+            (Resolved::Mem(dest), Resolved::Imm(val)) => {
+                let size = size.ok_or("operation size not specified")?;
+                (dest, self.imm(val, size)?, Encoding::ImmToRm { s: false })
+            }
+            _ => return Err("invalid combination of operands".to_string()),
+        };
+        Ok(Instr::Mov(MoveInstr {
+            dest,
+            src,
+            encoding,
+        }))
+    }
+
+    fn build_op(&self, kind: OpKind, operands: &[Operand]) -> Result<Instr, String> {
+        let (dest, src, size) = self.resolve_pair(operands)?;
+        let (dest, src, encoding) = match (dest, src) {
+            (Resolved::Reg(dest), Resolved::Reg(src)) => (
+                Location::Reg(dest),
+                Location::Reg(src),
+                Encoding::RmToFromReg { d: false },
+            ),
+            (Resolved::Reg(dest), Resolved::Mem(src)) => {
+                (Location::Reg(dest), src, Encoding::RmToFromReg { d: true })
+            }
+            (Resolved::Mem(dest), Resolved::Reg(src)) => {
+                (dest, Location::Reg(src), Encoding::RmToFromReg { d: false })
+            }
+            (Resolved::Reg(AL), Resolved::Imm(val)) => (
+                Location::Reg(AL),
+                self.imm(val, Size::Byte)?,
+                Encoding::ImmToAcc,
+            ),
+            (Resolved::Reg(dest), Resolved::Imm(val)) => {
+                let (src, encoding) = self.op_imm(val, size.unwrap())?;
+                let encoding = match (dest, encoding) {
+                    (AX, Encoding::ImmToRm { s: false }) => Encoding::ImmToAcc,
+                    (_, encoding) => encoding,
+                };
+                (Location::Reg(dest), src, encoding)
+            }
+            (Resolved::Mem(dest), Resolved::Imm(val)) => {
+                let size = size.ok_or("operation size not specified")?;
+                let (src, encoding) = self.op_imm(val, size)?;
+                (dest, src, encoding)
+            }
+            _ => return Err("invalid combination of operands".to_string()),
+        };
+        Ok(Instr::Op(OpInstr {
+            kind,
+            dest,
+            src,
+            encoding,
+        }))
+    }
+
+    // Word immediates that fit in a sign-extended byte use the short form
+    fn op_imm(&self, val: i64, size: Size) -> Result<(Location, Encoding), String> {
+        let imm = self.imm(val, size)?;
+        let s = match imm {
+            Location::Immediate16(val) => (-128..=127).contains(&(val as i16)),
+            _ => false,
+        };
+        Ok((imm, Encoding::ImmToRm { s }))
+    }
+
+    fn build_short_jump(&self, operands: &[Operand]) -> Result<i8, String> {
+        match self.jump_offset(operands, 2)? {
+            (Some(Distance::Near), _) => Err("expected a short jump".to_string()),
+            (_, offset) => self.short_offset(offset),
+        }
+    }
+
+    fn build_near_jump(&self, operands: &[Operand]) -> Result<i16, String> {
+        match self.jump_offset(operands, 3)? {
+            (Some(Distance::Short), _) => Err("expected a near jump".to_string()),
+            // Near jumps wrap around the segment
+            (_, offset) => Ok(offset as i16),
+        }
+    }
+
+    // Without a distance, use the short form whenever the target is in range
+    fn build_jmp(&self, operands: &[Operand]) -> Result<Instr, String> {
+        match self.jump_offset(operands, 2)? {
+            (Some(Distance::Short), offset) => Ok(Instr::Jmp(self.short_offset(offset)?)),
+            (Some(Distance::Near), _) => Ok(Instr::JmpNear(self.build_near_jump(operands)?)),
+            (None, offset) => match i8::try_from(offset) {
+                Ok(offset) => Ok(Instr::Jmp(offset)),
+                Err(_) => Ok(Instr::JmpNear(self.build_near_jump(operands)?)),
+            },
+        }
+    }
+
+    fn short_offset(&self, offset: i64) -> Result<i8, String> {
+        match i8::try_from(offset) {
+            Ok(offset) => Ok(offset),
+            Err(_) if self.eval.strict => Err(format!("jump out of range: {}", offset)),
+            Err(_) => Ok(0),
+        }
+    }
+
+    /// The target of a jump relative to the end of the `len` byte instruction
+    fn jump_offset(
+        &self,
+        operands: &[Operand],
+        len: i64,
+    ) -> Result<(Option<Distance>, i64), String> {
+        let [Operand {
+            size: None,
+            distance,
+            kind: OperandKind::Imm(target),
+        }] = operands
+        else {
+            return Err("expected a single jump target".to_string());
+        };
+        let offset = self.value(target)? - (self.eval.here + self.prefix_len + len);
+        Ok((*distance, offset))
+    }
+
+    fn resolve_pair(
+        &self,
+        operands: &[Operand],
+    ) -> Result<(Resolved, Resolved, Option<Size>), String> {
+        let [dest, src] = operands else {
+            return Err(format!("expected 2 operands, got {}", operands.len()));
+        };
+        let dest_size = self.operand_size(dest)?;
+        let src_size = self.operand_size(src)?;
+        let size = match (dest_size, src_size) {
+            (Some(dest), Some(src)) if dest != src => {
+                return Err("mismatch in operand sizes".to_string())
+            }
+            (Some(size), _) | (_, Some(size)) => Some(size),
+            (None, None) => None,
+        };
+        Ok((self.resolve(dest)?, self.resolve(src)?, size))
+    }
+
+    fn operand_size(&self, operand: &Operand) -> Result<Option<Size>, String> {
+        let implied = match operand.kind {
+            OperandKind::Reg(reg) if is_byte(reg) => Some(Size::Byte),
+            OperandKind::Reg(_) => Some(Size::Word),
+            _ => None,
+        };
+        match (operand.size, implied) {
+            (Some(size), Some(implied)) if size != implied => {
+                Err("mismatch in operand sizes".to_string())
+            }
+            (size, implied) => Ok(size.or(implied)),
+        }
+    }
+
+    fn resolve(&self, operand: &Operand) -> Result<Resolved, String> {
+        match &operand.kind {
+            OperandKind::Reg(reg) => Ok(Resolved::Reg(reg)),
+            OperandKind::Imm(expr) => Ok(Resolved::Imm(self.value(expr)?)),
+            OperandKind::Mem { mode, disp, .. } => {
+                let disp = match disp {
+                    Some(disp) => self.value(disp)?,
+                    None => 0,
+                };
+                Ok(Resolved::Mem(self.mem(*mode, disp)?))
+            }
+        }
+    }
+
+    fn mem(&self, mode: Option<EffectiveAddressMode>, disp: i64) -> Result<Location, String> {
+        if !(-0x8000..=0xFFFF).contains(&disp) && self.eval.strict {
+            return Err(format!("displacement out of range: {}", disp));
+        }
+        let disp = disp as u16;
+        let Some(mode) = mode else {
+            return Ok(Location::Mem(disp));
+        };
+        let eac = match (mode, disp as i16) {
+            // There is no encoding for [bp] without a displacement
+            (EffectiveAddressMode::Bp, 0) => EffectiveAddress::Byte(mode, 0),
+            (_, 0) => EffectiveAddress::Mode(mode),
+            (_, disp @ -128..=127) => EffectiveAddress::Byte(mode, disp as i8),
+            (_, disp) => EffectiveAddress::Word(mode, disp),
+        };
+        Ok(Location::Eac(eac))
+    }
+
+    fn imm(&self, val: i64, size: Size) -> Result<Location, String> {
+        let range = match size {
+            Size::Byte => -0x80..=0xFF,
+            Size::Word => -0x8000..=0xFFFF,
+        };
+        if !range.contains(&val) && self.eval.strict {
+            return Err(format!("immediate out of range: {}", val));
+        }
+        match size {
+            Size::Byte => Ok(Location::Immediate8(val as u8)),
+            Size::Word => Ok(Location::Immediate16(val as u16)),
+        }
+    }
+
+    fn value(&self, expr: &Expr) -> Result<i64, String> {
+        expr.eval(&self.eval)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        assembler::{assemble, AssembleError},
+        decoder::decode,
+    };
+
+    fn assert_assembles(src: &str, expected: Vec<u8>) {
+        assert_eq!(assemble(src), Ok(expected));
+    }
+
+    fn assert_error(src: &str, line: usize, message: &str) {
+        assert_eq!(
+            assemble(src),
+            Err(AssembleError {
+                line,
+                message: message.to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_assemble_listing() {
+        let bytes = assemble(include_str!("../../test.asm")).unwrap();
+        assert_eq!(
+            bytes,
+            vec![
+                0xBD, 0x00, 0x01, 0xBA, 0x00, 0x00, 0xB9, 0x00, 0x00, 0x89, 0x4E, 0x00, 0x89, 0x56,
+                0x02, 0xC6, 0x46, 0x03, 0xFF, 0x83, 0xC5, 0x04, 0x83, 0xC1, 0x01, 0x83, 0xF9, 0x40,
+                0x75, 0xEB, 0x83, 0xC2, 0x01, 0x83, 0xFA, 0x40, 0x75, 0xE0,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_assemble_disassembly() {
+        let bytes = std::fs::read("test").unwrap();
+        let src = decode(bytes.clone())
+            .iter()
+            .map(|instr| instr.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(assemble(&src), Ok(bytes));
+    }
+
+    #[test]
+    fn test_assemble_mov_forms() {
+        assert_assembles("mov cx, bx", vec![0x89, 0xD9]);
+        assert_assembles("mov dl, [bx + si]", vec![0x8A, 0x10]);
+        assert_assembles("mov [bp + di - 300], cx", vec![0x89, 0x8B, 0xD4, 0xFE]);
+        assert_assembles("mov ax, [2555]", vec![0xA1, 0xFB, 0x09]);
+        assert_assembles("mov [15], al", vec![0xA2, 0x0F, 0x00]);
+        assert_assembles("mov bx, [1000]", vec![0x8B, 0x1E, 0xE8, 0x03]);
+        assert_assembles("mov cl, -12", vec![0xB1, 0xF4]);
+        assert_assembles("mov [bx + di], byte 7", vec![0xC6, 0x01, 0x07]);
+        assert_assembles(
+            "mov word [1000], 1",
+            vec![0xC7, 0x06, 0xE8, 0x03, 0x01, 0x00],
+        );
+    }
+
+    #[test]
+    fn test_assemble_op_forms() {
+        assert_assembles("add bx, [bx + si]", vec![0x03, 0x18]);
+        assert_assembles("sub [bp], al", vec![0x28, 0x46, 0x00]);
+        assert_assembles("add al, 34", vec![0x04, 0x22]);
+        assert_assembles("add ax, 1000", vec![0x05, 0xE8, 0x03]);
+        assert_assembles("cmp ax, -2", vec![0x83, 0xF8, 0xFE]);
+        assert_assembles("add si, 2", vec![0x83, 0xC6, 0x02]);
+        assert_assembles("sub bl, 1", vec![0x80, 0xEB, 0x01]);
+        assert_assembles("cmp word [4834], 29", vec![0x83, 0x3E, 0xE2, 0x12, 0x1D]);
+        assert_assembles("add byte [bx], 34", vec![0x80, 0x07, 0x22]);
+        assert_assembles("sub dx, 1000", vec![0x81, 0xEA, 0xE8, 0x03]);
+    }
+
+    #[test]
+    fn test_assemble_jumps() {
+        assert_assembles(
+            "start:\nje start\njnz end\nadd cx, 1\nend:",
+            vec![0x74, 0xFE, 0x75, 0x03, 0x83, 0xC1, 0x01],
+        );
+        assert_assembles("jne $-6", vec![0x75, 0xF8]);
+    }
+
+    #[test]
+    fn test_assemble_jmp_call_ret() {
+        assert_assembles(
+            "call func\njmp end\nfunc:\nret\nend:",
+            vec![0xE8, 0x02, 0x00, 0xEB, 0x01, 0xC3],
+        );
+        assert_assembles("jmp near $+3", vec![0xE9, 0x00, 0x00]);
+        assert_assembles("jmp short $", vec![0xEB, 0xFE]);
+        assert_assembles("jmp $+1000", vec![0xE9, 0xE5, 0x03]);
+        assert_assembles("call $-297", vec![0xE8, 0xD4, 0xFE]);
+        assert_assembles("hlt", vec![0xF4]);
+        assert_assembles("int 0x21", vec![0xCD, 0x21]);
+    }
+
+    #[test]
+    fn test_assemble_prefixes() {
+        assert_assembles("rep movsb", vec![0xF3, 0xA4]);
+        assert_assembles("repne scasw", vec![0xF2, 0xAF]);
+        assert_assembles("repz cmpsb", vec![0xF3, 0xA6]);
+        assert_assembles("es lodsb", vec![0x26, 0xAC]);
+        assert_assembles("mov ax, [es:bx + si + 4]", vec![0x26, 0x8B, 0x40, 0x04]);
+        assert_assembles("mov [cs:1000], al", vec![0x2E, 0xA2, 0xE8, 0x03]);
+        assert_assembles(
+            "lock add [ss:bp], byte 1",
+            vec![0xF0, 0x36, 0x80, 0x46, 0x00, 0x01],
+        );
+        assert_assembles("nop\nstosw", vec![0x90, 0xAB]);
+    }
+
+    #[test]
+    fn test_assemble_xchg() {
+        assert_assembles("xchg ax, cx", vec![0x91]);
+        assert_assembles("xchg dx, ax", vec![0x92]);
+        assert_assembles("xchg cx, dx", vec![0x87, 0xCA]);
+        assert_assembles("xchg al, bl", vec![0x86, 0xC3]);
+        assert_assembles("xchg [bx + di], cl", vec![0x86, 0x09]);
+        assert_assembles("lock xchg ax, [ds:bx]", vec![0xF0, 0x3E, 0x87, 0x07]);
+    }
+
+    #[test]
+    fn test_assemble_forward_label_size() {
+        assert_assembles("mov cx, [bx + far]\nfar:", vec![0x8B, 0x4F, 0x03]);
+    }
+
+    #[test]
+    fn test_assemble_data() {
+        assert_assembles(
+            "db 0x8f, -1\ndw 1000, end\nend:",
+            vec![0x8F, 0xFF, 0xE8, 0x03, 0x06, 0x00],
+        );
+    }
+
+    #[test]
+    fn test_assemble_errors() {
+        assert_error("bits 32", 1, "unsupported bits 32, expected 16");
+        assert_error("bits 16\nfoo ax, bx", 2, "unknown instruction 'foo'");
+        assert_error("mov [bx], 1", 1, "operation size not specified");
+        assert_error("mov ax, bl", 1, "mismatch in operand sizes");
+        assert_error("mov byte [bx], 256", 1, "immediate out of range: 256");
+        assert_error("jne nowhere", 1, "undefined label 'nowhere'");
+        assert_error("a:\na:", 2, "label 'a' redefined");
+        assert_error("mov 1, ax", 1, "invalid combination of operands");
+        assert_error("add ax", 1, "expected 2 operands, got 1");
+        assert_error("mov ax, [bx", 1, "expected ']'");
+        assert_error("je 300", 1, "jump out of range: 298");
+        assert_error("db 256", 1, "immediate out of range: 256");
+        assert_error("jmp short $+200", 1, "jump out of range: 198");
+        assert_error("ret 1", 1, "expected no operands");
+        assert_error("movsb [si]", 1, "expected no operands");
+        assert_error("xchg ax, 1", 1, "invalid combination of operands");
+        // The displacement only fits in a byte when the long form is used
+        assert_error(
+            "mov cx, [bx + 140 - 4 * x]\nx:",
+            2,
+            "label 'x' didn't settle after 16 passes",
+        );
+    }
+}
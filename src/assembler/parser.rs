@@ -0,0 +1,429 @@
+use crate::{
+    assembler::{
+        expr::{parse_expr, parse_term, Expr},
+        lexer::Token,
+    },
+    decoder::{
+        loc::{eac_mode::EffectiveAddressMode, Size},
+        mov::{AH, AL, AX, BH, BL, BP, BX, CH, CL, CX, DH, DI, DL, DX, SI, SP},
+        prefix::{Prefix, Rep, Segment},
+    },
+};
+
+#[derive(Debug, PartialEq)]
+pub struct Line {
+    pub label: Option<String>,
+    pub statement: Option<Statement>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Statement {
+    Bits(Expr),
+    Data {
+        size: Size,
+        values: Vec<Expr>,
+    },
+    Instr {
+        prefixes: Vec<Prefix>,
+        mnemonic: String,
+        operands: Vec<Operand>,
+    },
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Operand {
+    pub size: Option<Size>,
+    pub distance: Option<Distance>,
+    pub kind: OperandKind,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Distance {
+    Short,
+    Near,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum OperandKind {
+    Reg(&'static str),
+    Mem {
+        segment: Option<Segment>,
+        mode: Option<EffectiveAddressMode>,
+        disp: Option<Expr>,
+    },
+    Imm(Expr),
+}
+
+pub fn parse_line(tokens: &[Token]) -> Result<Line, String> {
+    let mut pos = 0;
+    let label = match tokens {
+        [Token::Ident(name), Token::Symbol(':'), ..] => {
+            pos = 2;
+            Some(name.clone())
+        }
+        _ => None,
+    };
+    let mut prefixes = vec![];
+    while let Some(prefix) = tokens.get(pos).and_then(parse_prefix) {
+        prefixes.push(prefix);
+        pos += 1;
+    }
+    let statement = match tokens.get(pos) {
+        None if !prefixes.is_empty() => return Err("expected instruction after prefix".to_string()),
+        None => None,
+        Some(Token::Ident(name)) => {
+            pos += 1;
+            Some(parse_statement(name, prefixes, tokens, &mut pos)?)
+        }
+        Some(token) => return Err(format!("expected instruction, got {:?}", token)),
+    };
+    if let Some(token) = tokens.get(pos) {
+        return Err(format!("unexpected {:?} at end of line", token));
+    }
+    Ok(Line { label, statement })
+}
+
+fn parse_statement(
+    name: &str,
+    prefixes: Vec<Prefix>,
+    tokens: &[Token],
+    pos: &mut usize,
+) -> Result<Statement, String> {
+    let mnemonic = name.to_ascii_lowercase();
+    if !prefixes.is_empty() && matches!(mnemonic.as_str(), "bits" | "db" | "dw") {
+        return Err(format!("'{}' can't take a prefix", mnemonic));
+    }
+    let data_size = match mnemonic.as_str() {
+        "bits" => return Ok(Statement::Bits(parse_expr(tokens, pos)?)),
+        "db" => Some(Size::Byte),
+        "dw" => Some(Size::Word),
+        _ => None,
+    };
+    if let Some(size) = data_size {
+        let mut values = vec![parse_expr(tokens, pos)?];
+        while let Some(Token::Symbol(',')) = tokens.get(*pos) {
+            *pos += 1;
+            values.push(parse_expr(tokens, pos)?);
+        }
+        return Ok(Statement::Data { size, values });
+    }
+    let mut operands = vec![];
+    if *pos < tokens.len() {
+        operands.push(parse_operand(tokens, pos)?);
+        while let Some(Token::Symbol(',')) = tokens.get(*pos) {
+            *pos += 1;
+            operands.push(parse_operand(tokens, pos)?);
+        }
+    }
+    Ok(Statement::Instr {
+        prefixes,
+        mnemonic,
+        operands,
+    })
+}
+
+fn parse_prefix(token: &Token) -> Option<Prefix> {
+    let Token::Ident(name) = token else {
+        return None;
+    };
+    let prefix = match name.to_ascii_lowercase().as_str() {
+        "lock" => Prefix::Lock,
+        "rep" | "repe" | "repz" => Prefix::Rep(Rep::Repe),
+        "repne" | "repnz" => Prefix::Rep(Rep::Repne),
+        name => Prefix::Segment(parse_segment(name)?),
+    };
+    Some(prefix)
+}
+
+fn parse_segment(name: &str) -> Option<Segment> {
+    match name.to_ascii_lowercase().as_str() {
+        "es" => Some(Segment::Es),
+        "cs" => Some(Segment::Cs),
+        "ss" => Some(Segment::Ss),
+        "ds" => Some(Segment::Ds),
+        _ => None,
+    }
+}
+
+fn parse_operand(tokens: &[Token], pos: &mut usize) -> Result<Operand, String> {
+    let size = match tokens.get(*pos) {
+        Some(Token::Ident(name)) => match name.to_ascii_lowercase().as_str() {
+            "byte" => Some(Size::Byte),
+            "word" => Some(Size::Word),
+            _ => None,
+        },
+        _ => None,
+    };
+    if size.is_some() {
+        *pos += 1;
+    }
+    let distance = match tokens.get(*pos) {
+        Some(Token::Ident(name)) => match name.to_ascii_lowercase().as_str() {
+            "short" => Some(Distance::Short),
+            "near" => Some(Distance::Near),
+            _ => None,
+        },
+        _ => None,
+    };
+    if distance.is_some() {
+        *pos += 1;
+    }
+    let kind = match tokens.get(*pos) {
+        Some(Token::Symbol('[')) => {
+            *pos += 1;
+            parse_mem(tokens, pos)?
+        }
+        Some(Token::Ident(name)) if parse_reg(name).is_some() => {
+            *pos += 1;
+            OperandKind::Reg(parse_reg(name).unwrap())
+        }
+        _ => OperandKind::Imm(parse_expr(tokens, pos)?),
+    };
+    Ok(Operand {
+        size,
+        distance,
+        kind,
+    })
+}
+
+fn parse_mem(tokens: &[Token], pos: &mut usize) -> Result<OperandKind, String> {
+    let segment = match tokens.get(*pos..*pos + 2) {
+        Some([Token::Ident(name), Token::Symbol(':')]) => {
+            let segment =
+                parse_segment(name).ok_or_else(|| format!("invalid segment register: {}", name))?;
+            *pos += 2;
+            Some(segment)
+        }
+        _ => None,
+    };
+    let mut regs = vec![];
+    let mut disp: Option<Expr> = None;
+    let mut sign = '+';
+    if let Some(Token::Symbol('-')) = tokens.get(*pos) {
+        sign = '-';
+        *pos += 1;
+    }
+    loop {
+        match tokens.get(*pos) {
+            Some(Token::Ident(name)) if sign == '+' && parse_base_reg(name).is_some() => {
+                regs.push(parse_base_reg(name).unwrap());
+                *pos += 1;
+            }
+            _ => {
+                let term = parse_term(tokens, pos)?;
+                disp = Some(match (disp, sign) {
+                    (None, '+') => term,
+                    (None, _) => Expr::Neg(Box::new(term)),
+                    (Some(left), op) => Expr::Binary(op, Box::new(left), Box::new(term)),
+                });
+            }
+        }
+        match tokens.get(*pos) {
+            Some(Token::Symbol(c @ ('+' | '-'))) => {
+                sign = *c;
+                *pos += 1;
+            }
+            Some(Token::Symbol(']')) => {
+                *pos += 1;
+                break;
+            }
+            _ => return Err("expected ']'".to_string()),
+        }
+    }
+    let mode = match regs.as_slice() {
+        [] => None,
+        [BX] => Some(EffectiveAddressMode::Bx),
+        [BP] => Some(EffectiveAddressMode::Bp),
+        [SI] => Some(EffectiveAddressMode::Si),
+        [DI] => Some(EffectiveAddressMode::Di),
+        [BX, SI] | [SI, BX] => Some(EffectiveAddressMode::BxSi),
+        [BX, DI] | [DI, BX] => Some(EffectiveAddressMode::BxDi),
+        [BP, SI] | [SI, BP] => Some(EffectiveAddressMode::BpSi),
+        [BP, DI] | [DI, BP] => Some(EffectiveAddressMode::BpDi),
+        _ => return Err(format!("invalid effective address: {}", regs.join(" + "))),
+    };
+    Ok(OperandKind::Mem {
+        segment,
+        mode,
+        disp,
+    })
+}
+
+fn parse_base_reg(name: &str) -> Option<&'static str> {
+    match name.to_ascii_lowercase().as_str() {
+        "bx" => Some(BX),
+        "bp" => Some(BP),
+        "si" => Some(SI),
+        "di" => Some(DI),
+        _ => None,
+    }
+}
+
+pub fn parse_reg(name: &str) -> Option<&'static str> {
+    let reg = match name.to_ascii_lowercase().as_str() {
+        "ax" => AX,
+        "cx" => CX,
+        "dx" => DX,
+        "bx" => BX,
+        "sp" => SP,
+        "bp" => BP,
+        "si" => SI,
+        "di" => DI,
+        "al" => AL,
+        "cl" => CL,
+        "dl" => DL,
+        "bl" => BL,
+        "ah" => AH,
+        "ch" => CH,
+        "dh" => DH,
+        "bh" => BH,
+        _ => return None,
+    };
+    Some(reg)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        assembler::{
+            expr::Expr,
+            lexer::tokenize,
+            parser::{parse_line, Line, Operand, OperandKind, Statement},
+        },
+        decoder::{
+            loc::{eac_mode::EffectiveAddressMode, Size},
+            mov::{BX, CX},
+            prefix::{Prefix, Rep, Segment},
+        },
+    };
+
+    fn parse(src: &str) -> Result<Line, String> {
+        parse_line(&tokenize(src).unwrap())
+    }
+
+    #[test]
+    fn test_parse_label_only() {
+        assert_eq!(
+            parse("y_loop_start:"),
+            Ok(Line {
+                label: Some("y_loop_start".to_string()),
+                statement: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_mem_operand() {
+        assert_eq!(
+            parse("mov word [bp + 0], cx"),
+            Ok(Line {
+                label: None,
+                statement: Some(Statement::Instr {
+                    prefixes: vec![],
+                    mnemonic: "mov".to_string(),
+                    operands: vec![
+                        Operand {
+                            size: Some(Size::Word),
+                            distance: None,
+                            kind: OperandKind::Mem {
+                                segment: None,
+                                mode: Some(EffectiveAddressMode::Bp),
+                                disp: Some(Expr::Num(0)),
+                            },
+                        },
+                        Operand {
+                            size: None,
+                            distance: None,
+                            kind: OperandKind::Reg(CX),
+                        },
+                    ],
+                }),
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_negative_disp() {
+        let line = parse("mov bx, [si + bx - 2]").unwrap();
+        let Some(Statement::Instr { operands, .. }) = line.statement else {
+            panic!("expected instruction")
+        };
+        assert_eq!(operands[0].kind, OperandKind::Reg(BX));
+        assert_eq!(
+            operands[1].kind,
+            OperandKind::Mem {
+                segment: None,
+                mode: Some(EffectiveAddressMode::BxSi),
+                disp: Some(Expr::Neg(Box::new(Expr::Num(2)))),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_data() {
+        assert_eq!(
+            parse("db 0x8f, 2 ; unknown"),
+            Ok(Line {
+                label: None,
+                statement: Some(Statement::Data {
+                    size: Size::Byte,
+                    values: vec![Expr::Num(0x8f), Expr::Num(2)],
+                }),
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_prefixes() {
+        let line = parse("lock xchg [es:bx], ax").unwrap();
+        let Some(Statement::Instr {
+            prefixes, operands, ..
+        }) = line.statement
+        else {
+            panic!("expected instruction")
+        };
+        assert_eq!(prefixes, vec![Prefix::Lock]);
+        assert_eq!(
+            operands[0].kind,
+            OperandKind::Mem {
+                segment: Some(Segment::Es),
+                mode: Some(EffectiveAddressMode::Bx),
+                disp: None,
+            }
+        );
+
+        let line = parse("es repne scasb").unwrap();
+        let Some(Statement::Instr { prefixes, .. }) = line.statement else {
+            panic!("expected instruction")
+        };
+        assert_eq!(
+            prefixes,
+            vec![Prefix::Segment(Segment::Es), Prefix::Rep(Rep::Repne)]
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            parse("mov ax, [bx + bp]"),
+            Err("invalid effective address: bx + bp".to_string())
+        );
+        assert_eq!(parse("mov ax, [bx"), Err("expected ']'".to_string()));
+        assert_eq!(
+            parse("mov ax, [fs:bx]"),
+            Err("invalid segment register: fs".to_string())
+        );
+        assert_eq!(
+            parse("rep"),
+            Err("expected instruction after prefix".to_string())
+        );
+        assert_eq!(
+            parse("lock db 1"),
+            Err("'db' can't take a prefix".to_string())
+        );
+        assert_eq!(
+            parse("mov ax, bx bx"),
+            Err("unexpected Ident(\"bx\") at end of line".to_string())
+        );
+    }
+}
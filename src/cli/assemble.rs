@@ -0,0 +1,14 @@
+use std::path::PathBuf;
+
+use rusty_8086::assembler::assemble;
+
+pub fn assemble_file(path: &PathBuf, output: &PathBuf) {
+    let src = std::fs::read_to_string(path).unwrap();
+    match assemble(&src) {
+        Ok(bytes) => std::fs::write(output, bytes).unwrap(),
+        Err(err) => {
+            eprintln!("{}:{}: {}", path.to_string_lossy(), err.line, err.message);
+            std::process::exit(1);
+        }
+    }
+}
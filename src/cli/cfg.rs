@@ -0,0 +1,11 @@
+use std::path::PathBuf;
+
+use rusty_8086::decoder::{cfg::Cfg, traverse::decode_recursive};
+
+pub fn cfg(path: &PathBuf, entry: &[usize]) {
+    let bytes = std::fs::read(path).unwrap();
+    let mut entries = vec![0];
+    entries.extend(entry);
+    let cfg = Cfg::new(decode_recursive(bytes, &entries));
+    print!("{}", cfg.to_dot());
+}
@@ -0,0 +1,34 @@
+use std::io::{BufRead, Write};
+
+use rusty_8086::debugger::{Debugger, PROMPT};
+
+use crate::cli::{load::load_program, DebugArgs};
+
+pub fn debug(args: &DebugArgs) {
+    let mut state = load_program(&args.path, &args.load);
+    state.set_history_budget(args.history);
+    let mut debugger = Debugger::new(state);
+    let mut out = std::io::stdout().lock();
+    if let Some(script) = &args.script {
+        let script = match std::fs::read_to_string(script) {
+            Ok(script) => script,
+            Err(err) => {
+                eprintln!("{}: {}", script.to_string_lossy(), err);
+                std::process::exit(1);
+            }
+        };
+        debugger.run_script(&script, &mut out).unwrap();
+        return;
+    }
+    let mut lines = std::io::stdin().lock().lines();
+    loop {
+        write!(out, "{}", PROMPT).unwrap();
+        out.flush().unwrap();
+        let Some(line) = lines.next() else {
+            break;
+        };
+        if !debugger.execute_line(&line.unwrap(), &mut out).unwrap() {
+            break;
+        }
+    }
+}
@@ -1,13 +1,60 @@
-use std::path::PathBuf;
+use rusty_8086::{
+    decoder::{
+        count_data_bytes, decode_items, format::Formatter, json::item_json,
+        listing::format_listing, traverse::decode_recursive,
+    },
+    json::Json,
+};
 
-use crate::decoder::decode;
+use crate::cli::{DisassembleArgs, OutputFormat};
 
-pub fn disassemble(path: &PathBuf) {
-    let bytes = std::fs::read(path).unwrap();
-    let found = decode(bytes);
+pub fn disassemble(args: &DisassembleArgs) {
+    let bytes = std::fs::read(&args.path).unwrap();
+    let items = if args.recursive {
+        let mut entries = vec![0];
+        entries.extend(&args.entry);
+        decode_recursive(bytes.clone(), &entries)
+    } else {
+        decode_items(bytes.clone())
+    };
+    if args.format == OutputFormat::Json {
+        let instructions = items.iter().map(|item| item_json(&bytes, item)).collect();
+        println!(
+            "{}",
+            Json::object([("instructions", Json::Array(instructions))])
+        );
+        return;
+    }
+    let formatter = Formatter::new(args.syntax)
+        .radix(args.radix)
+        .case(args.case);
+    if args.listing {
+        print!(
+            "{}",
+            format_listing(&bytes, &items, args.fields, &formatter)
+        );
+    } else {
+        println!("{}", formatter.header());
+        for item in &items {
+            println!("{}", formatter.item(item));
+        }
+    }
 
-    println!("bits 16");
-    for instr in found {
-        println!("{}", instr);
+    let unknown = count_data_bytes(&items);
+    if unknown > 0 {
+        println!(
+            "{} {} of {} bytes treated as data",
+            formatter.comment(),
+            unknown,
+            bytes.len()
+        );
     }
 }
+
+pub fn parse_addr(value: &str) -> Result<usize, String> {
+    let parsed = match value.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+    parsed.map_err(|err| format!("invalid address '{}': {}", value, err))
+}
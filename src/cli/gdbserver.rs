@@ -0,0 +1,24 @@
+use std::{io::BufReader, net::TcpListener};
+
+use rusty_8086::gdb::GdbServer;
+
+use crate::cli::{load::load_program, GdbServerArgs};
+
+pub fn gdbserver(args: &GdbServerArgs) {
+    let state = load_program(&args.path, &args.load);
+    let listener = match TcpListener::bind(("127.0.0.1", args.port)) {
+        Ok(listener) => listener,
+        Err(err) => {
+            eprintln!("can't listen on port {}: {}", args.port, err);
+            std::process::exit(1);
+        }
+    };
+    eprintln!("listening on 127.0.0.1:{}", args.port);
+    let (stream, addr) = listener.accept().unwrap();
+    eprintln!("gdb connected from {}", addr);
+    let input = BufReader::new(stream.try_clone().unwrap());
+    if let Err(err) = GdbServer::new(state).serve(input, stream) {
+        eprintln!("connection lost: {}", err);
+        std::process::exit(1);
+    }
+}
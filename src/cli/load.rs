@@ -0,0 +1,24 @@
+use std::path::Path;
+
+use rusty_8086::sim::{load::LoadOptions, SimState};
+
+use crate::cli::LoadArgs;
+
+/// Reads and loads the program at `path`, exiting on an error
+pub fn load_program(path: &Path, args: &LoadArgs) -> SimState {
+    let options = LoadOptions {
+        format: args.program_format,
+        segment: args.segment,
+        args: args.args.clone(),
+    };
+    let loaded = std::fs::read(path)
+        .map_err(|err| err.to_string())
+        .and_then(|bytes| SimState::load(&bytes, &options).map_err(|err| err.to_string()));
+    match loaded {
+        Ok(state) => state,
+        Err(err) => {
+            eprintln!("{}: {}", path.to_string_lossy(), err);
+            std::process::exit(1);
+        }
+    }
+}
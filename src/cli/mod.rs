@@ -1,44 +1,205 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, str::FromStr};
 
 use clap::{Args, Parser};
+use rusty_8086::{
+    debugger::command::parse_number,
+    decoder::format::{Case, Radix, Syntax},
+    sim::{
+        bus::TimingMode,
+        load::{ProgramFormat, DEFAULT_LOAD_SEGMENT},
+        timing::Cpu,
+    },
+};
 
-use crate::cli::disassemble::disassemble;
+use crate::cli::{
+    assemble::assemble_file,
+    cfg::cfg,
+    debug::debug,
+    disassemble::{disassemble, parse_addr},
+    gdbserver::gdbserver,
+    verify::verify_file,
+};
 use bytes::bytes;
 
 use self::sim::sim;
 
+mod assemble;
 mod bytes;
+mod cfg;
+mod debug;
 mod disassemble;
+mod gdbserver;
+mod load;
 mod sim;
+mod verify;
 
 #[derive(Parser)]
 #[clap(name = "8085 Sim")]
 pub enum Command {
-    Disassemble { path: PathBuf },
-    Bytes { path: PathBuf },
+    Disassemble(DisassembleArgs),
+    Bytes {
+        path: PathBuf,
+    },
+    Assemble(AssembleArgs),
+    Verify {
+        path: PathBuf,
+    },
+    Cfg(CfgArgs),
     Sim(SimArgs),
+    /// Step through a program at a prompt, or from a script of commands
+    Debug(DebugArgs),
+    /// Serve gdb's remote protocol on a local port, for `target remote`
+    Gdbserver(GdbServerArgs),
 }
 
 impl Command {
     pub fn run(&self) {
         match self {
-            Command::Disassemble { path } => disassemble(path),
+            Command::Disassemble(args) => disassemble(args),
             Command::Bytes { path } => bytes(path),
-            Command::Sim(SimArgs {
-                path,
-                output,
-                trace,
-            }) => sim(path, output, *trace),
+            Command::Assemble(AssembleArgs { path, output }) => assemble_file(path, output),
+            Command::Verify { path } => verify_file(path),
+            Command::Cfg(CfgArgs { path, entry }) => cfg(path, entry),
+            Command::Sim(args) => sim(args),
+            Command::Debug(args) => debug(args),
+            Command::Gdbserver(args) => gdbserver(args),
         }
     }
 }
 
 #[derive(Args)]
-pub struct SimArgs {
+pub struct DisassembleArgs {
+    pub path: PathBuf,
+    #[clap(short, long)]
+    pub listing: bool,
+    #[clap(short, long, requires = "listing")]
+    pub fields: bool,
+    /// Only decode bytes reachable from the entry points
+    #[clap(short, long)]
+    pub recursive: bool,
+    /// Extra entry point for recursive disassembly, e.g. 0x1a
+    #[clap(short, long, requires = "recursive", value_parser = parse_addr)]
+    pub entry: Vec<usize>,
+    /// Output syntax: nasm, masm or att
+    #[clap(short, long, default_value = "nasm")]
+    pub syntax: Syntax,
+    /// Immediates and displacements in dec or hex
+    #[clap(long, default_value = "dec")]
+    pub radix: Radix,
+    /// Mnemonics and registers in lower or upper case
+    #[clap(long, default_value = "lower")]
+    pub case: Case,
+    /// Print text or one JSON document
+    #[clap(long, default_value = "text")]
+    pub format: OutputFormat,
+}
+
+#[derive(Args)]
+pub struct CfgArgs {
+    pub path: PathBuf,
+    /// Extra entry point to follow besides 0, e.g. 0x1a
+    #[clap(short, long, value_parser = parse_addr)]
+    pub entry: Vec<usize>,
+}
+
+#[derive(Args)]
+pub struct DebugArgs {
     #[clap(short, long)]
     pub path: PathBuf,
+    /// Run the commands in this file instead of prompting, one per line
+    #[clap(short, long)]
+    pub script: Option<PathBuf>,
+    /// How many instructions to remember for back and reverse-continue, 0
+    /// turns recording off
+    #[clap(long, default_value = "10000")]
+    pub history: usize,
+    #[clap(flatten)]
+    pub load: LoadArgs,
+}
+
+#[derive(Args)]
+pub struct GdbServerArgs {
+    #[clap(short, long)]
+    pub path: PathBuf,
+    #[clap(long, default_value = "1234")]
+    pub port: u16,
+    #[clap(flatten)]
+    pub load: LoadArgs,
+}
+
+#[derive(Args)]
+pub struct LoadArgs {
+    /// How the program file is laid out: raw, loaded at 0, or a DOS com or
+    /// exe
+    #[clap(long, default_value = "raw")]
+    pub program_format: ProgramFormat,
+    /// Segment a DOS program's PSP is loaded at
+    #[clap(long, default_value_t = DEFAULT_LOAD_SEGMENT, value_parser = parse_number)]
+    pub segment: u16,
+    /// Command line passed to a DOS program in its PSP
+    #[clap(long, default_value = "", allow_hyphen_values = true)]
+    pub args: String,
+}
+
+#[derive(Args)]
+pub struct AssembleArgs {
+    pub path: PathBuf,
+    #[clap(short, long)]
+    pub output: PathBuf,
+}
+
+#[derive(Args)]
+pub struct SimArgs {
+    #[clap(short, long, required_unless_present = "load_snapshot")]
+    pub path: Option<PathBuf>,
+    /// Write the 64K of memory at ds:0 here once the program stops
     #[clap(short, long)]
     pub output: Option<PathBuf>,
     #[clap(short, long)]
     pub trace: bool,
+    /// Stop after executing this many instructions
+    #[clap(long)]
+    pub max_steps: Option<u64>,
+    /// Stop once the estimated clock count reaches this
+    #[clap(long)]
+    pub max_cycles: Option<u64>,
+    /// Model whose bus timing the clock estimates follow: 8086 or 8088
+    #[clap(long, default_value = "8086")]
+    pub cpu: Cpu,
+    /// Clock counts from the timing tables alone, or also the clocks spent
+    /// waiting on the prefetch queue and bus: table or prefetch
+    #[clap(long, default_value = "table")]
+    pub timing: TimingMode,
+    /// Stop once the whole machine state repeats
+    #[clap(long)]
+    pub detect_loops: bool,
+    /// Print text or one JSON document
+    #[clap(long, default_value = "text")]
+    pub format: OutputFormat,
+    /// Resume from a machine snapshot instead of loading a program
+    #[clap(long, conflicts_with = "path")]
+    pub load_snapshot: Option<PathBuf>,
+    /// Save a machine snapshot once the program stops, even on an error
+    #[clap(long)]
+    pub save_snapshot: Option<PathBuf>,
+    #[clap(flatten)]
+    pub load: LoadArgs,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(format!("unknown format '{}', expected text or json", s)),
+        }
+    }
 }
@@ -1,21 +1,118 @@
-use std::path::PathBuf;
+use rusty_8086::{
+    json::Json,
+    sim::{error::SimError, limits::Limits, step::StopReason, SimState},
+};
 
-use crate::sim::SimState;
+use crate::cli::{load::load_program, OutputFormat, SimArgs};
 
-pub fn sim(path: &PathBuf, output: &Option<PathBuf>, trace: bool) {
-    let bytes = std::fs::read(path).unwrap();
-    let mut state = SimState::new(bytes);
+pub fn sim(args: &SimArgs) {
+    let mut state = load(args);
+    state.set_cpu(args.cpu);
+    state.set_timing(args.timing);
+    state.set_limits(Limits {
+        max_steps: args.max_steps,
+        max_cycles: args.max_cycles,
+        detect_loops: args.detect_loops,
+    });
+    if args.format == OutputFormat::Json {
+        sim_json(args, state);
+        return;
+    }
     println!("start");
     println!("{}", state);
-    if trace {
-        state.run_trace();
+    let result = if args.trace {
+        state.run_trace()
     } else {
-        state.run();
+        state.run()
+    };
+    save_snapshot(args, &state);
+    let stop = match result {
+        Ok(stop) => stop,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            eprint!("{}", state);
+            eprintln!("ip: {:04x}", state.ip());
+            eprintln!("flags: {}", state.flags().letters());
+            std::process::exit(1);
+        }
+    };
+    if stop.is_limit() {
+        eprintln!("stopped: {} after {} steps", stop, state.steps());
     }
     println!("end");
     println!("{}", state);
-    if let Some(output) = output {
+    println!("clocks: {}", state.cycles());
+    if let Some(output) = &args.output {
         println!("Writing memory to {}", output.to_string_lossy());
         state.write_memory(output);
     }
+    if stop.is_limit() {
+        std::process::exit(1);
+    }
+}
+
+/// Loads the program, or restores the snapshot, exiting on an error
+fn load(args: &SimArgs) -> SimState {
+    if let Some(path) = &args.load_snapshot {
+        let mut state = SimState::default();
+        let restored = std::fs::read(path)
+            .map_err(|err| err.to_string())
+            .and_then(|snapshot| state.restore(&snapshot).map_err(|err| err.to_string()));
+        if let Err(err) = restored {
+            eprintln!("{}: {}", path.to_string_lossy(), err);
+            std::process::exit(1);
+        }
+        return state;
+    }
+    let path = args
+        .path
+        .as_ref()
+        .expect("clap requires a path without a snapshot");
+    load_program(path, &args.load)
+}
+
+fn save_snapshot(args: &SimArgs, state: &SimState) {
+    let Some(path) = &args.save_snapshot else {
+        return;
+    };
+    if let Err(err) = std::fs::write(path, state.snapshot()) {
+        eprintln!("{}: {}", path.to_string_lossy(), err);
+        std::process::exit(1);
+    }
+}
+
+fn sim_json(args: &SimArgs, mut state: SimState) {
+    let initial = state.state_json();
+    let mut fields = vec![("initial".to_string(), initial)];
+    let result = if args.trace {
+        let mut steps = vec![];
+        let result = trace_json(&mut state, &mut steps);
+        fields.push(("trace".to_string(), Json::Array(steps)));
+        result
+    } else {
+        state.run()
+    };
+    match &result {
+        Ok(stop) => fields.push(("stop".to_string(), stop.to_string().into())),
+        Err(err) => fields.push(("error".to_string(), err.to_string().into())),
+    }
+    fields.push(("final".to_string(), state.state_json()));
+    save_snapshot(args, &state);
+    if let Some(output) = &args.output {
+        state.write_memory(output);
+    }
+    println!("{}", Json::Object(fields));
+    if result.map_or(true, |stop| stop.is_limit()) {
+        std::process::exit(1);
+    }
+}
+
+fn trace_json(state: &mut SimState, steps: &mut Vec<Json>) -> Result<StopReason, SimError> {
+    loop {
+        let result = state.step()?;
+        steps.extend(result.executed.map(|step| step.to_json()));
+        if let Some(stop) = result.stop {
+            return Ok(stop);
+        }
+    }
 }
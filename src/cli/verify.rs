@@ -0,0 +1,14 @@
+use std::path::PathBuf;
+
+use rusty_8086::verify::verify;
+
+pub fn verify_file(path: &PathBuf) {
+    let bytes = std::fs::read(path).unwrap();
+    match verify(&bytes) {
+        Ok(count) => println!("ok: {} lines, {} bytes", count, bytes.len()),
+        Err(err) => {
+            eprintln!("{}: {}", path.to_string_lossy(), err);
+            std::process::exit(1);
+        }
+    }
+}
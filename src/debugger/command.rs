@@ -0,0 +1,268 @@
+use std::str::FromStr;
+
+use crate::{
+    decoder::loc::Size,
+    sim::{condition::Condition, watch::WatchKind},
+};
+
+/// How `x` shows each unit of memory
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Radix {
+    Hex,
+    Dec,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Command {
+    /// Executes this many instructions, printing what each changed
+    Step(u32),
+    /// Steps, but runs a call until it returns
+    Next,
+    Continue,
+    /// Undoes this many instructions
+    Back(u32),
+    /// Runs backwards to the last breakpoint
+    ReverseContinue,
+    /// Finds the instruction that last wrote an address
+    LastWrite(u16),
+    Break {
+        addr: u16,
+        condition: Option<Condition>,
+    },
+    Delete(u16),
+    /// Watches `len` bytes from `addr` in the data segment
+    Watch {
+        addr: u16,
+        len: u16,
+        kind: WatchKind,
+    },
+    /// Removes the watchpoint starting at this address
+    Unwatch(u16),
+    Breakpoints,
+    Registers,
+    /// `x/16xb 0x100` shows 16 bytes in hex from 0x100
+    Examine {
+        addr: u16,
+        count: u16,
+        radix: Radix,
+        size: Size,
+    },
+    /// Sets a 16 or 8 bit register, a segment register or ip
+    Set {
+        reg: String,
+        value: u16,
+    },
+    /// Disassembles this many instructions from ip
+    Disassemble(usize),
+    Help,
+    Quit,
+}
+
+pub const HELP: &str = "\
+step [n]          execute n instructions, default 1
+next              step, running calls until they return
+continue          run until a breakpoint or the program stops
+back [n]          undo n instructions, default 1
+reverse-continue  run backwards until a breakpoint or the start of history
+lastwrite <addr>  show which instruction last wrote addr
+break <addr> [if <condition>]
+                  stop when ip reaches addr, e.g. break 0x12 if cx == 64 && ZF
+delete <addr>     remove the breakpoint at addr
+watch <addr> [n]  stop after an instruction writes any of n bytes from addr
+rwatch <addr> [n] stop after an instruction reads them
+awatch <addr> [n] stop after an instruction reads or writes them
+unwatch <addr>    remove the watchpoint starting at addr
+breakpoints       list breakpoints and watchpoints
+registers         print registers, ip and flags
+x/<n><x|d><b|w> <addr>
+                  examine n bytes or words of memory in hex or decimal
+set <reg> <value> set a register, segment register or ip
+disas [n]         disassemble n instructions from ip, default 5
+help              print this
+quit              stop debugging
+
+Breakpoints are offsets in cs, other addresses are offsets in ds.";
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let name = words.next().unwrap_or_default();
+        let args = words.collect::<Vec<_>>();
+        let command = match (name, args.as_slice()) {
+            ("s" | "step", []) => Command::Step(1),
+            ("s" | "step", [count]) => Command::Step(parse_number(count)? as u32),
+            ("n" | "next", []) => Command::Next,
+            ("c" | "continue", []) => Command::Continue,
+            ("back", []) => Command::Back(1),
+            ("back", [count]) => Command::Back(parse_number(count)? as u32),
+            ("rc" | "reverse-continue", []) => Command::ReverseContinue,
+            ("lastwrite", [addr]) => Command::LastWrite(parse_number(addr)?),
+            ("b" | "break", [addr]) => Command::Break {
+                addr: parse_number(addr)?,
+                condition: None,
+            },
+            ("b" | "break", [addr, "if", condition @ ..]) if !condition.is_empty() => {
+                Command::Break {
+                    addr: parse_number(addr)?,
+                    condition: Some(condition.join(" ").parse()?),
+                }
+            }
+            ("d" | "delete", [addr]) => Command::Delete(parse_number(addr)?),
+            ("watch" | "rwatch" | "awatch", [addr, len @ ..]) if len.len() <= 1 => {
+                let kind = match name {
+                    "watch" => WatchKind::Write,
+                    "rwatch" => WatchKind::Read,
+                    _ => WatchKind::Access,
+                };
+                let len = match len {
+                    [len] => parse_number(len)?,
+                    _ => 1,
+                };
+                if len == 0 {
+                    return Err("a watchpoint needs at least 1 byte".to_string());
+                }
+                Command::Watch {
+                    addr: parse_number(addr)?,
+                    len,
+                    kind,
+                }
+            }
+            ("unwatch", [addr]) => Command::Unwatch(parse_number(addr)?),
+            ("breakpoints", []) => Command::Breakpoints,
+            ("r" | "registers", []) => Command::Registers,
+            ("set", [reg, value]) => Command::Set {
+                reg: reg.to_ascii_lowercase(),
+                value: parse_number(value)?,
+            },
+            ("disas", []) => Command::Disassemble(5),
+            ("disas", [count]) => Command::Disassemble(parse_number(count)? as usize),
+            ("h" | "help", []) => Command::Help,
+            ("q" | "quit", []) => Command::Quit,
+            (examine, [addr]) if examine == "x" || examine.starts_with("x/") => {
+                parse_examine(&examine[1..], parse_number(addr)?)?
+            }
+            _ => {
+                return Err(format!(
+                    "unknown command '{}', try help",
+                    s.split_whitespace().collect::<Vec<_>>().join(" ")
+                ))
+            }
+        };
+        Ok(command)
+    }
+}
+
+/// Parses the `/16xb` after an `x`, defaulting to one byte in hex
+fn parse_examine(spec: &str, addr: u16) -> Result<Command, String> {
+    let spec = spec.strip_prefix('/').unwrap_or(spec);
+    let digits = spec.chars().take_while(char::is_ascii_digit).count();
+    let count = match digits {
+        0 => 1,
+        _ => parse_number(&spec[..digits])?,
+    };
+    let (mut radix, mut size) = (Radix::Hex, Size::Byte);
+    for letter in spec[digits..].chars() {
+        match letter {
+            'x' => radix = Radix::Hex,
+            'd' => radix = Radix::Dec,
+            'b' => size = Size::Byte,
+            'w' => size = Size::Word,
+            _ => {
+                return Err(format!(
+                    "unknown format '{}', expected x, d, b or w",
+                    letter
+                ))
+            }
+        }
+    }
+    Ok(Command::Examine {
+        addr,
+        count,
+        radix,
+        size,
+    })
+}
+
+/// A decimal number, or hex with a 0x prefix
+pub fn parse_number(value: &str) -> Result<u16, String> {
+    let parsed = match value.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+    parsed.map_err(|err| format!("invalid number '{}': {}", value, err))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        debugger::command::{Command, Radix},
+        decoder::loc::Size,
+        sim::watch::WatchKind,
+    };
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!("step 3".parse(), Ok(Command::Step(3)));
+        assert_eq!("s".parse(), Ok(Command::Step(1)));
+        assert_eq!("back 0x10".parse(), Ok(Command::Back(16)));
+        assert_eq!("rc".parse(), Ok(Command::ReverseContinue));
+        assert_eq!("lastwrite 0x100".parse(), Ok(Command::LastWrite(0x100)));
+        assert_eq!(
+            "break 0x1a".parse(),
+            Ok(Command::Break {
+                addr: 0x1a,
+                condition: None
+            })
+        );
+        assert_eq!(
+            "break 0x12 if cx == 64 && ZF".parse(),
+            Ok(Command::Break {
+                addr: 0x12,
+                condition: Some("cx == 64 && ZF".parse().unwrap())
+            })
+        );
+        assert_eq!(
+            "rwatch 0x100 4".parse(),
+            Ok(Command::Watch {
+                addr: 0x100,
+                len: 4,
+                kind: WatchKind::Read
+            })
+        );
+        assert!("watch 0x100 0".parse::<Command>().is_err());
+        assert!("break 0x12 if".parse::<Command>().is_err());
+        assert_eq!(
+            "set AX 0x10".parse(),
+            Ok(Command::Set {
+                reg: "ax".to_string(),
+                value: 0x10
+            })
+        );
+        assert_eq!(
+            "x/16xb 0x100".parse(),
+            Ok(Command::Examine {
+                addr: 0x100,
+                count: 16,
+                radix: Radix::Hex,
+                size: Size::Byte
+            })
+        );
+        assert_eq!(
+            "x/dw 8".parse(),
+            Ok(Command::Examine {
+                addr: 8,
+                count: 1,
+                radix: Radix::Dec,
+                size: Size::Word
+            })
+        );
+        assert_eq!(
+            "jump  1".parse::<Command>(),
+            Err("unknown command 'jump 1', try help".to_string())
+        );
+        assert!("x/4q 0".parse::<Command>().is_err());
+        assert!("break 0x10000".parse::<Command>().is_err());
+    }
+}
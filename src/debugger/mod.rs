@@ -0,0 +1,471 @@
+use std::io::Write;
+
+use crate::{
+    decoder::{
+        decode_item, instr::Instr, loc::Size, prefix::Segment, state::Decoder, state::DecoderState,
+        Item,
+    },
+    sim::{
+        error::SimError, is_byte, step::StopReason, watch::Watchpoint, SimState, SEGMENT_REGISTERS,
+    },
+};
+
+use self::command::{Command, Radix, HELP};
+
+pub mod command;
+
+pub const PROMPT: &str = "(dbg) ";
+
+/// Values shown on each line by `x`
+const EXAMINE_WIDTH: usize = 8;
+
+/// Runs commands against a [SimState], writing what they print to an output
+/// so a script's transcript can be checked
+pub struct Debugger {
+    state: SimState,
+}
+
+impl Debugger {
+    pub fn new(state: SimState) -> Self {
+        Self { state }
+    }
+
+    pub fn state(&self) -> &SimState {
+        &self.state
+    }
+
+    /// Runs every line of `script` as if typed at the prompt, until the end
+    /// or a quit
+    pub fn run_script(&mut self, script: &str, out: &mut impl Write) -> std::io::Result<()> {
+        for line in script.lines() {
+            writeln!(out, "{}{}", PROMPT, line)?;
+            if !self.execute_line(line, out)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses and runs a line, returning false once it quits. Blank lines and
+    /// lines starting with # do nothing.
+    pub fn execute_line(&mut self, line: &str, out: &mut impl Write) -> std::io::Result<bool> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(true);
+        }
+        match line.parse() {
+            Ok(command) => self.execute(command, out),
+            Err(err) => {
+                writeln!(out, "error: {}", err)?;
+                Ok(true)
+            }
+        }
+    }
+
+    pub fn execute(&mut self, command: Command, out: &mut impl Write) -> std::io::Result<bool> {
+        match command {
+            Command::Step(count) => self.step(count, out)?,
+            Command::Next => self.next(out)?,
+            Command::Continue => {
+                let result = self.state.run();
+                self.report(result, out)?;
+                self.show_ip(out)?;
+            }
+            Command::Back(count) => {
+                for _ in 0..count {
+                    if !self.state.step_back() {
+                        writeln!(out, "error: no history to step back through")?;
+                        break;
+                    }
+                }
+                self.show_ip(out)?;
+            }
+            Command::ReverseContinue => {
+                let stop = self.state.reverse_continue();
+                self.report(Ok(stop), out)?;
+                self.show_ip(out)?;
+            }
+            Command::LastWrite(addr) => match self.state.last_write(self.data_addr(addr)) {
+                Some(last) => {
+                    let item = self.decode_at(last.instr_addr);
+                    writeln!(
+                        out,
+                        "{:#06x} last written by {:04x}: {}: {:#x}->{:#x} at step {}",
+                        addr, last.instr_addr, item, last.write.before, last.write.after, last.step
+                    )?;
+                }
+                None => writeln!(out, "{:#06x} wasn't written in the recorded history", addr)?,
+            },
+            Command::Break { addr, condition } => {
+                write!(out, "breakpoint at {:#06x}", addr)?;
+                if let Some(condition) = &condition {
+                    write!(out, " if {}", condition)?;
+                }
+                writeln!(out)?;
+                self.state.add_conditional_breakpoint(addr, condition);
+            }
+            Command::Watch { addr, len, kind } => {
+                let segment = self.state.segment(Segment::Ds);
+                let watchpoint = Watchpoint::in_segment(segment, addr, len, kind);
+                write!(out, "watchpoint at {:#06x}", watchpoint.start)?;
+                if watchpoint.len != 1 {
+                    write!(out, "-{:#06x}", watchpoint.end())?;
+                }
+                writeln!(out)?;
+                self.state.add_watchpoint(watchpoint);
+            }
+            Command::Unwatch(addr) => {
+                if !self.state.remove_watchpoint(self.data_addr(addr)) {
+                    writeln!(out, "error: no watchpoint at {:#06x}", addr)?;
+                }
+            }
+            Command::Delete(addr) => {
+                if !self.state.remove_breakpoint(addr) {
+                    writeln!(out, "error: no breakpoint at {:#06x}", addr)?;
+                }
+            }
+            Command::Breakpoints => {
+                for breakpoint in self.state.breakpoints() {
+                    writeln!(out, "{}", breakpoint)?;
+                }
+                for watchpoint in self.state.watchpoints() {
+                    writeln!(out, "{}", watchpoint)?;
+                }
+            }
+            Command::Registers => {
+                write!(out, "{}", self.state)?;
+                for reg in SEGMENT_REGISTERS {
+                    writeln!(out, "{}: {:04x}", reg, self.state.get_register_16(reg))?;
+                }
+                writeln!(out, "ip: {:04x}", self.state.ip())?;
+                writeln!(out, "flags: {}", self.state.flags().letters())?;
+            }
+            Command::Examine {
+                addr,
+                count,
+                radix,
+                size,
+            } => self.examine(addr, count, radix, size, out)?,
+            Command::Set { reg, value } => {
+                let result = match reg.as_str() {
+                    "ip" => {
+                        self.state.set_ip(value);
+                        Ok(())
+                    }
+                    reg if is_byte(reg) => self.state.try_set_register_8(reg, value as u8),
+                    reg => self.state.try_set_register_16(reg, value),
+                };
+                if let Err(err) = result {
+                    writeln!(out, "error: {}", err)?;
+                }
+            }
+            Command::Disassemble(count) => self.disassemble(count, out)?,
+            Command::Help => writeln!(out, "{}", HELP)?,
+            Command::Quit => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn step(&mut self, count: u32, out: &mut impl Write) -> std::io::Result<()> {
+        for _ in 0..count {
+            let result = match self.state.step() {
+                Ok(result) => result,
+                Err(err) => return writeln!(out, "error: {}", err),
+            };
+            if let Some(step) = result.executed {
+                writeln!(out, "{}", step)?;
+            }
+            if let Some(stop) = result.stop {
+                return writeln!(out, "stopped: {}", stop);
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs to the instruction after a call with a breakpoint that's removed
+    /// again, unless one was already set there
+    fn next(&mut self, out: &mut impl Write) -> std::io::Result<()> {
+        let after = match self.decode_at(self.state.ip()) {
+            Item::Instr(decoded) if matches!(decoded.instr, Instr::Call(_)) => {
+                (decoded.addr + decoded.len) as u16
+            }
+            _ => return self.step(1, out),
+        };
+        let added = !self
+            .state
+            .breakpoints()
+            .iter()
+            .any(|breakpoint| breakpoint.addr == after);
+        if added {
+            self.state.add_breakpoint(after);
+        }
+        let result = self.state.run();
+        if added {
+            self.state.remove_breakpoint(after);
+        }
+        match result {
+            Ok(StopReason::Breakpoint(addr)) if addr == after && added => {}
+            result => self.report(result, out)?,
+        }
+        self.show_ip(out)
+    }
+
+    /// Says why a run stopped, and for a watchpoint which instruction
+    /// touched the memory
+    fn report(
+        &self,
+        result: Result<StopReason, SimError>,
+        out: &mut impl Write,
+    ) -> std::io::Result<()> {
+        match result {
+            Ok(StopReason::Watchpoint(hit)) => {
+                writeln!(out, "stopped: {}", hit)?;
+                let item = self.decode_at(hit.instr_addr);
+                writeln!(out, "   {:04x}: {}", item.addr(), item)
+            }
+            Ok(stop) => writeln!(out, "stopped: {}", stop),
+            Err(err) => writeln!(out, "error: {}", err),
+        }
+    }
+
+    /// Shows where execution stopped, unless it ran off the end of the
+    /// program or the program exited
+    fn show_ip(&self, out: &mut impl Write) -> std::io::Result<()> {
+        if self.state.has_more() && !self.state.exited() {
+            self.disassemble(1, out)?;
+        }
+        Ok(())
+    }
+
+    /// The physical address of `offset` in the data segment
+    fn data_addr(&self, offset: u16) -> u32 {
+        self.state.physical(Segment::Ds, offset)
+    }
+
+    fn examine(
+        &self,
+        addr: u16,
+        count: u16,
+        radix: Radix,
+        size: Size,
+        out: &mut impl Write,
+    ) -> std::io::Result<()> {
+        let memory = self.state.memory();
+        let width = match size {
+            Size::Byte => 1,
+            Size::Word => 2,
+        };
+        let byte = |offset: u16| memory[self.data_addr(offset) as usize] as u16;
+        let values = (0..count).map(|index| {
+            let addr = addr.wrapping_add(index.wrapping_mul(width));
+            match size {
+                Size::Byte => byte(addr),
+                Size::Word => byte(addr.wrapping_add(1)) << 8 | byte(addr),
+            }
+        });
+        let values = values.collect::<Vec<_>>();
+        for (line, chunk) in values.chunks(EXAMINE_WIDTH).enumerate() {
+            let line_addr = addr.wrapping_add((line * EXAMINE_WIDTH) as u16 * width);
+            write!(out, "{:#06x}:", line_addr)?;
+            for value in chunk {
+                match (radix, size) {
+                    (Radix::Hex, Size::Byte) => write!(out, " {:#04x}", value)?,
+                    (Radix::Hex, Size::Word) => write!(out, " {:#06x}", value)?,
+                    (Radix::Dec, _) => write!(out, " {}", value)?,
+                }
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+
+    /// Prints `count` instructions from ip, stopping early at the end of
+    /// memory
+    fn disassemble(&self, count: usize, out: &mut impl Write) -> std::io::Result<()> {
+        let mut decoder = DecoderState::new(self.state.segment_memory(Segment::Cs));
+        decoder.offset = self.state.ip() as usize;
+        for index in 0..count {
+            if !decoder.has_more() {
+                break;
+            }
+            let item = decode_item(&mut decoder);
+            let marker = if index == 0 { "=>" } else { "  " };
+            writeln!(out, "{} {:04x}: {}", marker, item.addr(), item)?;
+            decoder.advance();
+        }
+        Ok(())
+    }
+
+    fn decode_at(&self, addr: u16) -> Item {
+        let mut decoder = DecoderState::new(self.state.segment_memory(Segment::Cs));
+        decoder.offset = addr as usize;
+        decode_item(&mut decoder)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{debugger::Debugger, sim::SimState};
+
+    fn transcript(program: Vec<u8>, script: &str) -> String {
+        let mut state = SimState::new(program);
+        state.set_history_budget(100);
+        let mut debugger = Debugger::new(state);
+        let mut out = vec![];
+        debugger.run_script(script, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_script() {
+        // mov cx, 3; call $+5; hlt; db 0; mov [0x100], cx; ret
+        let program = vec![
+            0xB9, 0x03, 0x00, 0xE8, 0x02, 0x00, 0xF4, 0x00, 0x89, 0x0E, 0x00, 0x01, 0xC3,
+        ];
+        let script = "\
+            step\n\
+            # steps over the call\n\
+            next\n\
+            x/4xb 0x100\n\
+            set ip 3\n\
+            break 8\n\
+            continue\n\
+            set CL 0x12\n\
+            registers\n\
+            frobnicate\n\
+            quit\n\
+            step\n";
+
+        assert_eq!(
+            transcript(program, script),
+            "(dbg) step\n\
+             mov cx, 3 ; Clocks: +4 = 4 | cx:0x0->0x3 ip:0x0->0x3\n\
+             (dbg) # steps over the call\n\
+             (dbg) next\n\
+             => 0006: hlt\n\
+             (dbg) x/4xb 0x100\n\
+             0x0100: 0x03 0x00 0x00 0x00\n\
+             (dbg) set ip 3\n\
+             (dbg) break 8\n\
+             breakpoint at 0x0008\n\
+             (dbg) continue\n\
+             stopped: breakpoint at 0x8\n\
+             => 0008: mov [256], cx\n\
+             (dbg) set CL 0x12\n\
+             (dbg) registers\n\
+             ax: 0000\nbx: 0000\ncx: 0012\ndx: 0000\nsp: fffe\nbp: 0000\nsi: 0000\ndi: 0000\n\
+             cs: 0000\nds: 0000\nes: 0000\nss: 0000\n\
+             ip: 0008\n\
+             flags: \n\
+             (dbg) frobnicate\n\
+             error: unknown command 'frobnicate', try help\n\
+             (dbg) quit\n"
+        );
+    }
+
+    #[test]
+    fn test_disassemble_and_examine_words() {
+        let script = "disas 3\nx/3dw 0\nstep 5\nstep\n";
+
+        assert_eq!(
+            transcript(vec![0xB9, 0x03, 0x00, 0xF4, 0xFF], script),
+            "(dbg) disas 3\n\
+             => 0000: mov cx, 3\n   \
+             0003: hlt\n   \
+             0004: db 0xff ; unknown\n\
+             (dbg) x/3dw 0\n\
+             0x0000: 953 62464 255\n\
+             (dbg) step 5\n\
+             mov cx, 3 ; Clocks: +4 = 4 | cx:0x0->0x3 ip:0x0->0x3\n\
+             hlt ; Clocks: +2 = 6 | ip:0x3->0x4\n\
+             stopped: halted\n\
+             (dbg) step\n\
+             error: the cpu is halted\n"
+        );
+    }
+
+    #[test]
+    fn test_conditional_breakpoint_and_watchpoint() {
+        // mov cx, 3; sub cx, 1; mov [0x100], cx; jne $-8
+        let program = vec![
+            0xB9, 0x03, 0x00, 0x83, 0xE9, 0x01, 0x89, 0x0E, 0x00, 0x01, 0x75, 0xF7,
+        ];
+        let script = "\
+            break 6 if cx == 1\n\
+            watch 0x101\n\
+            breakpoints\n\
+            continue\n\
+            unwatch 0x101\n\
+            awatch 0x100 2\n\
+            continue\n\
+            continue\n";
+
+        assert_eq!(
+            transcript(program, script),
+            "(dbg) break 6 if cx == 1\n\
+             breakpoint at 0x0006 if cx == 1\n\
+             (dbg) watch 0x101\n\
+             watchpoint at 0x0101\n\
+             (dbg) breakpoints\n\
+             break 0x0006 if cx == 1\n\
+             watch 0x0101\n\
+             (dbg) continue\n\
+             stopped: watchpoint 0x101 written by instruction at 0x6: 0x0->0x0\n   \
+             0006: mov [256], cx\n\
+             => 000a: jne $-7\n\
+             (dbg) unwatch 0x101\n\
+             (dbg) awatch 0x100 2\n\
+             watchpoint at 0x0100-0x0101\n\
+             (dbg) continue\n\
+             stopped: breakpoint at 0x6\n\
+             => 0006: mov [256], cx\n\
+             (dbg) continue\n\
+             stopped: watchpoint 0x100 written by instruction at 0x6: 0x2->0x1\n   \
+             0006: mov [256], cx\n\
+             => 000a: jne $-7\n"
+        );
+    }
+
+    #[test]
+    fn test_reverse_execution() {
+        // mov cx, 3; mov [0x100], cx; sub cx, 1; jne $-7; hlt
+        let program = vec![
+            0xB9, 0x03, 0x00, 0x89, 0x0E, 0x00, 0x01, 0x83, 0xE9, 0x01, 0x75, 0xF7, 0xF4,
+        ];
+        let script = "\
+            back\n\
+            continue\n\
+            lastwrite 0x100\n\
+            lastwrite 0x200\n\
+            back 2\n\
+            break 3 if cx == 2\n\
+            rc\n\
+            x/1xb 0x100\n\
+            rc\n";
+
+        assert_eq!(
+            transcript(program, script),
+            "(dbg) back\n\
+             error: no history to step back through\n\
+             => 0000: mov cx, 3\n\
+             (dbg) continue\n\
+             stopped: halted\n\
+             (dbg) lastwrite 0x100\n\
+             0x0100 last written by 0003: mov [256], cx: 0x2->0x1 at step 7\n\
+             (dbg) lastwrite 0x200\n\
+             0x0200 wasn't written in the recorded history\n\
+             (dbg) back 2\n\
+             => 000a: jne $-7\n\
+             (dbg) break 3 if cx == 2\n\
+             breakpoint at 0x0003 if cx == 2\n\
+             (dbg) rc\n\
+             stopped: breakpoint at 0x3\n\
+             => 0003: mov [256], cx\n\
+             (dbg) x/1xb 0x100\n\
+             0x0100: 0x03\n\
+             (dbg) rc\n\
+             stopped: start of recorded history\n\
+             => 0000: mov cx, 3\n"
+        );
+    }
+}
@@ -0,0 +1,271 @@
+use std::collections::BTreeSet;
+
+use crate::decoder::{instr::Instr, Decoded, Item};
+
+#[derive(Debug, PartialEq)]
+pub struct BasicBlock {
+    pub start: usize,
+    pub instrs: Vec<Decoded>,
+}
+
+impl BasicBlock {
+    /// The address just past the last instruction in the block
+    pub fn end(&self) -> usize {
+        self.instrs
+            .last()
+            .map_or(self.start, |last| last.addr + last.len)
+    }
+
+    pub fn last(&self) -> &Decoded {
+        self.instrs.last().expect("Basic blocks are never empty")
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum EdgeKind {
+    /// Execution runs off the end of the block into the next one
+    Fallthrough,
+    /// A jump or branch to its target
+    Taken,
+    /// A call to the start of a subroutine
+    Call,
+}
+
+/// An edge between two blocks, given as indices into `Cfg::blocks`
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub kind: EdgeKind,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Cfg {
+    pub blocks: Vec<BasicBlock>,
+    pub edges: Vec<Edge>,
+}
+
+impl Cfg {
+    /// Splits the decoded instructions into basic blocks. Blocks start at the
+    /// first instruction, at every jump target and after every jump, call or
+    /// ret. Anything that isn't an instruction ends the current block.
+    pub fn new(items: Vec<Item>) -> Cfg {
+        let mut leaders = BTreeSet::new();
+        for item in &items {
+            if let Item::Instr(decoded) = item {
+                let next = decoded.addr + decoded.len;
+                if let Some(target) = decoded.instr.jump_target(next) {
+                    leaders.insert(target);
+                    leaders.insert(next);
+                }
+                if !decoded.instr.falls_through() {
+                    leaders.insert(next);
+                }
+            }
+        }
+
+        let mut blocks: Vec<BasicBlock> = vec![];
+        let mut open = false;
+        for item in items {
+            let Item::Instr(decoded) = item else {
+                open = false;
+                continue;
+            };
+            if !open || leaders.contains(&decoded.addr) {
+                blocks.push(BasicBlock {
+                    start: decoded.addr,
+                    instrs: vec![],
+                });
+                open = true;
+            }
+            blocks.last_mut().unwrap().instrs.push(decoded);
+        }
+
+        let mut edges = vec![];
+        for (from, block) in blocks.iter().enumerate() {
+            let last = block.last();
+            if last.instr.falls_through() {
+                if let Some(to) = find_block(&blocks, block.end()) {
+                    edges.push(Edge {
+                        from,
+                        to,
+                        kind: EdgeKind::Fallthrough,
+                    });
+                }
+            }
+            if let Some(to) = last
+                .instr
+                .jump_target(block.end())
+                .and_then(|target| find_block(&blocks, target))
+            {
+                let kind = match last.instr {
+                    Instr::Call(_) => EdgeKind::Call,
+                    _ => EdgeKind::Taken,
+                };
+                edges.push(Edge { from, to, kind });
+            }
+        }
+
+        Cfg { blocks, edges }
+    }
+
+    /// The index of the block starting at `addr`
+    pub fn block_at(&self, addr: usize) -> Option<usize> {
+        find_block(&self.blocks, addr)
+    }
+
+    pub fn successors(&self, block: usize) -> impl Iterator<Item = &Edge> {
+        self.edges.iter().filter(move |edge| edge.from == block)
+    }
+
+    pub fn predecessors(&self, block: usize) -> impl Iterator<Item = &Edge> {
+        self.edges.iter().filter(move |edge| edge.to == block)
+    }
+
+    /// Renders the graph in Graphviz DOT, one node per block listing its
+    /// instructions
+    pub fn to_dot(&self) -> String {
+        let mut dot = "digraph cfg {\n    node [shape=box, fontname=\"monospace\"];\n".to_string();
+        for block in &self.blocks {
+            let mut label = String::new();
+            for decoded in &block.instrs {
+                label.push_str(&format!("{:04X}: {}\\l", decoded.addr, decoded.instr));
+            }
+            dot.push_str(&format!(
+                "    {} [label=\"{}\"];\n",
+                node_name(block),
+                label.replace('"', "\\\"")
+            ));
+        }
+        for edge in &self.edges {
+            let style = match edge.kind {
+                EdgeKind::Fallthrough => "label=\"fallthrough\"",
+                EdgeKind::Taken => "label=\"taken\", color=\"darkgreen\"",
+                EdgeKind::Call => "label=\"call\", style=\"dashed\"",
+            };
+            dot.push_str(&format!(
+                "    {} -> {} [{}];\n",
+                node_name(&self.blocks[edge.from]),
+                node_name(&self.blocks[edge.to]),
+                style
+            ));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+fn find_block(blocks: &[BasicBlock], addr: usize) -> Option<usize> {
+    blocks.binary_search_by_key(&addr, |block| block.start).ok()
+}
+
+fn node_name(block: &BasicBlock) -> String {
+    format!("b{:04X}", block.start)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::decoder::{
+        cfg::{Cfg, Edge, EdgeKind},
+        decode_items,
+        traverse::decode_recursive,
+    };
+
+    #[test]
+    fn test_loop_blocks() {
+        // mov cx, 3; loop: sub cx, 1; jne loop; ret
+        let cfg = Cfg::new(decode_items(vec![
+            0b10111001, 0b11, 0b0, 0b10000011, 0b11101001, 0b1, 0b01110101, 0b11111011, 0b11000011,
+        ]));
+
+        let starts = cfg
+            .blocks
+            .iter()
+            .map(|block| block.start)
+            .collect::<Vec<_>>();
+        assert_eq!(starts, vec![0, 3, 8]);
+        assert_eq!(cfg.blocks[1].instrs.len(), 2);
+        assert_eq!(cfg.blocks[1].end(), 8);
+        assert_eq!(
+            cfg.edges,
+            vec![
+                Edge {
+                    from: 0,
+                    to: 1,
+                    kind: EdgeKind::Fallthrough
+                },
+                Edge {
+                    from: 1,
+                    to: 2,
+                    kind: EdgeKind::Fallthrough
+                },
+                Edge {
+                    from: 1,
+                    to: 1,
+                    kind: EdgeKind::Taken
+                },
+            ]
+        );
+        assert_eq!(cfg.predecessors(1).count(), 2);
+        assert_eq!(cfg.successors(2).count(), 0);
+    }
+
+    #[test]
+    fn test_call_edges() {
+        // call 4; ret; mov cl, 12; ret
+        let cfg = Cfg::new(decode_recursive(
+            vec![
+                0b11101000, 0b1, 0b0, 0b11000011, 0b10110001, 0b1100, 0b11000011,
+            ],
+            &[0],
+        ));
+
+        assert_eq!(cfg.blocks.len(), 3);
+        let call = cfg.block_at(4).unwrap();
+        assert_eq!(
+            cfg.successors(0).map(|edge| edge.kind).collect::<Vec<_>>(),
+            vec![EdgeKind::Fallthrough, EdgeKind::Call]
+        );
+        assert_eq!(cfg.successors(0).last().unwrap().to, call);
+        assert_eq!(cfg.block_at(5), None);
+    }
+
+    #[test]
+    fn test_data_splits_blocks() {
+        // jmp short 3; db 0xff; mov cl, 12
+        let cfg = Cfg::new(decode_recursive(
+            vec![0b11101011, 0b1, 0xFF, 0b10110001, 0b1100],
+            &[0],
+        ));
+
+        assert_eq!(cfg.blocks.len(), 2);
+        assert_eq!(
+            cfg.edges,
+            vec![Edge {
+                from: 0,
+                to: 1,
+                kind: EdgeKind::Taken
+            }]
+        );
+    }
+
+    #[test]
+    fn test_to_dot() {
+        // je 4; mov cl, 12; ret
+        let cfg = Cfg::new(decode_items(vec![
+            0b01110100, 0b10, 0b10110001, 0b1100, 0b11000011,
+        ]));
+
+        assert_eq!(
+            cfg.to_dot(),
+            "digraph cfg {\n    node [shape=box, fontname=\"monospace\"];\n\
+             \x20   b0000 [label=\"0000: je $+4\\l\"];\n\
+             \x20   b0002 [label=\"0002: mov cl, 12\\l\"];\n\
+             \x20   b0004 [label=\"0004: ret\\l\"];\n\
+             \x20   b0000 -> b0002 [label=\"fallthrough\"];\n\
+             \x20   b0000 -> b0004 [label=\"taken\", color=\"darkgreen\"];\n\
+             \x20   b0002 -> b0004 [label=\"fallthrough\"];\n\
+             }\n"
+        );
+    }
+}
@@ -29,6 +29,7 @@ fn decode_imm<T: Decoder>(state: &mut T) -> Location {
 #[cfg(test)]
 mod test {
     use crate::decoder::{
+        common::Encoding,
         decode,
         instr::Instr,
         loc::Location,
@@ -44,7 +45,8 @@ mod test {
             asm[0],
             Instr::Mov(MoveInstr {
                 src: Location::Immediate8(0b1100100),
-                dest: Location::Reg(BL)
+                dest: Location::Reg(BL),
+                encoding: Encoding::ImmToReg,
             })
         );
     }
@@ -58,7 +60,8 @@ mod test {
             asm[0],
             Instr::Mov(MoveInstr {
                 src: Location::Immediate16(0b1100100),
-                dest: Location::Reg(BX)
+                dest: Location::Reg(BX),
+                encoding: Encoding::ImmToReg,
             })
         );
     }
@@ -72,7 +75,8 @@ mod test {
             asm[0],
             Instr::Mov(MoveInstr {
                 src: Location::Immediate8(4),
-                dest: Location::Mem(3)
+                dest: Location::Mem(3),
+                encoding: Encoding::ImmToRm { s: false },
             })
         );
     }
@@ -86,7 +90,8 @@ mod test {
             asm[0],
             Instr::Mov(MoveInstr {
                 src: Location::Immediate16(4),
-                dest: Location::Mem(3)
+                dest: Location::Mem(3),
+                encoding: Encoding::ImmToRm { s: false },
             })
         );
     }
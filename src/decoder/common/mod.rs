@@ -1,3 +1,21 @@
 pub mod imm_to_reg;
 pub mod imm_to_rm;
 pub mod rm_to_reg;
+
+/// The machine encoding an instruction was decoded from, where the 8086 offers
+/// more than one way of encoding the same operands.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Encoding {
+    /// Register/Memory to/from Register, `d` set when the reg field is the destination
+    RmToFromReg { d: bool },
+    /// Immediate to Register using the register encoded in the opcode
+    ImmToReg,
+    /// Immediate to Register/Memory, `s` set when the immediate was a sign-extended byte
+    ImmToRm { s: bool },
+    /// Immediate to Accumulator
+    ImmToAcc,
+    /// Memory to/from Accumulator
+    Acc,
+    /// Accumulator with Register using the register encoded in the opcode
+    AccWithReg,
+}
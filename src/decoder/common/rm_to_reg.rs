@@ -26,15 +26,23 @@ pub fn decode_rm_to_reg<T: Decoder>(state: &mut T) -> (Location, Location) {
     let sw = first & 0b00000011;
     let rm = decode_rm(state);
     let len = state.get_instr_len();
-    let imm = if sw == 0b01 {
-        let low = state.get_byte(len);
-        let high = state.get_byte(len + 1);
-        state.add_len(2);
-        Location::Immediate16((high as u16) << 8 | low as u16)
-    } else {
-        let low = state.get_byte(len);
-        state.add_len(1);
-        Location::Immediate8(low)
+    let imm = match sw {
+        0b01 => {
+            let low = state.get_byte(len);
+            let high = state.get_byte(len + 1);
+            state.add_len(2);
+            Location::Immediate16((high as u16) << 8 | low as u16)
+        }
+        0b11 => {
+            let low = state.get_byte(len);
+            state.add_len(1);
+            Location::Immediate16(low as i8 as u16)
+        }
+        _ => {
+            let low = state.get_byte(len);
+            state.add_len(1);
+            Location::Immediate8(low)
+        }
     };
 
     (rm, imm)
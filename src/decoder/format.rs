@@ -0,0 +1,720 @@
+use std::str::FromStr;
+
+use crate::decoder::{
+    instr::{Instr, JumpTarget},
+    loc::{eac::EffectiveAddress, Location, Size},
+    prefix::{Prefix, PrefixedInstr, Rep, Segment},
+    Item,
+};
+
+/// The assembler dialect instructions are written in
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Syntax {
+    /// Intel syntax as NASM reads it, e.g. `mov [bp + 2], word 1`
+    #[default]
+    Nasm,
+    /// Intel syntax as MASM reads it, e.g. `mov word ptr [bp+2], 1`
+    Masm,
+    /// AT&T syntax as GNU as and objdump use, e.g. `movw $1,2(%bp)`
+    Att,
+}
+
+impl FromStr for Syntax {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "nasm" | "intel" => Ok(Syntax::Nasm),
+            "masm" => Ok(Syntax::Masm),
+            "att" | "at&t" | "gas" => Ok(Syntax::Att),
+            _ => Err(format!(
+                "unknown syntax '{}', expected nasm, masm or att",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Radix {
+    #[default]
+    Decimal,
+    Hex,
+}
+
+impl FromStr for Radix {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "dec" | "decimal" => Ok(Radix::Decimal),
+            "hex" | "hexadecimal" => Ok(Radix::Hex),
+            _ => Err(format!("unknown radix '{}', expected dec or hex", s)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Case {
+    #[default]
+    Lower,
+    Upper,
+}
+
+impl FromStr for Case {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "lower" => Ok(Case::Lower),
+            "upper" => Ok(Case::Upper),
+            _ => Err(format!("unknown case '{}', expected lower or upper", s)),
+        }
+    }
+}
+
+/// Turns decoded instructions into text. The default formatter writes the
+/// NASM syntax the `Display` impls use.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Formatter {
+    pub syntax: Syntax,
+    pub radix: Radix,
+    pub case: Case,
+}
+
+impl Formatter {
+    pub fn new(syntax: Syntax) -> Self {
+        Self {
+            syntax,
+            ..Default::default()
+        }
+    }
+
+    pub fn radix(self, radix: Radix) -> Self {
+        Self { radix, ..self }
+    }
+
+    pub fn case(self, case: Case) -> Self {
+        Self { case, ..self }
+    }
+
+    /// The marker that starts a comment in this syntax
+    pub fn comment(&self) -> &'static str {
+        match self.syntax {
+            Syntax::Nasm | Syntax::Masm => ";",
+            Syntax::Att => "#",
+        }
+    }
+
+    /// The directive that puts the assembler into 16-bit mode
+    pub fn header(&self) -> String {
+        let header = match self.syntax {
+            Syntax::Nasm => "bits 16",
+            Syntax::Masm => ".8086",
+            Syntax::Att => ".code16",
+        };
+        self.keyword(header)
+    }
+
+    pub fn item(&self, item: &Item) -> String {
+        match item {
+            Item::Instr(decoded) => self.instr(&decoded.instr),
+            Item::Unknown { byte, .. } => format!(
+                "{} {} {} unknown",
+                self.data_directive(Size::Byte),
+                self.data_byte(*byte),
+                self.comment()
+            ),
+            // Whole words are written as such, anything else byte by byte
+            Item::Data { bytes, .. } if bytes.len() % 2 == 0 => {
+                let words = bytes
+                    .chunks(2)
+                    .map(|word| self.data_word(u16::from_le_bytes([word[0], word[1]])))
+                    .collect::<Vec<_>>();
+                format!("{} {}", self.data_directive(Size::Word), words.join(", "))
+            }
+            Item::Data { bytes, .. } => {
+                let bytes = bytes
+                    .iter()
+                    .map(|byte| self.data_byte(*byte))
+                    .collect::<Vec<_>>();
+                format!("{} {}", self.data_directive(Size::Byte), bytes.join(", "))
+            }
+        }
+    }
+
+    pub fn instr(&self, instr: &Instr) -> String {
+        self.instr_in(instr, None, 0)
+    }
+
+    /// Formats `instr` following `prefix_len` prefix bytes, with `segment`
+    /// written into its memory operand
+    fn instr_in(&self, instr: &Instr, segment: Option<Segment>, prefix_len: i32) -> String {
+        let short = |offset| JumpTarget(JumpTarget::short(offset).0 + prefix_len);
+        let near = |offset| JumpTarget(JumpTarget::near(offset).0 + prefix_len);
+        match instr {
+            Instr::Mov(mov) => self.operands("mov", &mov.dest, &mov.src, segment),
+            Instr::Op(op) => self.operands(&op.kind.to_string(), &op.dest, &op.src, segment),
+            Instr::Xchg(xchg) => self.operands("xchg", &xchg.dest, &xchg.src, segment),
+            Instr::Je(offset) => self.jump("je", None, short(*offset)),
+            Instr::Jne(offset) => self.jump("jne", None, short(*offset)),
+            Instr::Jmp(offset) => self.jump("jmp", Some("short"), short(*offset)),
+            Instr::JmpNear(offset) => {
+                let distance = match self.syntax {
+                    Syntax::Masm => "near ptr",
+                    _ => "near",
+                };
+                self.jump("jmp", Some(distance), near(*offset))
+            }
+            Instr::Call(offset) => self.jump("call", None, near(*offset)),
+            Instr::Ret => self.keyword("ret"),
+            Instr::Hlt => self.keyword("hlt"),
+            Instr::Int(vector) => format!(
+                "{} {}",
+                self.keyword("int"),
+                self.operand(&Location::Immediate8(*vector), None)
+            ),
+            Instr::Str(string) => self.keyword(&string.to_string()),
+            Instr::Nop => self.keyword("nop"),
+            Instr::Prefixed(prefixed) => self.prefixed(prefixed),
+        }
+    }
+
+    fn prefixed(&self, prefixed: &PrefixedInstr) -> String {
+        // A segment override is written in the memory operand if there is
+        // one, but only when it's the sole segment prefix and comes last, as
+        // that's where the assembler puts it back
+        let segments = prefixed
+            .prefixes
+            .iter()
+            .filter(|prefix| matches!(prefix, Prefix::Segment(_)))
+            .count();
+        let segment = match prefixed.prefixes.last() {
+            Some(Prefix::Segment(segment))
+                if segments == 1 && has_memory_operand(&prefixed.instr) =>
+            {
+                Some(*segment)
+            }
+            _ => None,
+        };
+        let mut words = vec![];
+        for prefix in &prefixed.prefixes {
+            match prefix {
+                Prefix::Lock => words.push(self.keyword("lock")),
+                Prefix::Rep(Rep::Repe) if compares(&prefixed.instr) => {
+                    words.push(self.keyword("repe"))
+                }
+                Prefix::Rep(Rep::Repe) => words.push(self.keyword("rep")),
+                Prefix::Rep(Rep::Repne) => words.push(self.keyword("repne")),
+                Prefix::Segment(_) if segment.is_some() => {}
+                Prefix::Segment(segment) => words.push(self.keyword(&segment.to_string())),
+            }
+        }
+        words.push(self.instr_in(&prefixed.instr, segment, prefixed.prefixes.len() as i32));
+        words.join(" ")
+    }
+
+    pub fn location(&self, loc: &Location) -> String {
+        self.operand(loc, None)
+    }
+
+    /// Formats `loc` as an operand, with a segment override if it is in memory
+    pub fn operand(&self, loc: &Location, segment: Option<Segment>) -> String {
+        if let (Some(segment), Location::Mem(_) | Location::Eac(_)) = (segment, loc) {
+            let segment = self.keyword(&segment.to_string());
+            return match (self.syntax, loc) {
+                (Syntax::Nasm, Location::Mem(addr)) => {
+                    format!("[{}:{}]", segment, self.number(*addr))
+                }
+                (Syntax::Nasm, Location::Eac(eac)) => {
+                    format!("[{}:{}]", segment, self.address(eac))
+                }
+                (Syntax::Masm, Location::Mem(addr)) => {
+                    format!("{}:[{}]", segment, self.number(*addr))
+                }
+                (Syntax::Att, _) => format!("%{}:{}", segment, self.location(loc)),
+                _ => format!("{}:{}", segment, self.location(loc)),
+            };
+        }
+        match (self.syntax, loc) {
+            (Syntax::Att, Location::Reg(reg)) => format!("%{}", self.keyword(reg)),
+            (_, Location::Reg(reg)) => self.keyword(reg),
+            (Syntax::Att, Location::Immediate16(val)) => format!("${}", self.number(*val)),
+            (Syntax::Att, Location::Immediate8(val)) => format!("${}", self.number(*val as u16)),
+            (_, Location::Immediate16(val)) => self.number(*val),
+            (_, Location::Immediate8(val)) => self.number(*val as u16),
+            (Syntax::Nasm, Location::Mem(addr)) => format!("[{}]", self.number(*addr)),
+            // MASM reads a bare [1000] as an immediate, so name the segment
+            (Syntax::Masm, Location::Mem(addr)) => {
+                format!("{}:[{}]", self.keyword("ds"), self.number(*addr))
+            }
+            (Syntax::Att, Location::Mem(addr)) => self.number(*addr),
+            (Syntax::Att, Location::Eac(eac)) => {
+                let regs = eac
+                    .mode()
+                    .regs()
+                    .iter()
+                    .map(|reg| format!("%{}", self.keyword(reg)))
+                    .collect::<Vec<_>>();
+                let disp = match eac {
+                    EffectiveAddress::Mode(_) => String::new(),
+                    _ => self.signed(eac.offset()),
+                };
+                format!("{}({})", disp, regs.join(","))
+            }
+            (_, Location::Eac(eac)) => format!("[{}]", self.address(eac)),
+        }
+    }
+
+    /// The inside of the brackets of an Intel syntax memory operand
+    pub fn address(&self, eac: &EffectiveAddress) -> String {
+        let sep = match self.syntax {
+            Syntax::Masm => "",
+            _ => " ",
+        };
+        let regs = eac
+            .mode()
+            .regs()
+            .iter()
+            .map(|reg| self.keyword(reg))
+            .collect::<Vec<_>>()
+            .join(&format!("{}+{}", sep, sep));
+        match eac {
+            EffectiveAddress::Mode(_) => regs,
+            _ => {
+                let offset = eac.offset();
+                let sign = if offset < 0 { '-' } else { '+' };
+                format!(
+                    "{}{}{}{}{}",
+                    regs,
+                    sep,
+                    sign,
+                    sep,
+                    self.number(offset.unsigned_abs())
+                )
+            }
+        }
+    }
+
+    /// A mov or arithmetic instruction, with a size where the operands don't
+    /// imply one
+    pub fn two_operand(&self, mnemonic: &str, dest: &Location, src: &Location) -> String {
+        self.operands(mnemonic, dest, src, None)
+    }
+
+    fn operands(
+        &self,
+        mnemonic: &str,
+        dest: &Location,
+        src: &Location,
+        segment: Option<Segment>,
+    ) -> String {
+        // Only an immediate written to memory leaves the size ambiguous
+        let size = match (dest, src) {
+            (Location::Eac(_) | Location::Mem(_), Location::Immediate8(_)) => Some(Size::Byte),
+            (Location::Eac(_) | Location::Mem(_), Location::Immediate16(_)) => Some(Size::Word),
+            _ => None,
+        };
+        let (dest, src) = (self.operand(dest, segment), self.operand(src, segment));
+        match (self.syntax, size) {
+            (Syntax::Nasm, Some(size)) => format!(
+                "{} {}, {} {}",
+                self.keyword(mnemonic),
+                dest,
+                self.keyword(size_name(size)),
+                src
+            ),
+            (Syntax::Masm, Some(size)) => format!(
+                "{} {} {}, {}",
+                self.keyword(mnemonic),
+                self.keyword(&format!("{} ptr", size_name(size))),
+                dest,
+                src
+            ),
+            (Syntax::Att, size) => {
+                let suffix = match size {
+                    Some(Size::Byte) => "b",
+                    Some(Size::Word) => "w",
+                    None => "",
+                };
+                format!(
+                    "{} {},{}",
+                    self.keyword(&format!("{}{}", mnemonic, suffix)),
+                    src,
+                    dest
+                )
+            }
+            (_, None) => format!("{} {}, {}", self.keyword(mnemonic), dest, src),
+        }
+    }
+
+    fn jump(&self, mnemonic: &str, distance: Option<&str>, target: JumpTarget) -> String {
+        let here = match self.syntax {
+            Syntax::Nasm | Syntax::Masm => '$',
+            Syntax::Att => '.',
+        };
+        let target = if target.0 < 0 {
+            format!("{}-{}", here, self.number(target.0.unsigned_abs() as u16))
+        } else {
+            format!("{}+{}", here, self.number(target.0 as u16))
+        };
+        match (self.syntax, distance) {
+            // GNU as picks the jump size itself
+            (Syntax::Att, _) | (_, None) => format!("{} {}", self.keyword(mnemonic), target),
+            (_, Some(distance)) => format!(
+                "{} {} {}",
+                self.keyword(mnemonic),
+                self.keyword(distance),
+                target
+            ),
+        }
+    }
+
+    fn data_directive(&self, size: Size) -> String {
+        let directive = match (self.syntax, size) {
+            (Syntax::Att, Size::Byte) => ".byte",
+            (Syntax::Att, Size::Word) => ".word",
+            (_, Size::Byte) => "db",
+            (_, Size::Word) => "dw",
+        };
+        self.keyword(directive)
+    }
+
+    // Data is always written in hex, padded to its size
+    fn data_byte(&self, byte: u8) -> String {
+        self.hex(format!("{:02x}", byte))
+    }
+
+    fn data_word(&self, word: u16) -> String {
+        self.hex(format!("{:04x}", word))
+    }
+
+    fn signed(&self, val: i16) -> String {
+        if val < 0 {
+            format!("-{}", self.number(val.unsigned_abs()))
+        } else {
+            self.number(val as u16)
+        }
+    }
+
+    fn number(&self, val: u16) -> String {
+        match self.radix {
+            Radix::Decimal => val.to_string(),
+            Radix::Hex => self.hex(format!("{:x}", val)),
+        }
+    }
+
+    fn hex(&self, digits: String) -> String {
+        let digits = self.keyword(&digits);
+        match self.syntax {
+            // MASM numbers must start with a digit
+            Syntax::Masm if digits.starts_with(|c: char| c.is_ascii_alphabetic()) => {
+                format!("0{}{}", digits, self.keyword("h"))
+            }
+            Syntax::Masm => format!("{}{}", digits, self.keyword("h")),
+            Syntax::Nasm | Syntax::Att => format!("0x{}", digits),
+        }
+    }
+
+    fn keyword(&self, word: &str) -> String {
+        match self.case {
+            Case::Lower => word.to_ascii_lowercase(),
+            Case::Upper => word.to_ascii_uppercase(),
+        }
+    }
+}
+
+fn has_memory_operand(instr: &Instr) -> bool {
+    let (dest, src) = match instr {
+        Instr::Mov(mov) => (&mov.dest, &mov.src),
+        Instr::Op(op) => (&op.dest, &op.src),
+        Instr::Xchg(xchg) => (&xchg.dest, &xchg.src),
+        _ => return false,
+    };
+    [dest, src]
+        .iter()
+        .any(|loc| matches!(loc, Location::Mem(_) | Location::Eac(_)))
+}
+
+fn compares(instr: &Instr) -> bool {
+    matches!(instr, Instr::Str(string) if string.kind.compares())
+}
+
+fn size_name(size: Size) -> &'static str {
+    match size {
+        Size::Byte => "byte",
+        Size::Word => "word",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        assembler::assemble,
+        decoder::{
+            decode, decode_items,
+            format::{Case, Formatter, Radix, Syntax},
+        },
+    };
+
+    // mov [bp + di - 300], cx; mov [1000], word 1; add [bx], byte 34;
+    // mov al, [15]; cmp ax, -2; jne $-6; jmp short $+2; ret
+    const SRC: [u8; 24] = [
+        0b10001001, 0b10001011, 0b11010100, 0b11111110, 0b11000111, 0b110, 0b11101000, 0b11, 0b1,
+        0b0, 0b10000000, 0b111, 0b100010, 0b10100000, 0b1111, 0b0, 0b10000011, 0b11111000,
+        0b11111110, 0b1110101, 0b11111000, 0b11101011, 0b0, 0b11000011,
+    ];
+
+    fn format_all(formatter: Formatter) -> Vec<String> {
+        decode_items(SRC.to_vec())
+            .iter()
+            .map(|item| formatter.item(item))
+            .collect()
+    }
+
+    #[test]
+    fn test_default_matches_display() {
+        let formatter = Formatter::default();
+        for instr in decode(SRC.to_vec()) {
+            assert_eq!(formatter.instr(&instr), instr.to_string());
+        }
+    }
+
+    #[test]
+    fn test_nasm_hex_upper() {
+        let formatter = Formatter::new(Syntax::Nasm)
+            .radix(Radix::Hex)
+            .case(Case::Upper);
+        assert_eq!(
+            format_all(formatter),
+            vec![
+                "MOV [BP + DI - 0x12C], CX",
+                "MOV [0x3E8], WORD 0x1",
+                "ADD [BX], BYTE 0x22",
+                "MOV AL, [0xF]",
+                "CMP AX, 0xFFFE",
+                "JNE $-0x6",
+                "JMP SHORT $+0x2",
+                "RET",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_masm() {
+        assert_eq!(
+            format_all(Formatter::new(Syntax::Masm)),
+            vec![
+                "mov [bp+di-300], cx",
+                "mov word ptr ds:[1000], 1",
+                "add byte ptr [bx], 34",
+                "mov al, ds:[15]",
+                "cmp ax, 65534",
+                "jne $-6",
+                "jmp short $+2",
+                "ret",
+            ]
+        );
+        assert_eq!(Formatter::new(Syntax::Masm).header(), ".8086");
+        let hex = Formatter::new(Syntax::Masm).radix(Radix::Hex);
+        assert_eq!(format_all(hex)[4], "cmp ax, 0fffeh");
+    }
+
+    #[test]
+    fn test_att() {
+        assert_eq!(
+            format_all(Formatter::new(Syntax::Att).radix(Radix::Hex)),
+            vec![
+                "mov %cx,-0x12c(%bp,%di)",
+                "movw $0x1,0x3e8",
+                "addb $0x22,(%bx)",
+                "mov 0xf,%al",
+                "cmp $0xfffe,%ax",
+                "jne .-0x6",
+                "jmp .+0x2",
+                "ret",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_data() {
+        let items = decode_items(vec![0xFF]);
+        assert_eq!(Formatter::default().item(&items[0]), "db 0xff ; unknown");
+        assert_eq!(
+            Formatter::new(Syntax::Masm).item(&items[0]),
+            "db 0ffh ; unknown"
+        );
+        assert_eq!(
+            Formatter::new(Syntax::Att)
+                .case(Case::Upper)
+                .item(&items[0]),
+            ".BYTE 0xFF # unknown"
+        );
+    }
+
+    // Every addressing form the decoder produces, as mod/rm bits, displacement
+    // bytes and the NASM text inside the brackets
+    const MEMORY_FORMS: [(u8, &[u8], &str); 25] = [
+        (0b00000000, &[], "bx + si"),
+        (0b00000001, &[], "bx + di"),
+        (0b00000010, &[], "bp + si"),
+        (0b00000011, &[], "bp + di"),
+        (0b00000100, &[], "si"),
+        (0b00000101, &[], "di"),
+        (0b00000110, &[0xE8, 0x03], "1000"),
+        (0b00000111, &[], "bx"),
+        (0b01000000, &[0xFC], "bx + si - 4"),
+        (0b01000001, &[0x04], "bx + di + 4"),
+        (0b01000010, &[0x04], "bp + si + 4"),
+        (0b01000011, &[0x04], "bp + di + 4"),
+        (0b01000100, &[0x04], "si + 4"),
+        (0b01000101, &[0x04], "di + 4"),
+        (0b01000110, &[0x00], "bp + 0"),
+        (0b01000111, &[0x04], "bx + 4"),
+        (0b10000000, &[0xD4, 0xFE], "bx + si - 300"),
+        (0b10000001, &[0x2C, 0x01], "bx + di + 300"),
+        (0b10000010, &[0x2C, 0x01], "bp + si + 300"),
+        (0b10000011, &[0x2C, 0x01], "bp + di + 300"),
+        (0b10000100, &[0x2C, 0x01], "si + 300"),
+        (0b10000101, &[0x2C, 0x01], "di + 300"),
+        (0b10000110, &[0x2C, 0x01], "bp + 300"),
+        (0b10000111, &[0x2C, 0x01], "bx + 300"),
+        (0b11000001, &[], ""),
+    ];
+
+    const SEGMENTS: [(u8, &str); 4] = [(0x26, "es"), (0x2E, "cs"), (0x36, "ss"), (0x3E, "ds")];
+
+    #[test]
+    fn test_segment_overrides() {
+        for (prefix, segment) in SEGMENTS {
+            for (modrm, disp, address) in MEMORY_FORMS {
+                // mov <rm>, dx with the override
+                let mut bytes = vec![prefix, 0b10001001, 0b00010000 | modrm];
+                bytes.extend(disp);
+                let instr = &decode(bytes.clone())[0];
+
+                let expected = match address {
+                    // A register operand leaves the override as a plain prefix
+                    "" => format!("{} mov cx, dx", segment),
+                    _ => format!("mov [{}:{}], dx", segment, address),
+                };
+                assert_eq!(instr.to_string(), expected);
+                assert_eq!(assemble(&expected), Ok(bytes));
+            }
+        }
+    }
+
+    #[test]
+    fn test_segment_override_syntaxes() {
+        // mov [es:bx + si + 4], cx; mov ax, [cs:1000]; es add [bx], byte 1
+        let bytes = vec![
+            0x26, 0x89, 0x48, 0x04, 0x2E, 0xA1, 0xE8, 0x03, 0x26, 0x80, 0x07, 0x01,
+        ];
+        let format = |formatter: Formatter| {
+            decode(bytes.clone())
+                .iter()
+                .map(|instr| formatter.instr(instr))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            format(Formatter::default()),
+            vec![
+                "mov [es:bx + si + 4], cx",
+                "mov ax, [cs:1000]",
+                "add [es:bx], byte 1"
+            ]
+        );
+        assert_eq!(
+            format(Formatter::new(Syntax::Masm)),
+            vec![
+                "mov es:[bx+si+4], cx",
+                "mov ax, cs:[1000]",
+                "add byte ptr es:[bx], 1"
+            ]
+        );
+        assert_eq!(
+            format(Formatter::new(Syntax::Att).case(Case::Upper)),
+            vec![
+                "MOV %CX,%ES:4(%BX,%SI)",
+                "MOV %CS:1000,%AX",
+                "ADDB $1,%ES:(%BX)"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lock_and_rep() {
+        let cases: [(&[u8], &str); 16] = [
+            (&[0xF3, 0xA4], "rep movsb"),
+            (&[0xF3, 0xA5], "rep movsw"),
+            (&[0xF3, 0xA6], "repe cmpsb"),
+            (&[0xF3, 0xA7], "repe cmpsw"),
+            (&[0xF3, 0xAA], "rep stosb"),
+            (&[0xF3, 0xAB], "rep stosw"),
+            (&[0xF3, 0xAC], "rep lodsb"),
+            (&[0xF3, 0xAD], "rep lodsw"),
+            (&[0xF3, 0xAE], "repe scasb"),
+            (&[0xF3, 0xAF], "repe scasw"),
+            (&[0xF2, 0xA6], "repne cmpsb"),
+            (&[0xF2, 0xAF], "repne scasw"),
+            (&[0x26, 0xF3, 0xA4], "es rep movsb"),
+            (&[0xF0, 0x87, 0x0F], "lock xchg cx, [bx]"),
+            (&[0xF0, 0x01, 0x07], "lock add [bx], ax"),
+            (&[0xF0, 0x36, 0x86, 0x46, 0x02], "lock xchg al, [ss:bp + 2]"),
+        ];
+        for (bytes, expected) in cases {
+            let instr = &decode(bytes.to_vec())[0];
+            assert_eq!(instr.to_string(), expected);
+            assert_eq!(assemble(expected), Ok(bytes.to_vec()), "{}", expected);
+        }
+    }
+
+    #[test]
+    fn test_prefix_order() {
+        // Only a single segment prefix that comes last goes in the operand,
+        // anything else is kept as written so no bytes are lost
+        let cases: [(&[u8], &str); 7] = [
+            (
+                &[0x26, 0xF0, 0xC7, 0x87, 0xE8, 0x03, 0xE8, 0x03],
+                "es lock mov [bx + 1000], word 1000",
+            ),
+            (
+                &[0xF0, 0x26, 0xC7, 0x87, 0xE8, 0x03, 0xE8, 0x03],
+                "lock mov [es:bx + 1000], word 1000",
+            ),
+            (&[0x26, 0x26, 0x89, 0x07], "es es mov [bx], ax"),
+            (&[0x26, 0x2E, 0x89, 0x07], "es cs mov [bx], ax"),
+            (&[0xF3, 0xF3, 0xA4], "rep rep movsb"),
+            (&[0x36, 0xF2, 0xF0, 0xAF], "ss repne lock scasw"),
+            (&[0xF0, 0x2E, 0x01, 0xC8], "lock cs add ax, cx"),
+        ];
+        for (bytes, expected) in cases {
+            let instr = &decode(bytes.to_vec())[0];
+            assert_eq!(instr.to_string(), expected);
+            assert_eq!(assemble(expected), Ok(bytes.to_vec()), "{}", expected);
+        }
+    }
+
+    #[test]
+    fn test_prefixed_jump_target() {
+        // cs je $+6, the prefix makes the jump a byte longer
+        let bytes = vec![0x2E, 0x74, 0x03];
+        let instr = &decode(bytes.clone())[0];
+
+        assert_eq!(instr.to_string(), "cs je $+6");
+        assert_eq!(assemble("cs je $+6"), Ok(bytes));
+    }
+
+    #[test]
+    fn test_parse_options() {
+        assert_eq!("AT&T".parse(), Ok(Syntax::Att));
+        assert_eq!("hex".parse(), Ok(Radix::Hex));
+        assert_eq!("upper".parse(), Ok(Case::Upper));
+        assert_eq!(
+            "intel64".parse::<Syntax>(),
+            Err("unknown syntax 'intel64', expected nasm, masm or att".to_string())
+        );
+    }
+}
@@ -1,10 +1,14 @@
 use std::fmt::Display;
 
 use crate::decoder::{
+    format::Formatter,
     jump::decode_jump,
     mov::{decode_mov, MoveInstr},
     op::decode_op,
+    prefix::{decode_prefix, AfterPrefixes, PrefixedInstr},
     state::Decoder,
+    string::{decode_string, StringInstr},
+    xchg::{decode_xchg, XchgInstr},
 };
 
 use super::op::OpInstr;
@@ -15,33 +19,108 @@ pub enum Instr {
     Op(OpInstr),
     Je(i8),
     Jne(i8),
+    Jmp(i8),
+    JmpNear(i16),
+    Call(i16),
+    Ret,
+    Hlt,
+    /// A software interrupt of this type
+    Int(u8),
+    Xchg(XchgInstr),
+    Str(StringInstr),
+    Nop,
+    Prefixed(PrefixedInstr),
 }
 
 impl Display for Instr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Formatter::default().instr(self))
+    }
+}
+
+impl Instr {
+    /// Where this instruction can transfer control to, other than the next
+    /// instruction at `next`
+    pub fn jump_target(&self, next: usize) -> Option<usize> {
+        let offset = match self {
+            Instr::Je(offset) | Instr::Jne(offset) | Instr::Jmp(offset) => *offset as i16,
+            Instr::JmpNear(offset) | Instr::Call(offset) => *offset,
+            Instr::Prefixed(prefixed) => return prefixed.instr.jump_target(next),
+            Instr::Mov(_)
+            | Instr::Op(_)
+            | Instr::Ret
+            | Instr::Hlt
+            | Instr::Int(_)
+            | Instr::Xchg(_)
+            | Instr::Str(_)
+            | Instr::Nop => return None,
+        };
+        Some((next as u16).wrapping_add_signed(offset) as usize)
+    }
+
+    /// Whether execution can continue with the next instruction
+    pub fn falls_through(&self) -> bool {
         match self {
-            Instr::Mov(mov) => write!(f, "{}", mov),
-            Instr::Op(op) => write!(f, "{}", op),
-            Instr::Je(offset) => write!(f, "je {}", offset),
-            Instr::Jne(offset) => write!(f, "jne {}", offset),
+            Instr::Prefixed(prefixed) => prefixed.instr.falls_through(),
+            _ => !matches!(
+                self,
+                Instr::Jmp(_) | Instr::JmpNear(_) | Instr::Ret | Instr::Hlt
+            ),
         }
     }
 }
 
+/// A jump offset written relative to the start of the jump, as NASM reads it
+pub struct JumpTarget(pub i32);
+
+impl JumpTarget {
+    // Offsets are relative to the end of the jump, so add its length
+    pub fn short(offset: i8) -> Self {
+        Self(offset as i32 + 2)
+    }
+
+    pub fn near(offset: i16) -> Self {
+        Self(offset as i32 + 3)
+    }
+}
+
 pub fn decode_instr<T: Decoder>(state: &mut T) -> Instr {
-    let instr = decode_mov(state)
-        .or_else(|| decode_op(state))
-        .or_else(|| decode_jump(state));
-    if let Some(instr) = instr {
+    if let Some(instr) = try_decode_instr(state) {
         instr
     } else {
         panic!("Unknown instruction: {:#10b} ", state.get_byte(0))
     }
 }
 
+pub fn try_decode_instr<T: Decoder>(state: &mut T) -> Option<Instr> {
+    let mut prefixes = vec![];
+    while let Some(prefix) = decode_prefix(state.get_byte(prefixes.len())) {
+        prefixes.push(prefix);
+    }
+    if prefixes.is_empty() {
+        return try_decode_unprefixed(state);
+    }
+    let len = prefixes.len();
+    state.add_len(len);
+    let instr = try_decode_unprefixed(&mut AfterPrefixes { inner: state, len })?;
+    Some(Instr::Prefixed(PrefixedInstr {
+        prefixes,
+        instr: Box::new(instr),
+    }))
+}
+
+fn try_decode_unprefixed<T: Decoder>(state: &mut T) -> Option<Instr> {
+    decode_mov(state)
+        .or_else(|| decode_op(state))
+        .or_else(|| decode_jump(state))
+        .or_else(|| decode_xchg(state))
+        .or_else(|| decode_string(state))
+}
+
 #[cfg(test)]
 mod test {
     use crate::decoder::{
+        common::Encoding,
         loc::Location,
         mov::{AX, BX},
         op::OpKind,
@@ -55,6 +134,7 @@ mod test {
         let mov = Instr::Mov(MoveInstr {
             dest: Location::Reg(AX),
             src: Location::Reg(BX),
+            encoding: Encoding::RmToFromReg { d: false },
         });
 
         assert_eq!(mov.to_string(), "mov ax, bx");
@@ -66,6 +146,7 @@ mod test {
             kind: OpKind::Add,
             dest: Location::Reg(AX),
             src: Location::Reg(BX),
+            encoding: Encoding::RmToFromReg { d: false },
         });
 
         assert_eq!(op.to_string(), "add ax, bx");
@@ -75,14 +156,39 @@ mod test {
     fn test_je_display() {
         let je = Instr::Je(0x12);
 
-        assert_eq!(je.to_string(), "je 18");
+        assert_eq!(je.to_string(), "je $+20");
     }
 
     #[test]
     fn test_jne_display() {
         let jne = Instr::Jne(0x12);
 
-        assert_eq!(jne.to_string(), "jne 18");
+        assert_eq!(jne.to_string(), "jne $+20");
+    }
+
+    #[test]
+    fn test_jump_backwards_display() {
+        assert_eq!(Instr::Jne(-8).to_string(), "jne $-6");
+        assert_eq!(Instr::Je(-2).to_string(), "je $+0");
+    }
+
+    #[test]
+    fn test_jmp_call_ret_display() {
+        assert_eq!(Instr::Jmp(4).to_string(), "jmp short $+6");
+        assert_eq!(Instr::JmpNear(-300).to_string(), "jmp near $-297");
+        assert_eq!(Instr::Call(0x100).to_string(), "call $+259");
+        assert_eq!(Instr::Ret.to_string(), "ret");
+    }
+
+    #[test]
+    fn test_jump_target() {
+        assert_eq!(Instr::Jne(-8).jump_target(14), Some(6));
+        assert_eq!(Instr::Call(-3).jump_target(2), Some(0xFFFF));
+        assert_eq!(Instr::Ret.jump_target(2), None);
+        assert!(Instr::Je(2).falls_through());
+        assert!(Instr::Call(2).falls_through());
+        assert!(!Instr::Jmp(2).falls_through());
+        assert!(!Instr::Ret.falls_through());
     }
 
     #[test]
@@ -0,0 +1,175 @@
+use crate::{
+    decoder::{
+        instr::Instr,
+        loc::{eac::EffectiveAddress, Location},
+        prefix::{Prefix, Rep, Segment},
+        Item,
+    },
+    json::Json,
+};
+
+/// A record for each decoded item, with the bytes it was decoded from
+pub fn item_json(src: &[u8], item: &Item) -> Json {
+    let bytes = src[item.addr()..item.addr() + item.byte_len()].to_vec();
+    match item {
+        Item::Instr(decoded) => {
+            let next = decoded.addr + decoded.len;
+            let mut fields = vec![
+                ("address".to_string(), decoded.addr.into()),
+                ("bytes".to_string(), bytes.into()),
+                ("text".to_string(), item.to_string().into()),
+            ];
+            if let Json::Object(instr) = instr_json(&decoded.instr, next) {
+                fields.extend(instr);
+            }
+            Json::Object(fields)
+        }
+        Item::Unknown { addr, .. } => Json::object([
+            ("address", (*addr).into()),
+            ("bytes", bytes.into()),
+            ("text", item.to_string().into()),
+            ("data", true.into()),
+        ]),
+        Item::Data { addr, .. } => Json::object([
+            ("address", (*addr).into()),
+            ("bytes", bytes.into()),
+            ("text", item.to_string().into()),
+            ("data", true.into()),
+        ]),
+    }
+}
+
+/// The mnemonic, prefixes and operands of `instr`, where `next` is the
+/// address of the following instruction for working out jump targets
+pub fn instr_json(instr: &Instr, next: usize) -> Json {
+    let (prefixes, segment, instr) = match instr {
+        Instr::Prefixed(prefixed) => (
+            prefixed.prefixes.as_slice(),
+            prefixed.segment(),
+            prefixed.instr.as_ref(),
+        ),
+        _ => (&[][..], None, instr),
+    };
+    let prefixes = prefixes.iter().map(prefix_name).collect::<Vec<_>>();
+    let locations = |dest, src| {
+        Json::Array(vec![
+            location_json(dest, segment),
+            location_json(src, segment),
+        ])
+    };
+    let (mnemonic, operands) = match instr {
+        Instr::Mov(mov) => ("mov".to_string(), locations(&mov.dest, &mov.src)),
+        Instr::Op(op) => (op.kind.to_string(), locations(&op.dest, &op.src)),
+        Instr::Xchg(xchg) => ("xchg".to_string(), locations(&xchg.dest, &xchg.src)),
+        Instr::Je(offset) => ("je".to_string(), relative(*offset as i16, instr, next)),
+        Instr::Jne(offset) => ("jne".to_string(), relative(*offset as i16, instr, next)),
+        Instr::Jmp(offset) => ("jmp".to_string(), relative(*offset as i16, instr, next)),
+        Instr::JmpNear(offset) => ("jmp".to_string(), relative(*offset, instr, next)),
+        Instr::Call(offset) => ("call".to_string(), relative(*offset, instr, next)),
+        Instr::Str(string) => (string.to_string(), Json::Array(vec![])),
+        Instr::Ret => ("ret".to_string(), Json::Array(vec![])),
+        Instr::Hlt => ("hlt".to_string(), Json::Array(vec![])),
+        Instr::Int(vector) => (
+            "int".to_string(),
+            Json::Array(vec![location_json(&Location::Immediate8(*vector), None)]),
+        ),
+        Instr::Nop => ("nop".to_string(), Json::Array(vec![])),
+        Instr::Prefixed(_) => unreachable!("Prefixes are never nested"),
+    };
+    Json::object([
+        ("mnemonic", mnemonic.into()),
+        ("prefixes", prefixes.into()),
+        ("operands", operands),
+    ])
+}
+
+fn relative(offset: i16, instr: &Instr, next: usize) -> Json {
+    Json::Array(vec![Json::object([
+        ("kind", "Rel".into()),
+        ("offset", offset.into()),
+        ("target", instr.jump_target(next).into()),
+    ])])
+}
+
+pub fn location_json(loc: &Location, segment: Option<Segment>) -> Json {
+    let segment = || Json::from(segment.map(|segment| segment.to_string()));
+    match loc {
+        Location::Reg(reg) => Json::object([("kind", "Reg".into()), ("reg", (*reg).into())]),
+        Location::Mem(addr) => Json::object([
+            ("kind", "Mem".into()),
+            ("segment", segment()),
+            ("address", (*addr).into()),
+        ]),
+        Location::Eac(eac) => {
+            let regs = eac.mode().regs().to_vec();
+            let disp = match eac {
+                EffectiveAddress::Mode(_) => Json::Null,
+                _ => eac.offset().into(),
+            };
+            Json::object([
+                ("kind", "Eac".into()),
+                ("segment", segment()),
+                ("regs", regs.into()),
+                ("disp", disp),
+            ])
+        }
+        Location::Immediate8(value) => {
+            Json::object([("kind", "Immediate8".into()), ("value", (*value).into())])
+        }
+        Location::Immediate16(value) => {
+            Json::object([("kind", "Immediate16".into()), ("value", (*value).into())])
+        }
+    }
+}
+
+fn prefix_name(prefix: &Prefix) -> String {
+    match prefix {
+        Prefix::Lock => "lock".to_string(),
+        Prefix::Rep(Rep::Repe) => "rep".to_string(),
+        Prefix::Rep(Rep::Repne) => "repne".to_string(),
+        Prefix::Segment(segment) => segment.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::decoder::{decode_items, json::item_json};
+
+    #[test]
+    fn test_instr_json() {
+        // mov [es:bx + si - 4], cx
+        let src = vec![0b00100110, 0b10001001, 0b1001000, 0b11111100];
+        let items = decode_items(src.clone());
+
+        assert_eq!(
+            item_json(&src, &items[0]).to_string(),
+            "{\"address\":0,\"bytes\":[38,137,72,252],\"text\":\"mov [es:bx + si - 4], cx\",\
+             \"mnemonic\":\"mov\",\"prefixes\":[\"es\"],\"operands\":[\
+             {\"kind\":\"Eac\",\"segment\":\"es\",\"regs\":[\"bx\",\"si\"],\"disp\":-4},\
+             {\"kind\":\"Reg\",\"reg\":\"cx\"}]}"
+        );
+    }
+
+    #[test]
+    fn test_immediate_and_jump_json() {
+        // mov [1000], word 1; jne $-6; db 0xff
+        let src = vec![
+            0b11000111, 0b110, 0b11101000, 0b11, 0b1, 0b0, 0b1110101, 0b11111000, 0xFF,
+        ];
+        let items = decode_items(src.clone());
+        let json = items
+            .iter()
+            .map(|item| item_json(&src, item).to_string())
+            .collect::<Vec<_>>();
+
+        assert!(json[0].contains(
+            "\"operands\":[{\"kind\":\"Mem\",\"segment\":null,\"address\":1000},\
+             {\"kind\":\"Immediate16\",\"value\":1}]"
+        ));
+        assert!(json[1].contains("\"operands\":[{\"kind\":\"Rel\",\"offset\":-8,\"target\":0}]"));
+        assert_eq!(
+            json[2],
+            "{\"address\":8,\"bytes\":[255],\"text\":\"db 0xff ; unknown\",\"data\":true}"
+        );
+    }
+}
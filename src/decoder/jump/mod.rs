@@ -15,10 +15,43 @@ pub fn decode_jump<T: Decoder>(state: &mut T) -> Option<Instr> {
             state.add_len(2);
             Some(Instr::Jne(to as i8))
         }
+        // Direct within segment short
+        _ if 0b11101011 == byte => {
+            let to = state.get_byte(1);
+            state.add_len(2);
+            Some(Instr::Jmp(to as i8))
+        }
+        // Direct within segment
+        _ if 0b11101001 == byte => Some(Instr::JmpNear(decode_near_offset(state))),
+        // Call direct within segment
+        _ if 0b11101000 == byte => Some(Instr::Call(decode_near_offset(state))),
+        // Return within segment
+        _ if 0b11000011 == byte => {
+            state.add_len(1);
+            Some(Instr::Ret)
+        }
+        // Halt, only an interrupt resumes execution
+        _ if 0b11110100 == byte => {
+            state.add_len(1);
+            Some(Instr::Hlt)
+        }
+        // Interrupt with the type given
+        _ if 0b11001101 == byte => {
+            let vector = state.get_byte(1);
+            state.add_len(2);
+            Some(Instr::Int(vector))
+        }
         _ => None,
     }
 }
 
+fn decode_near_offset<T: Decoder>(state: &mut T) -> i16 {
+    let low = state.get_byte(1);
+    let high = state.get_byte(2);
+    state.add_len(3);
+    ((high as u16) << 8 | low as u16) as i16
+}
+
 #[cfg(test)]
 mod test {
     use crate::decoder::{decode, instr::Instr};
@@ -38,4 +71,24 @@ mod test {
         assert_eq!(asm.len(), 1);
         assert_eq!(asm[0], Instr::Jne(7));
     }
+
+    #[test]
+    fn test_jmp_call_ret() {
+        let asm = decode(vec![
+            0b11101011, 0b11111110, 0b11101001, 0b11010100, 0b11111110, 0b11101000, 0b0, 0b1,
+            0b11000011, 0b11110100, 0b11001101, 0b100000,
+        ]);
+
+        assert_eq!(
+            asm,
+            vec![
+                Instr::Jmp(-2),
+                Instr::JmpNear(-300),
+                Instr::Call(256),
+                Instr::Ret,
+                Instr::Hlt,
+                Instr::Int(0x20)
+            ]
+        );
+    }
 }
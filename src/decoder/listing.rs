@@ -0,0 +1,238 @@
+use std::fmt::Write;
+
+use crate::decoder::{
+    format::Formatter,
+    instr::Instr,
+    loc::{eac::EffectiveAddress, Location},
+    Item,
+};
+
+// Without prefixes the longest instruction is 6 bytes: opcode, modrm, 2 disp,
+// 2 data. Prefixes have no limit, so the hex column widens to fit them.
+const MIN_HEX_BYTES: usize = 6;
+
+pub fn format_listing(src: &[u8], items: &[Item], fields: bool, formatter: &Formatter) -> String {
+    let hex_bytes = items
+        .iter()
+        .map(Item::byte_len)
+        .fold(MIN_HEX_BYTES, usize::max);
+    let mut out = String::new();
+    for item in items {
+        let line = format_listing_line(src, item, fields, formatter, hex_bytes);
+        writeln!(out, "{}", line).unwrap();
+    }
+    out
+}
+
+/// Formats one listing line, with the hex column padded to fit `hex_bytes`
+/// bytes
+pub fn format_listing_line(
+    src: &[u8],
+    item: &Item,
+    fields: bool,
+    formatter: &Formatter,
+    hex_bytes: usize,
+) -> String {
+    let bytes = &src[item.addr()..item.addr() + item.byte_len()];
+    let hex = bytes
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let mut line = format!(
+        "{:04X}: {:<width$}  {}",
+        item.addr(),
+        hex,
+        formatter.item(item),
+        width = hex_bytes * 3 - 1
+    );
+    if let (true, Item::Instr(decoded)) = (fields, item) {
+        let breakdown = format_fields(bytes, &decoded.instr);
+        if !breakdown.is_empty() {
+            write!(line, " {} {}", formatter.comment(), breakdown).unwrap();
+        }
+    }
+    line
+}
+
+pub fn format_fields(bytes: &[u8], instr: &Instr) -> String {
+    let mut parts = vec![];
+    if let Some((mod_, reg, rm)) = modrm_fields(bytes) {
+        parts.push(format!("mod={:02b} reg={:03b} rm={:03b}", mod_, reg, rm));
+    }
+    match instr {
+        Instr::Mov(mov) => {
+            push_location_fields(&mut parts, &mov.dest);
+            push_location_fields(&mut parts, &mov.src);
+        }
+        Instr::Op(op) => {
+            push_location_fields(&mut parts, &op.dest);
+            push_location_fields(&mut parts, &op.src);
+        }
+        Instr::Xchg(xchg) => {
+            push_location_fields(&mut parts, &xchg.dest);
+            push_location_fields(&mut parts, &xchg.src);
+        }
+        Instr::Je(offset) | Instr::Jne(offset) | Instr::Jmp(offset) => {
+            parts.push(format!("rel={}", offset))
+        }
+        Instr::JmpNear(offset) | Instr::Call(offset) => parts.push(format!("rel={}", offset)),
+        Instr::Int(vector) => parts.push(format!("imm={}", vector)),
+        Instr::Prefixed(prefixed) => {
+            let fields = format_fields(&bytes[prefixed.prefixes.len()..], &prefixed.instr);
+            if !fields.is_empty() {
+                parts.push(fields);
+            }
+        }
+        Instr::Ret | Instr::Hlt | Instr::Str(_) | Instr::Nop => {}
+    }
+    parts.join(" ")
+}
+
+fn push_location_fields(parts: &mut Vec<String>, loc: &Location) {
+    match loc {
+        Location::Reg(_) => {}
+        Location::Mem(addr) => parts.push(format!("disp={}", addr)),
+        Location::Immediate16(val) => parts.push(format!("imm={}", val)),
+        Location::Immediate8(val) => parts.push(format!("imm={}", val)),
+        Location::Eac(EffectiveAddress::Mode(_)) => {}
+        Location::Eac(eac) => parts.push(format!("disp={}", eac.offset())),
+    }
+}
+
+pub fn modrm_fields(bytes: &[u8]) -> Option<(u8, u8, u8)> {
+    let first = *bytes.first()?;
+    if !has_modrm(first) {
+        return None;
+    }
+    let second = *bytes.get(1)?;
+    Some((second >> 6, (second & 0b00111000) >> 3, second & 0b00000111))
+}
+
+pub fn has_modrm(opcode: u8) -> bool {
+    match opcode {
+        // mov Register/Memory to/from Register
+        _ if 0b10001000 == opcode & 0b11111100 => true,
+        // mov Immediate to Register/Memory
+        _ if 0b11000110 == opcode & 0b11111110 => true,
+        // op Register/Memory with Register to Either
+        _ if 0b00000000 == opcode & 0b11000100 => true,
+        // op Immediate to Register/Memory
+        _ if 0b10000000 == opcode & 0b11111100 => true,
+        // xchg Register/Memory with Register
+        _ if 0b10000110 == opcode & 0b11111110 => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::decoder::{
+        decode_items, decode_listing,
+        format::{Formatter, Syntax},
+        listing::*,
+    };
+
+    #[test]
+    fn test_listing_line() {
+        let src = vec![0b10001011, 0b1010110, 0b0];
+        let items = decode_items(src.clone());
+
+        assert_eq!(items.len(), 1);
+        assert_eq!(
+            format_listing_line(&src, &items[0], false, &Formatter::default(), 6),
+            "0000: 8B 56 00           mov dx, [bp + 0]"
+        );
+    }
+
+    #[test]
+    fn test_listing_addresses() {
+        let src = vec![0b10110001, 0b1100, 0b10111001, 0b1100, 0b0];
+        let items = decode_items(src.clone());
+
+        assert_eq!(
+            format_listing(&src, &items, false, &Formatter::default()),
+            "0000: B1 0C              mov cl, 12\n0002: B9 0C 00           mov cx, 12\n"
+        );
+    }
+
+    #[test]
+    fn test_listing_fields() {
+        let src = vec![0b10001011, 0b1010110, 0b0];
+        let items = decode_items(src.clone());
+
+        assert_eq!(
+            format_listing_line(&src, &items[0], true, &Formatter::default(), 6),
+            "0000: 8B 56 00           mov dx, [bp + 0] ; mod=01 reg=010 rm=110 disp=0"
+        );
+    }
+
+    #[test]
+    fn test_listing_unknown() {
+        let src = vec![0b11111111, 0b10110001, 0b1100];
+        let items = decode_items(src.clone());
+
+        assert_eq!(
+            format_listing(&src, &items, true, &Formatter::default()),
+            "0000: FF                 db 0xff ; unknown\n0001: B1 0C              mov cl, 12 ; imm=12\n"
+        );
+    }
+
+    #[test]
+    fn test_listing_syntax() {
+        let src = vec![0b10001011, 0b1010110, 0b0];
+        let items = decode_items(src.clone());
+
+        assert_eq!(
+            format_listing(&src, &items, true, &Formatter::new(Syntax::Att)),
+            "0000: 8B 56 00           mov 0(%bp),%dx # mod=01 reg=010 rm=110 disp=0\n"
+        );
+    }
+
+    #[test]
+    fn test_fields_imm_to_mem() {
+        let src = vec![0b11000111, 0b110, 0b11101000, 0b11, 0b1, 0b0];
+        let decoded = decode_listing(src.clone());
+
+        assert_eq!(
+            format_fields(&src, &decoded[0].instr),
+            "mod=00 reg=000 rm=110 disp=1000 imm=1"
+        );
+    }
+
+    #[test]
+    fn test_listing_prefixed() {
+        let src = vec![0b00100110, 0b10001000, 0b1000, 0b11110011, 0b10100100];
+        let items = decode_items(src.clone());
+
+        assert_eq!(
+            format_listing(&src, &items, true, &Formatter::default()),
+            "0000: 26 88 08           mov [es:bx + si], cl ; mod=00 reg=001 rm=000\n\
+             0003: F3 A4              rep movsb\n"
+        );
+    }
+
+    #[test]
+    fn test_listing_widens_for_prefixes() {
+        // es lock mov [bx + 1000], word 1000; mov cl, 12
+        let src = vec![
+            0b00100110, 0b11110000, 0b11000111, 0b10000111, 0b11101000, 0b11, 0b11101000, 0b11,
+            0b10110001, 0b1100,
+        ];
+        let items = decode_items(src.clone());
+
+        assert_eq!(
+            format_listing(&src, &items, false, &Formatter::default()),
+            "0000: 26 F0 C7 87 E8 03 E8 03  es lock mov [bx + 1000], word 1000\n\
+             0008: B1 0C                    mov cl, 12\n"
+        );
+    }
+
+    #[test]
+    fn test_fields_no_modrm() {
+        let src = vec![0b01110101, 0b11111000];
+        let decoded = decode_listing(src.clone());
+
+        assert_eq!(format_fields(&src, &decoded[0].instr), "rel=-8");
+    }
+}
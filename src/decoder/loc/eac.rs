@@ -1,6 +1,6 @@
 use std::fmt::Display;
 
-use crate::decoder::state::Decoder;
+use crate::decoder::{format::Formatter, state::Decoder};
 
 use super::eac_mode::{decode_eac_mode, EffectiveAddressMode};
 
@@ -31,23 +31,7 @@ impl EffectiveAddress {
 
 impl Display for EffectiveAddress {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            EffectiveAddress::Mode(mode) => write!(f, "{}", mode),
-            EffectiveAddress::Byte(mode, offset) => {
-                if *offset < 0 {
-                    write!(f, "{} - {}", mode, offset.abs())
-                } else {
-                    write!(f, "{} + {}", mode, offset)
-                }
-            }
-            EffectiveAddress::Word(mode, offset) => {
-                if *offset < 0 {
-                    write!(f, "{} - {}", mode, offset.abs())
-                } else {
-                    write!(f, "{} + {}", mode, offset)
-                }
-            }
-        }
+        write!(f, "{}", Formatter::default().address(self))
     }
 }
 
@@ -76,6 +60,7 @@ pub fn decode_eac<T: Decoder>(state: &mut T) -> EffectiveAddress {
 #[cfg(test)]
 mod test {
     use crate::decoder::{
+        common::Encoding,
         decode,
         instr::Instr,
         loc::{eac::EffectiveAddress, eac_mode::EffectiveAddressMode, Location},
@@ -95,6 +80,7 @@ mod test {
             Instr::Mov(MoveInstr {
                 dest: Location::Reg(AL),
                 src: Location::Eac(EffectiveAddress::Mode(EffectiveAddressMode::BxSi)),
+                encoding: Encoding::RmToFromReg { d: true },
             })
         );
 
@@ -103,6 +89,7 @@ mod test {
             Instr::Mov(MoveInstr {
                 dest: Location::Reg(BX),
                 src: Location::Eac(EffectiveAddress::Mode(EffectiveAddressMode::BpDi)),
+                encoding: Encoding::RmToFromReg { d: true },
             })
         );
 
@@ -111,6 +98,7 @@ mod test {
             Instr::Mov(MoveInstr {
                 dest: Location::Reg(DX),
                 src: Location::Eac(EffectiveAddress::Byte(EffectiveAddressMode::Bp, 0)),
+                encoding: Encoding::RmToFromReg { d: true },
             })
         );
     }
@@ -126,6 +114,7 @@ mod test {
             Instr::Mov(MoveInstr {
                 dest: Location::Reg(AH),
                 src: Location::Eac(EffectiveAddress::Byte(EffectiveAddressMode::BxSi, 4)),
+                encoding: Encoding::RmToFromReg { d: true },
             })
         );
     }
@@ -141,6 +130,7 @@ mod test {
             Instr::Mov(MoveInstr {
                 dest: Location::Reg(AL),
                 src: Location::Eac(EffectiveAddress::Word(EffectiveAddressMode::BxSi, 4999)),
+                encoding: Encoding::RmToFromReg { d: true },
             })
         );
     }
@@ -158,6 +148,7 @@ mod test {
             Instr::Mov(MoveInstr {
                 dest: Location::Eac(EffectiveAddress::Mode(EffectiveAddressMode::BxDi,)),
                 src: Location::Reg(CX),
+                encoding: Encoding::RmToFromReg { d: false },
             })
         );
 
@@ -166,6 +157,7 @@ mod test {
             Instr::Mov(MoveInstr {
                 dest: Location::Eac(EffectiveAddress::Mode(EffectiveAddressMode::BpSi,)),
                 src: Location::Reg(CL),
+                encoding: Encoding::RmToFromReg { d: false },
             })
         );
 
@@ -174,6 +166,7 @@ mod test {
             Instr::Mov(MoveInstr {
                 dest: Location::Eac(EffectiveAddress::Byte(EffectiveAddressMode::Bp, 0)),
                 src: Location::Reg(CH),
+                encoding: Encoding::RmToFromReg { d: false },
             })
         );
     }
@@ -191,6 +184,7 @@ mod test {
             Instr::Mov(MoveInstr {
                 dest: Location::Reg(BP),
                 src: Location::Mem(5),
+                encoding: Encoding::RmToFromReg { d: true },
             })
         );
 
@@ -199,6 +193,7 @@ mod test {
             Instr::Mov(MoveInstr {
                 dest: Location::Reg(BX),
                 src: Location::Mem(3458),
+                encoding: Encoding::RmToFromReg { d: true },
             })
         );
     }
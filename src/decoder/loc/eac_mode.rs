@@ -1,5 +1,7 @@
 use std::fmt::Display;
 
+use crate::decoder::mov::{BP, BX, DI, SI};
+
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum EffectiveAddressMode {
     BxSi,
@@ -12,21 +14,28 @@ pub enum EffectiveAddressMode {
     Bx,
 }
 
-impl Display for EffectiveAddressMode {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl EffectiveAddressMode {
+    /// The registers summed to form the address
+    pub fn regs(&self) -> &'static [&'static str] {
         match self {
-            EffectiveAddressMode::BxSi => write!(f, "bx + si"),
-            EffectiveAddressMode::BxDi => write!(f, "bx + di"),
-            EffectiveAddressMode::BpSi => write!(f, "bp + si"),
-            EffectiveAddressMode::BpDi => write!(f, "bp + di"),
-            EffectiveAddressMode::Si => write!(f, "si"),
-            EffectiveAddressMode::Di => write!(f, "di"),
-            EffectiveAddressMode::Bp => write!(f, "bp"),
-            EffectiveAddressMode::Bx => write!(f, "bx"),
+            EffectiveAddressMode::BxSi => &[BX, SI],
+            EffectiveAddressMode::BxDi => &[BX, DI],
+            EffectiveAddressMode::BpSi => &[BP, SI],
+            EffectiveAddressMode::BpDi => &[BP, DI],
+            EffectiveAddressMode::Si => &[SI],
+            EffectiveAddressMode::Di => &[DI],
+            EffectiveAddressMode::Bp => &[BP],
+            EffectiveAddressMode::Bx => &[BX],
         }
     }
 }
 
+impl Display for EffectiveAddressMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.regs().join(" + "))
+    }
+}
+
 pub fn decode_eac_mode(byte: u8) -> EffectiveAddressMode {
     match byte {
         0b000 => EffectiveAddressMode::BxSi,
@@ -1,6 +1,6 @@
 use std::fmt::Display;
 
-use crate::sim::is_byte;
+use crate::{decoder::format::Formatter, sim::is_byte};
 
 use self::eac::EffectiveAddress;
 
@@ -18,16 +18,11 @@ pub enum Location {
 
 impl Display for Location {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Location::Reg(reg) => write!(f, "{}", reg),
-            Location::Mem(addr) => write!(f, "[{}]", addr),
-            Location::Immediate16(val) => write!(f, "{}", val),
-            Location::Immediate8(val) => write!(f, "{}", val),
-            Location::Eac(eac) => write!(f, "[{}]", eac),
-        }
+        write!(f, "{}", Formatter::default().location(self))
     }
 }
 
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Size {
     Byte,
     Word,
@@ -1,23 +1,188 @@
+use std::fmt::Display;
+
 use crate::decoder::{
-    instr::{decode_instr, Instr},
+    format::Formatter,
+    instr::{decode_instr, try_decode_instr, Instr},
     state::Decoder,
 };
 
 use self::state::DecoderState;
 
+pub mod cfg;
 pub mod common;
+pub mod format;
 pub mod instr;
+pub mod json;
 pub mod jump;
+pub mod listing;
 pub mod loc;
 pub mod mov;
 pub mod op;
+pub mod prefix;
 pub mod state;
+pub mod string;
+pub mod traverse;
+pub mod xchg;
+
+#[derive(Debug, PartialEq)]
+pub struct Decoded {
+    pub addr: usize,
+    pub len: usize,
+    pub instr: Instr,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Item {
+    Instr(Decoded),
+    /// A byte that doesn't start a known instruction
+    Unknown {
+        addr: usize,
+        byte: u8,
+    },
+    /// Bytes that are never reached as code
+    Data {
+        addr: usize,
+        bytes: Vec<u8>,
+    },
+}
+
+impl Item {
+    pub fn addr(&self) -> usize {
+        match self {
+            Item::Instr(decoded) => decoded.addr,
+            Item::Unknown { addr, .. } => *addr,
+            Item::Data { addr, .. } => *addr,
+        }
+    }
+
+    /// How many bytes of the program the item covers
+    pub fn byte_len(&self) -> usize {
+        match self {
+            Item::Instr(decoded) => decoded.len,
+            Item::Unknown { .. } => 1,
+            Item::Data { bytes, .. } => bytes.len(),
+        }
+    }
+}
+
+impl Display for Item {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Formatter::default().item(self))
+    }
+}
 
 pub fn decode(bytes: Vec<u8>) -> Vec<Instr> {
+    decode_listing(bytes)
+        .into_iter()
+        .map(|decoded| decoded.instr)
+        .collect()
+}
+
+pub fn decode_listing(bytes: Vec<u8>) -> Vec<Decoded> {
     let mut found = vec![];
     let mut state = DecoderState::new(bytes);
     while state.next() {
-        found.push(decode_instr(&mut state));
+        let instr = decode_instr(&mut state);
+        if state.is_truncated() {
+            panic!("Out of instr bounds");
+        }
+        found.push(Decoded {
+            addr: state.offset,
+            len: state.get_instr_len(),
+            instr,
+        });
     }
     found
 }
+
+/// Decodes as much as possible, emitting bytes that can't be decoded as
+/// unknown and carrying on from the next byte
+pub fn decode_items(bytes: Vec<u8>) -> Vec<Item> {
+    let mut found = vec![];
+    let mut state = DecoderState::new(bytes);
+    while state.next() {
+        found.push(decode_item(&mut state));
+    }
+    found
+}
+
+/// Decodes the item at the current offset, leaving `instr_len` set to its length
+pub fn decode_item(state: &mut DecoderState) -> Item {
+    match try_decode_instr(state) {
+        Some(instr) if !state.is_truncated() => Item::Instr(Decoded {
+            addr: state.offset,
+            len: state.get_instr_len(),
+            instr,
+        }),
+        _ => {
+            state.instr_len = 1;
+            Item::Unknown {
+                addr: state.offset,
+                byte: state.src[state.offset],
+            }
+        }
+    }
+}
+
+/// The number of bytes that were not decoded as instructions
+pub fn count_data_bytes(items: &[Item]) -> usize {
+    items
+        .iter()
+        .filter(|item| !matches!(item, Item::Instr(_)))
+        .map(Item::byte_len)
+        .sum()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::decoder::{count_data_bytes, decode_items, Item};
+
+    #[test]
+    fn test_decode_items_resync() {
+        let items = decode_items(vec![0b11111111, 0b10110001, 0b1100, 0b1000, 0b10001001]);
+
+        assert_eq!(items.len(), 4);
+        assert_eq!(
+            items[0],
+            Item::Unknown {
+                addr: 0,
+                byte: 0xFF
+            }
+        );
+        assert_eq!(items[1].to_string(), "mov cl, 12");
+        assert_eq!(items[1].addr(), 1);
+        // or isn't supported yet, so it's data too
+        assert_eq!(items[2].to_string(), "db 0x08 ; unknown");
+        // a truncated mov at the end
+        assert_eq!(
+            items[3],
+            Item::Unknown {
+                addr: 4,
+                byte: 0b10001001
+            }
+        );
+        assert_eq!(count_data_bytes(&items), 3);
+    }
+
+    #[test]
+    fn test_data_display() {
+        let words = Item::Data {
+            addr: 0,
+            bytes: vec![0x34, 0x12, 0xFF, 0x00],
+        };
+        let bytes = Item::Data {
+            addr: 0,
+            bytes: vec![0x34, 0x12, 0xFF],
+        };
+
+        assert_eq!(words.to_string(), "dw 0x1234, 0x00ff");
+        assert_eq!(bytes.to_string(), "db 0x34, 0x12, 0xff");
+        assert_eq!(count_data_bytes(&[words, bytes]), 7);
+    }
+
+    #[test]
+    #[should_panic(expected = "Out of instr bounds")]
+    fn test_decode_listing_truncated() {
+        crate::decoder::decode_listing(vec![0b10111001, 0b1100]);
+    }
+}
@@ -1,51 +1,38 @@
-use crate::decoder::{loc::Location, mov::MoveInstr, state::Decoder};
+use crate::decoder::{common::Encoding, loc::Location, mov::MoveInstr, state::Decoder};
 
 pub fn decode_mem_to_acc<T: Decoder>(state: &mut T) -> MoveInstr {
     let first = state.get_byte(0);
-    state.add_len(1);
     let w = first & 0b00000001;
-    if w == 0 {
-        let low = state.get_byte(1);
-        MoveInstr {
-            dest: Location::Reg("al"),
-            src: Location::Mem(low as u16),
-        }
-    } else {
-        let low = state.get_byte(1);
-        let high = state.get_byte(2);
-        state.add_len(2);
-        MoveInstr {
-            dest: Location::Reg("ax"),
-            src: Location::Mem((high as u16) << 8 | low as u16),
-        }
+    let addr = decode_addr(state);
+    MoveInstr {
+        dest: Location::Reg(if w == 0 { "al" } else { "ax" }),
+        src: Location::Mem(addr),
+        encoding: Encoding::Acc,
     }
 }
 
 pub fn decode_acc_to_mem<T: Decoder>(state: &mut T) -> MoveInstr {
     let first = state.get_byte(0);
-    state.add_len(1);
     let w = first & 0b00000001;
-    if w == 0 {
-        let low = state.get_byte(1);
-        state.add_len(1);
-        MoveInstr {
-            dest: Location::Mem(low as u16),
-            src: Location::Reg("al"),
-        }
-    } else {
-        let low = state.get_byte(1);
-        let high = state.get_byte(2);
-        state.add_len(2);
-        MoveInstr {
-            dest: Location::Mem((high as u16) << 8 | low as u16),
-            src: Location::Reg("ax"),
-        }
+    let addr = decode_addr(state);
+    MoveInstr {
+        dest: Location::Mem(addr),
+        src: Location::Reg(if w == 0 { "al" } else { "ax" }),
+        encoding: Encoding::Acc,
     }
 }
 
+// The address is always a full word, regardless of w
+fn decode_addr<T: Decoder>(state: &mut T) -> u16 {
+    let low = state.get_byte(1);
+    let high = state.get_byte(2);
+    state.add_len(3);
+    (high as u16) << 8 | low as u16
+}
+
 #[cfg(test)]
 mod test {
-    use crate::decoder::{decode, instr::Instr, loc::Location, mov::MoveInstr};
+    use crate::decoder::{common::Encoding, decode, instr::Instr, loc::Location, mov::MoveInstr};
 
     #[test]
     fn test_memory_to_acc() {
@@ -60,6 +47,7 @@ mod test {
             Instr::Mov(MoveInstr {
                 dest: Location::Reg("ax"),
                 src: Location::Mem(2555),
+                encoding: Encoding::Acc,
             })
         );
 
@@ -68,6 +56,7 @@ mod test {
             Instr::Mov(MoveInstr {
                 dest: Location::Reg("ax"),
                 src: Location::Mem(16),
+                encoding: Encoding::Acc,
             })
         );
     }
@@ -85,6 +74,7 @@ mod test {
             Instr::Mov(MoveInstr {
                 dest: Location::Mem(2554),
                 src: Location::Reg("ax"),
+                encoding: Encoding::Acc,
             })
         );
 
@@ -93,6 +83,7 @@ mod test {
             Instr::Mov(MoveInstr {
                 dest: Location::Mem(15),
                 src: Location::Reg("ax"),
+                encoding: Encoding::Acc,
             })
         );
     }
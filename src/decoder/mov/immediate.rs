@@ -1,6 +1,7 @@
 #[cfg(test)]
 mod test {
     use crate::decoder::{
+        common::Encoding,
         decode,
         instr::Instr,
         loc::Location,
@@ -17,6 +18,7 @@ mod test {
             Instr::Mov(MoveInstr {
                 dest: Location::Reg(CL),
                 src: Location::Immediate8(12),
+                encoding: Encoding::ImmToReg,
             })
         );
         assert_eq!(
@@ -24,6 +26,7 @@ mod test {
             Instr::Mov(MoveInstr {
                 dest: Location::Reg(CH),
                 src: Location::Immediate8(244),
+                encoding: Encoding::ImmToReg,
             })
         );
     }
@@ -42,6 +45,7 @@ mod test {
             Instr::Mov(MoveInstr {
                 dest: Location::Reg(CX),
                 src: Location::Immediate16(12),
+                encoding: Encoding::ImmToReg,
             })
         );
 
@@ -50,6 +54,7 @@ mod test {
             Instr::Mov(MoveInstr {
                 dest: Location::Reg(CX),
                 src: Location::Immediate16(65524),
+                encoding: Encoding::ImmToReg,
             })
         );
 
@@ -58,6 +63,7 @@ mod test {
             Instr::Mov(MoveInstr {
                 dest: Location::Reg(DX),
                 src: Location::Immediate16(3948),
+                encoding: Encoding::ImmToReg,
             })
         );
 
@@ -66,6 +72,7 @@ mod test {
             Instr::Mov(MoveInstr {
                 dest: Location::Reg(DX),
                 src: Location::Immediate16(61588),
+                encoding: Encoding::ImmToReg,
             })
         );
     }
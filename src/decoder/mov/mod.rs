@@ -4,8 +4,9 @@ use std::fmt::Display;
 use crate::decoder::{
     common::{
         imm_to_reg::decode_imm_to_reg, imm_to_rm::decode_imm_to_rm,
-        rm_to_reg::decode_rm_to_from_reg,
+        rm_to_reg::decode_rm_to_from_reg, Encoding,
     },
+    format::Formatter,
     instr::Instr,
     mov::acc::{decode_acc_to_mem, decode_mem_to_acc},
     state::Decoder,
@@ -20,6 +21,7 @@ pub mod immediate;
 pub struct MoveInstr {
     pub dest: Location,
     pub src: Location,
+    pub encoding: Encoding,
 }
 
 pub const AX: &str = "ax";
@@ -46,17 +48,30 @@ pub fn decode_mov<T: Decoder>(state: &mut T) -> Option<Instr> {
         // Register/Memory to/from Register
         _ if 0b10001000 == byte & 0b11111100 => {
             let (dest, src) = decode_rm_to_from_reg(state);
-            Some(Instr::Mov(MoveInstr { dest, src }))
+            let d = byte & 0b00000010 != 0;
+            Some(Instr::Mov(MoveInstr {
+                dest,
+                src,
+                encoding: Encoding::RmToFromReg { d },
+            }))
         }
         // Immediate to Register/Memory
         _ if 0b10110000 == byte & 0b11110000 => {
             let (dest, src) = decode_imm_to_reg(state);
-            Some(Instr::Mov(MoveInstr { dest, src }))
+            Some(Instr::Mov(MoveInstr {
+                dest,
+                src,
+                encoding: Encoding::ImmToReg,
+            }))
         }
         // Immediate to Register
         _ if 0b11000110 == byte & 0b11111110 => {
             let (dest, src) = decode_imm_to_rm(state);
-            Some(Instr::Mov(MoveInstr { dest, src }))
+            Some(Instr::Mov(MoveInstr {
+                dest,
+                src,
+                encoding: Encoding::ImmToRm { s: false },
+            }))
         }
         // Memory to Accumulator
         _ if 0b10100000 == byte & 0b11111110 => Some(Instr::Mov(decode_mem_to_acc(state))),
@@ -90,15 +105,11 @@ pub fn decode_reg(w: u8, reg: u8) -> &'static str {
 
 impl Display for MoveInstr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match (&self.dest, &self.src) {
-            (Location::Eac(_), Location::Immediate8(_)) => {
-                write!(f, "mov {}, byte {}", self.dest, self.src)
-            }
-            (Location::Eac(_), Location::Immediate16(_)) => {
-                write!(f, "mov {}, word {}", self.dest, self.src)
-            }
-            _ => write!(f, "mov {}, {}", self.dest, self.src),
-        }
+        write!(
+            f,
+            "{}",
+            Formatter::default().two_operand("mov", &self.dest, &self.src)
+        )
     }
 }
 
@@ -107,6 +118,7 @@ mod test {
     use crate::{
         decoder::{
             common::rm_to_reg::decode_rm_to_from_reg,
+            common::Encoding,
             decode,
             instr::Instr,
             loc::{eac::EffectiveAddress, eac_mode::EffectiveAddressMode},
@@ -140,77 +152,88 @@ mod test {
             asm[0],
             Instr::Mov(MoveInstr {
                 dest: Location::Reg(CX),
-                src: Location::Reg(BX)
+                src: Location::Reg(BX),
+                encoding: Encoding::RmToFromReg { d: false },
             })
         );
         assert_eq!(
             asm[1],
             Instr::Mov(MoveInstr {
                 dest: Location::Reg(CH),
-                src: Location::Reg(AH)
+                src: Location::Reg(AH),
+                encoding: Encoding::RmToFromReg { d: false },
             })
         );
         assert_eq!(
             asm[2],
             Instr::Mov(MoveInstr {
                 dest: Location::Reg(DX),
-                src: Location::Reg(BX)
+                src: Location::Reg(BX),
+                encoding: Encoding::RmToFromReg { d: false },
             })
         );
         assert_eq!(
             asm[3],
             Instr::Mov(MoveInstr {
                 dest: Location::Reg(SI),
-                src: Location::Reg(BX)
+                src: Location::Reg(BX),
+                encoding: Encoding::RmToFromReg { d: false },
             })
         );
         assert_eq!(
             asm[4],
             Instr::Mov(MoveInstr {
                 dest: Location::Reg(BX),
-                src: Location::Reg(DI)
+                src: Location::Reg(DI),
+                encoding: Encoding::RmToFromReg { d: false },
             })
         );
         assert_eq!(
             asm[5],
             Instr::Mov(MoveInstr {
                 dest: Location::Reg(AL),
-                src: Location::Reg(CL)
+                src: Location::Reg(CL),
+                encoding: Encoding::RmToFromReg { d: false },
             })
         );
         assert_eq!(
             asm[6],
             Instr::Mov(MoveInstr {
                 dest: Location::Reg(CH),
-                src: Location::Reg(CH)
+                src: Location::Reg(CH),
+                encoding: Encoding::RmToFromReg { d: false },
             })
         );
         assert_eq!(
             asm[7],
             Instr::Mov(MoveInstr {
                 dest: Location::Reg(BX),
-                src: Location::Reg(AX)
+                src: Location::Reg(AX),
+                encoding: Encoding::RmToFromReg { d: false },
             })
         );
         assert_eq!(
             asm[8],
             Instr::Mov(MoveInstr {
                 dest: Location::Reg(BX),
-                src: Location::Reg(SI)
+                src: Location::Reg(SI),
+                encoding: Encoding::RmToFromReg { d: false },
             })
         );
         assert_eq!(
             asm[9],
             Instr::Mov(MoveInstr {
                 dest: Location::Reg(SP),
-                src: Location::Reg(DI)
+                src: Location::Reg(DI),
+                encoding: Encoding::RmToFromReg { d: false },
             })
         );
         assert_eq!(
             asm[10],
             Instr::Mov(MoveInstr {
                 dest: Location::Reg(BP),
-                src: Location::Reg(AX)
+                src: Location::Reg(AX),
+                encoding: Encoding::RmToFromReg { d: false },
             })
         );
     }
@@ -220,6 +243,7 @@ mod test {
         let mov = Instr::Mov(MoveInstr {
             dest: Location::Reg(AX),
             src: Location::Reg(BX),
+            encoding: Encoding::RmToFromReg { d: false },
         });
 
         assert_eq!(mov.to_string(), "mov ax, bx");
@@ -230,6 +254,7 @@ mod test {
         let mov = Instr::Mov(MoveInstr {
             dest: Location::Eac(EffectiveAddress::Mode(EffectiveAddressMode::BxSi)),
             src: Location::Immediate16(123),
+            encoding: Encoding::ImmToRm { s: false },
         });
 
         assert_eq!(mov.to_string(), "mov [bx + si], word 123");
@@ -240,6 +265,7 @@ mod test {
         let mov = Instr::Mov(MoveInstr {
             dest: Location::Eac(EffectiveAddress::Mode(EffectiveAddressMode::BxSi)),
             src: Location::Immediate8(123),
+            encoding: Encoding::ImmToRm { s: false },
         });
 
         assert_eq!(mov.to_string(), "mov [bx + si], byte 123");
@@ -256,7 +282,7 @@ mod test {
             0b11101110, 0b11,
         ]);
 
-        state.run();
+        state.run().unwrap();
 
         assert_eq!(state.get_register_16("bx"), 1);
         assert_eq!(state.get_register_16("cx"), 2);
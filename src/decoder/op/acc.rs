@@ -1,6 +1,7 @@
 #[cfg(test)]
 mod test {
     use crate::decoder::{
+        common::Encoding,
         decode,
         instr::Instr,
         loc::Location,
@@ -20,6 +21,7 @@ mod test {
                 kind: OpKind::Add,
                 dest: Location::Reg(AL),
                 src: Location::Immediate8(12),
+                encoding: Encoding::ImmToAcc,
             })
         );
     }
@@ -1,7 +1,11 @@
 use std::fmt::Display;
 
 use crate::decoder::{
-    common::rm_to_reg::{decode_rm_to_from_reg, decode_rm_to_reg},
+    common::{
+        rm_to_reg::{decode_rm_to_from_reg, decode_rm_to_reg},
+        Encoding,
+    },
+    format::Formatter,
     instr::Instr,
     mov::{AL, AX},
     state::Decoder,
@@ -23,6 +27,7 @@ pub struct OpInstr {
     pub kind: OpKind,
     pub dest: Location,
     pub src: Location,
+    pub encoding: Encoding,
 }
 
 pub fn decode_op<T: Decoder>(state: &mut T) -> Option<Instr> {
@@ -30,23 +35,27 @@ pub fn decode_op<T: Decoder>(state: &mut T) -> Option<Instr> {
     match byte {
         // Register/Memory with Register to Either
         _ if 0b00000000 == byte & 0b11000100 => {
+            let kind = decode_op_kind((byte & 0b00111000) >> 3)?;
             let (dest, src) = decode_rm_to_from_reg(state);
-            let kind = (byte & 0b00111000) >> 3;
+            let d = byte & 0b00000010 != 0;
             Some(Instr::Op(OpInstr {
-                kind: decode_op_kind(kind),
+                kind,
                 dest,
                 src,
+                encoding: Encoding::RmToFromReg { d },
             }))
         }
         // Immediate to Register/Memory
-        _ if 0b10000000 == byte & 0b11000100 => {
+        _ if 0b10000000 == byte & 0b11111100 => {
             let second = state.get_byte(1);
-            let op = (second & 0b00111000) >> 3;
+            let kind = decode_op_kind((second & 0b00111000) >> 3)?;
             let (dest, src) = decode_rm_to_reg(state);
+            let s = byte & 0b00000010 != 0;
             Some(Instr::Op(OpInstr {
-                kind: decode_op_kind(op),
+                kind,
                 dest,
                 src,
+                encoding: Encoding::ImmToRm { s },
             }))
         }
         // Immediate to Accumulator
@@ -57,30 +66,28 @@ pub fn decode_op<T: Decoder>(state: &mut T) -> Option<Instr> {
 
 fn decode_imm_to_acc<T: Decoder>(state: &mut T) -> Option<Instr> {
     let byte = state.get_byte(0);
+    let kind = decode_op_kind((byte & 0b00111000) >> 3)?;
     state.add_len(1);
     let w = 0b00000001 & byte;
-    if w == 0 {
-        let dest = Location::Reg(AL);
+    let (dest, src) = if w == 0 {
         let data = state.get_byte(1);
         state.add_len(1);
-        let src = Location::Immediate8(data);
-        Some(Instr::Op(OpInstr {
-            kind: OpKind::Add,
-            dest,
-            src,
-        }))
+        (Location::Reg(AL), Location::Immediate8(data))
     } else {
-        let dest = Location::Reg(AX);
         let low = state.get_byte(1);
         let high = state.get_byte(2);
         state.add_len(2);
-        let src = Location::Immediate16((high as u16) << 8 | low as u16);
-        Some(Instr::Op(OpInstr {
-            kind: OpKind::Add,
-            dest,
-            src,
-        }))
-    }
+        (
+            Location::Reg(AX),
+            Location::Immediate16((high as u16) << 8 | low as u16),
+        )
+    };
+    Some(Instr::Op(OpInstr {
+        kind,
+        dest,
+        src,
+        encoding: Encoding::ImmToAcc,
+    }))
 }
 
 impl Display for OpKind {
@@ -93,32 +100,29 @@ impl Display for OpKind {
     }
 }
 
-pub fn decode_op_kind(op_part: u8) -> OpKind {
+pub fn decode_op_kind(op_part: u8) -> Option<OpKind> {
     match op_part {
-        0b000 => OpKind::Add,
-        0b101 => OpKind::Sub,
-        0b111 => OpKind::Cmp,
-        _ => panic!("Unknown op kind: {:#10b}", op_part),
+        0b000 => Some(OpKind::Add),
+        0b101 => Some(OpKind::Sub),
+        0b111 => Some(OpKind::Cmp),
+        _ => None,
     }
 }
 
 impl Display for OpInstr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match (&self.dest, &self.src) {
-            (Location::Eac(_), Location::Immediate8(_)) => {
-                write!(f, "{} {}, byte {}", self.kind, self.dest, self.src)
-            }
-            (Location::Eac(_), Location::Immediate16(_)) => {
-                write!(f, "{} {}, word {}", self.kind, self.dest, self.src)
-            }
-            _ => write!(f, "{} {}, {}", self.kind, self.dest, self.src),
-        }
+        write!(
+            f,
+            "{}",
+            Formatter::default().two_operand(&self.kind.to_string(), &self.dest, &self.src)
+        )
     }
 }
 
 #[cfg(test)]
 mod test {
     use crate::decoder::{
+        common::Encoding,
         decode,
         instr::Instr,
         loc::{eac::EffectiveAddress, eac_mode::EffectiveAddressMode, Location},
@@ -140,6 +144,7 @@ mod test {
                 kind: OpKind::Add,
                 dest: Location::Reg(AX),
                 src: Location::Reg(BX),
+                encoding: Encoding::RmToFromReg { d: false },
             })
         );
 
@@ -149,6 +154,7 @@ mod test {
                 kind: OpKind::Sub,
                 dest: Location::Reg(AX),
                 src: Location::Reg(BX),
+                encoding: Encoding::RmToFromReg { d: false },
             })
         );
 
@@ -158,6 +164,7 @@ mod test {
                 kind: OpKind::Cmp,
                 dest: Location::Reg(AX),
                 src: Location::Reg(BX),
+                encoding: Encoding::RmToFromReg { d: false },
             })
         );
     }
@@ -173,7 +180,8 @@ mod test {
             Instr::Op(OpInstr {
                 kind: OpKind::Add,
                 dest: Location::Reg(CX),
-                src: Location::Immediate8(12),
+                src: Location::Immediate16(12),
+                encoding: Encoding::ImmToRm { s: true },
             })
         );
     }
@@ -189,7 +197,8 @@ mod test {
             Instr::Op(OpInstr {
                 kind: OpKind::Add,
                 dest: Location::Reg(SI),
-                src: Location::Immediate8(2),
+                src: Location::Immediate16(2),
+                encoding: Encoding::ImmToRm { s: true },
             })
         );
     }
@@ -206,6 +215,7 @@ mod test {
                 kind: OpKind::Add,
                 dest: Location::Reg(SI),
                 src: Location::Mem(10),
+                encoding: Encoding::RmToFromReg { d: true },
             })
         );
     }
@@ -222,6 +232,7 @@ mod test {
                 kind: OpKind::Add,
                 dest: Location::Mem(10),
                 src: Location::Immediate16(1000),
+                encoding: Encoding::ImmToRm { s: false },
             })
         );
     }
@@ -239,6 +250,7 @@ mod test {
             kind: OpKind::Add,
             dest: Location::Reg(AX),
             src: Location::Reg(BX),
+            encoding: Encoding::RmToFromReg { d: false },
         };
 
         assert_eq!(format!("{}", instr), "add ax, bx");
@@ -247,6 +259,7 @@ mod test {
             kind: OpKind::Add,
             dest: Location::Eac(EffectiveAddress::Mode(EffectiveAddressMode::Bx)),
             src: Location::Immediate8(12),
+            encoding: Encoding::ImmToRm { s: false },
         };
 
         assert_eq!(format!("{}", instr), "add [bx], byte 12");
@@ -255,6 +268,7 @@ mod test {
             kind: OpKind::Add,
             dest: Location::Eac(EffectiveAddress::Mode(EffectiveAddressMode::Bx)),
             src: Location::Immediate16(12),
+            encoding: Encoding::ImmToRm { s: false },
         };
 
         assert_eq!(format!("{}", instr), "add [bx], word 12");
@@ -0,0 +1,179 @@
+use std::fmt::Display;
+
+use crate::decoder::{instr::Instr, state::Decoder};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Segment {
+    Es,
+    Cs,
+    Ss,
+    Ds,
+}
+
+impl Display for Segment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Segment::Es => write!(f, "es"),
+            Segment::Cs => write!(f, "cs"),
+            Segment::Ss => write!(f, "ss"),
+            Segment::Ds => write!(f, "ds"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Rep {
+    /// F3, repeats while cx is non-zero, and for cmps/scas while equal
+    Repe,
+    /// F2, repeats while cx is non-zero, and for cmps/scas while not equal
+    Repne,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Prefix {
+    Lock,
+    Rep(Rep),
+    Segment(Segment),
+}
+
+/// An instruction with one or more prefixes, kept in the order they were
+/// encoded in
+#[derive(Debug, PartialEq)]
+pub struct PrefixedInstr {
+    pub prefixes: Vec<Prefix>,
+    pub instr: Box<Instr>,
+}
+
+impl PrefixedInstr {
+    /// The segment override, the last one wins if there are several
+    pub fn segment(&self) -> Option<Segment> {
+        self.prefixes.iter().rev().find_map(|prefix| match prefix {
+            Prefix::Segment(segment) => Some(*segment),
+            _ => None,
+        })
+    }
+
+    pub fn rep(&self) -> Option<Rep> {
+        self.prefixes.iter().rev().find_map(|prefix| match prefix {
+            Prefix::Rep(rep) => Some(*rep),
+            _ => None,
+        })
+    }
+
+    pub fn lock(&self) -> bool {
+        self.prefixes.contains(&Prefix::Lock)
+    }
+}
+
+pub fn decode_prefix(byte: u8) -> Option<Prefix> {
+    match byte {
+        0b00100110 => Some(Prefix::Segment(Segment::Es)),
+        0b00101110 => Some(Prefix::Segment(Segment::Cs)),
+        0b00110110 => Some(Prefix::Segment(Segment::Ss)),
+        0b00111110 => Some(Prefix::Segment(Segment::Ds)),
+        0b11110000 => Some(Prefix::Lock),
+        0b11110010 => Some(Prefix::Rep(Rep::Repne)),
+        0b11110011 => Some(Prefix::Rep(Rep::Repe)),
+        _ => None,
+    }
+}
+
+/// Decodes the instruction following `len` prefix bytes, so the instruction
+/// decoders can keep reading their opcode at offset 0
+pub struct AfterPrefixes<'a, T: Decoder> {
+    pub inner: &'a mut T,
+    pub len: usize,
+}
+
+impl<T: Decoder> Decoder for AfterPrefixes<'_, T> {
+    fn has_more(&self) -> bool {
+        self.inner.has_more()
+    }
+
+    fn get_byte(&self, offset: usize) -> u8 {
+        self.inner.get_byte(self.len + offset)
+    }
+
+    fn add_len(&mut self, len: usize) {
+        self.inner.add_len(len)
+    }
+
+    fn next(&mut self) -> bool {
+        self.inner.next()
+    }
+
+    fn get_instr_len(&self) -> usize {
+        self.inner.get_instr_len() - self.len
+    }
+
+    fn advance(&mut self) {
+        self.inner.advance()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::decoder::{
+        decode, decode_items,
+        instr::Instr,
+        prefix::{Prefix, PrefixedInstr, Rep, Segment},
+        string::{StringInstr, StringKind},
+        Item,
+    };
+
+    #[test]
+    fn test_decode_prefixes() {
+        // es rep movsb
+        let asm = decode(vec![0b00100110, 0b11110011, 0b10100100]);
+
+        assert_eq!(asm.len(), 1);
+        let Instr::Prefixed(prefixed) = &asm[0] else {
+            panic!("expected prefixed instruction")
+        };
+        assert_eq!(
+            prefixed.prefixes,
+            vec![Prefix::Segment(Segment::Es), Prefix::Rep(Rep::Repe)]
+        );
+        assert_eq!(prefixed.segment(), Some(Segment::Es));
+        assert_eq!(prefixed.rep(), Some(Rep::Repe));
+        assert!(!prefixed.lock());
+        assert_eq!(
+            *prefixed.instr,
+            Instr::Str(StringInstr {
+                kind: StringKind::Movs,
+                size: crate::decoder::loc::Size::Byte,
+            })
+        );
+    }
+
+    #[test]
+    fn test_prefixed_length() {
+        // lock add [bx + 300], ax
+        let items = decode_items(vec![0b11110000, 0b1, 0b10000111, 0b101100, 0b1]);
+
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].byte_len(), 5);
+        assert_eq!(items[0].to_string(), "lock add [bx + 300], ax");
+    }
+
+    #[test]
+    fn test_prefix_without_instr() {
+        let items = decode_items(vec![0b11110000, 0b11111111]);
+
+        assert_eq!(
+            items[0],
+            Item::Unknown {
+                addr: 0,
+                byte: 0b11110000
+            }
+        );
+        assert_eq!(
+            Instr::Prefixed(PrefixedInstr {
+                prefixes: vec![Prefix::Lock],
+                instr: Box::new(Instr::Ret),
+            })
+            .to_string(),
+            "lock ret"
+        );
+    }
+}
@@ -12,6 +12,11 @@ impl DecoderState {
             instr_len: 0,
         }
     }
+
+    /// Whether the instruction being decoded runs past the end of the source
+    pub fn is_truncated(&self) -> bool {
+        self.offset + self.instr_len > self.src.len()
+    }
 }
 
 impl Decoder for DecoderState {
@@ -19,12 +24,10 @@ impl Decoder for DecoderState {
         self.offset < self.src.len()
     }
 
+    // Reads past the end give 0 so a truncated instruction can be reported
+    // with `is_truncated` once decoding finishes
     fn get_byte(&self, offset: usize) -> u8 {
-        let byte = self
-            .src
-            .get(self.offset + offset)
-            .expect("Out of instr bounds");
-        *byte
+        self.src.get(self.offset + offset).copied().unwrap_or(0)
     }
 
     fn add_len(&mut self, len: usize) {
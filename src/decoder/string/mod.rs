@@ -0,0 +1,110 @@
+use std::fmt::Display;
+
+use crate::decoder::{instr::Instr, loc::Size, state::Decoder};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum StringKind {
+    Movs,
+    Cmps,
+    Stos,
+    Lods,
+    Scas,
+}
+
+impl Display for StringKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StringKind::Movs => write!(f, "movs"),
+            StringKind::Cmps => write!(f, "cmps"),
+            StringKind::Stos => write!(f, "stos"),
+            StringKind::Lods => write!(f, "lods"),
+            StringKind::Scas => write!(f, "scas"),
+        }
+    }
+}
+
+impl StringKind {
+    /// Whether the instruction compares, so `rep` stops on the zero flag
+    pub fn compares(&self) -> bool {
+        matches!(self, StringKind::Cmps | StringKind::Scas)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct StringInstr {
+    pub kind: StringKind,
+    pub size: Size,
+}
+
+impl Display for StringInstr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let suffix = match self.size {
+            Size::Byte => "b",
+            Size::Word => "w",
+        };
+        write!(f, "{}{}", self.kind, suffix)
+    }
+}
+
+pub fn decode_string<T: Decoder>(state: &mut T) -> Option<Instr> {
+    let byte = state.get_byte(0);
+    let kind = match byte & 0b11111110 {
+        0b10100100 => StringKind::Movs,
+        0b10100110 => StringKind::Cmps,
+        0b10101010 => StringKind::Stos,
+        0b10101100 => StringKind::Lods,
+        0b10101110 => StringKind::Scas,
+        _ => return None,
+    };
+    let size = if byte & 1 == 0 {
+        Size::Byte
+    } else {
+        Size::Word
+    };
+    state.add_len(1);
+    Some(Instr::Str(StringInstr { kind, size }))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::decoder::{
+        decode,
+        instr::Instr,
+        loc::Size,
+        string::{StringInstr, StringKind},
+    };
+
+    #[test]
+    fn test_decode_string_ops() {
+        let asm = decode(vec![
+            0b10100100, 0b10100111, 0b10101010, 0b10101101, 0b10101110,
+        ]);
+
+        assert_eq!(
+            asm,
+            vec![
+                Instr::Str(StringInstr {
+                    kind: StringKind::Movs,
+                    size: Size::Byte
+                }),
+                Instr::Str(StringInstr {
+                    kind: StringKind::Cmps,
+                    size: Size::Word
+                }),
+                Instr::Str(StringInstr {
+                    kind: StringKind::Stos,
+                    size: Size::Byte
+                }),
+                Instr::Str(StringInstr {
+                    kind: StringKind::Lods,
+                    size: Size::Word
+                }),
+                Instr::Str(StringInstr {
+                    kind: StringKind::Scas,
+                    size: Size::Byte
+                }),
+            ]
+        );
+        assert_eq!(asm[1].to_string(), "cmpsw");
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::BTreeMap;
+
+use crate::decoder::{decode_item, state::DecoderState, Item};
+
+/// Unreached bytes are split into data items of at most this many bytes
+const DATA_CHUNK_LEN: usize = 6;
+
+/// Disassembles by following control flow from each entry point, so only
+/// bytes that can actually be executed are decoded as instructions. Anything
+/// left over is returned as data.
+pub fn decode_recursive(bytes: Vec<u8>, entries: &[usize]) -> Vec<Item> {
+    let len = bytes.len();
+    let mut state = DecoderState::new(bytes);
+    let mut covered = vec![false; len];
+    let mut found = BTreeMap::new();
+    let mut pending = entries.iter().rev().copied().collect::<Vec<_>>();
+
+    while let Some(addr) = pending.pop() {
+        if addr >= len || covered[addr] {
+            continue;
+        }
+        state.offset = addr;
+        state.instr_len = 0;
+        let item = decode_item(&mut state);
+        let end = addr + item.byte_len();
+        // Keep whichever decoding was found first if two paths disagree
+        if covered[addr..end].iter().any(|covered| *covered) {
+            continue;
+        }
+        covered[addr..end].fill(true);
+        if let Item::Instr(decoded) = &item {
+            if decoded.instr.falls_through() {
+                pending.push(end);
+            }
+            if let Some(target) = decoded.instr.jump_target(end) {
+                pending.push(target);
+            }
+        }
+        found.insert(addr, item);
+    }
+
+    let mut items = vec![];
+    let mut addr = 0;
+    while addr < len {
+        if let Some(item) = found.remove(&addr) {
+            addr += item.byte_len();
+            items.push(item);
+            continue;
+        }
+        let end = (addr..len)
+            .find(|addr| covered[*addr])
+            .unwrap_or(len)
+            .min(addr + DATA_CHUNK_LEN);
+        items.push(Item::Data {
+            addr,
+            bytes: state.src[addr..end].to_vec(),
+        });
+        addr = end;
+    }
+    items
+}
+
+#[cfg(test)]
+mod test {
+    use crate::decoder::{traverse::decode_recursive, Item};
+
+    #[test]
+    fn test_skips_data_after_jmp() {
+        // jmp short 3; dw 0x1234; db 0xff; mov cl, 12
+        let items = decode_recursive(
+            vec![0b11101011, 0b11, 0x34, 0x12, 0xFF, 0b10110001, 0b1100],
+            &[0],
+        );
+
+        let text = items
+            .iter()
+            .map(|item| item.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            text,
+            vec!["jmp short $+5", "db 0x34, 0x12, 0xff", "mov cl, 12"]
+        );
+        assert_eq!(items[1].addr(), 2);
+    }
+
+    #[test]
+    fn test_follows_both_branches() {
+        // je 2; mov cl, 12; ret; mov ch, 1; ret
+        let items = decode_recursive(
+            vec![
+                0b01110100, 0b11, 0b10110001, 0b1100, 0b11000011, 0b10110101, 0b1, 0b11000011,
+            ],
+            &[0],
+        );
+
+        assert!(items.iter().all(|item| matches!(item, Item::Instr(_))));
+        assert_eq!(items.len(), 5);
+    }
+
+    #[test]
+    fn test_extra_entry_points() {
+        // ret; dw 0; mov cl, 12
+        let src = vec![0b11000011, 0, 0, 0b10110001, 0b1100];
+
+        let items = decode_recursive(src.clone(), &[0]);
+        assert_eq!(items[1].to_string(), "dw 0x0000, 0x0cb1");
+
+        let items = decode_recursive(src, &[0, 3]);
+        assert_eq!(items[1].to_string(), "dw 0x0000");
+        assert_eq!(items[2].to_string(), "mov cl, 12");
+    }
+
+    #[test]
+    fn test_long_data_is_split() {
+        let mut src = vec![0; 9];
+        src[0] = 0b11000011;
+        let items = decode_recursive(src, &[0]);
+
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[1].byte_len(), 6);
+        assert_eq!(items[2].byte_len(), 2);
+    }
+
+    #[test]
+    fn test_overlapping_paths() {
+        // je -3 lands in the middle of mov cx, 0x74eb, so it is ignored
+        let items = decode_recursive(
+            vec![0b10111001, 0b11101011, 0b01110100, 0b01110100, 0b11111101],
+            &[0],
+        );
+
+        assert_eq!(items[0].to_string(), "mov cx, 29931");
+        assert_eq!(items[1].to_string(), "je $-1");
+        assert_eq!(items.len(), 2);
+    }
+}
@@ -0,0 +1,81 @@
+use crate::decoder::{
+    common::{rm_to_reg::decode_rm_to_from_reg, Encoding},
+    instr::Instr,
+    loc::Location,
+    mov::{decode_reg, AX},
+    state::Decoder,
+};
+
+#[derive(Debug, PartialEq)]
+pub struct XchgInstr {
+    pub dest: Location,
+    pub src: Location,
+    pub encoding: Encoding,
+}
+
+pub fn decode_xchg<T: Decoder>(state: &mut T) -> Option<Instr> {
+    let byte = state.get_byte(0);
+    match byte {
+        // xchg ax, ax does nothing and is the canonical nop
+        0b10010000 => {
+            state.add_len(1);
+            Some(Instr::Nop)
+        }
+        // Register/Memory with Register, the d bit is always set
+        _ if 0b10000110 == byte & 0b11111110 => {
+            let (dest, src) = decode_rm_to_from_reg(state);
+            Some(Instr::Xchg(XchgInstr {
+                dest,
+                src,
+                encoding: Encoding::RmToFromReg { d: true },
+            }))
+        }
+        // Register with Accumulator
+        _ if 0b10010000 == byte & 0b11111000 => {
+            state.add_len(1);
+            Some(Instr::Xchg(XchgInstr {
+                dest: Location::Reg(AX),
+                src: Location::Reg(decode_reg(1, byte & 0b111)),
+                encoding: Encoding::AccWithReg,
+            }))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::decoder::{
+        common::Encoding,
+        decode,
+        instr::Instr,
+        loc::{eac::EffectiveAddress, eac_mode::EffectiveAddressMode, Location},
+        mov::{AX, CL, SI},
+        xchg::XchgInstr,
+    };
+
+    #[test]
+    fn test_decode_xchg() {
+        let asm = decode(vec![0b10000110, 0b1001, 0b10010110, 0b10010000]);
+
+        assert_eq!(
+            asm,
+            vec![
+                Instr::Xchg(XchgInstr {
+                    dest: Location::Reg(CL),
+                    src: Location::Eac(EffectiveAddress::Mode(EffectiveAddressMode::BxDi)),
+                    encoding: Encoding::RmToFromReg { d: true },
+                }),
+                Instr::Xchg(XchgInstr {
+                    dest: Location::Reg(AX),
+                    src: Location::Reg(SI),
+                    encoding: Encoding::AccWithReg,
+                }),
+                Instr::Nop,
+            ]
+        );
+        assert_eq!(asm[0].to_string(), "xchg cl, [bx + di]");
+        assert_eq!(asm[1].to_string(), "xchg ax, si");
+        assert_eq!(asm[2].to_string(), "nop");
+    }
+}
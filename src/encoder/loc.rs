@@ -0,0 +1,112 @@
+use crate::{
+    decoder::{
+        loc::{eac::EffectiveAddress, eac_mode::EffectiveAddressMode, Location},
+        mov::{AH, AL, AX, BH, BL, BP, BX, CH, CL, CX, DH, DI, DL, DX, SI, SP},
+    },
+    encoder::EncodeError,
+};
+
+pub fn encode_reg(reg: &'static str) -> Result<u8, EncodeError> {
+    Ok(match reg {
+        AX | AL => 0b000,
+        CX | CL => 0b001,
+        DX | DL => 0b010,
+        BX | BL => 0b011,
+        SP | AH => 0b100,
+        BP | CH => 0b101,
+        SI | DH => 0b110,
+        DI | BH => 0b111,
+        _ => return Err(EncodeError::UnknownRegister(reg)),
+    })
+}
+
+pub fn encode_eac_mode(mode: EffectiveAddressMode) -> u8 {
+    match mode {
+        EffectiveAddressMode::BxSi => 0b000,
+        EffectiveAddressMode::BxDi => 0b001,
+        EffectiveAddressMode::BpSi => 0b010,
+        EffectiveAddressMode::BpDi => 0b011,
+        EffectiveAddressMode::Si => 0b100,
+        EffectiveAddressMode::Di => 0b101,
+        EffectiveAddressMode::Bp => 0b110,
+        EffectiveAddressMode::Bx => 0b111,
+    }
+}
+
+/// Encodes the mod/rm byte for `rm` with the given reg field, followed by any displacement
+pub fn encode_rm(reg: u8, rm: &Location) -> Result<Vec<u8>, EncodeError> {
+    let reg = reg << 3;
+    Ok(match rm {
+        Location::Reg(name) => vec![0b11000000 | reg | encode_reg(name)?],
+        Location::Mem(addr) => {
+            let [low, high] = addr.to_le_bytes();
+            vec![reg | 0b110, low, high]
+        }
+        Location::Eac(EffectiveAddress::Mode(mode)) => vec![reg | encode_eac_mode(*mode)],
+        Location::Eac(EffectiveAddress::Byte(mode, offset)) => {
+            vec![0b01000000 | reg | encode_eac_mode(*mode), *offset as u8]
+        }
+        Location::Eac(EffectiveAddress::Word(mode, offset)) => {
+            let [low, high] = offset.to_le_bytes();
+            vec![0b10000000 | reg | encode_eac_mode(*mode), low, high]
+        }
+        Location::Immediate8(_) | Location::Immediate16(_) => {
+            return Err(EncodeError::ImmediateAsRm(rm.to_string()))
+        }
+    })
+}
+
+pub fn encode_imm(imm: &Location) -> Result<Vec<u8>, EncodeError> {
+    match imm {
+        Location::Immediate8(val) => Ok(vec![*val]),
+        Location::Immediate16(val) => Ok(val.to_le_bytes().to_vec()),
+        _ => Err(EncodeError::ExpectedImmediate(imm.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        decoder::{
+            loc::{eac::EffectiveAddress, eac_mode::EffectiveAddressMode, Location},
+            mov::{BH, CX},
+        },
+        encoder::loc::{encode_reg, encode_rm},
+    };
+
+    #[test]
+    fn test_encode_reg() {
+        assert_eq!(encode_reg(CX), Ok(0b001));
+        assert_eq!(encode_reg(BH), Ok(0b111));
+    }
+
+    #[test]
+    fn test_encode_rm() {
+        assert_eq!(encode_rm(0b010, &Location::Reg(CX)), Ok(vec![0b11010001]));
+        assert_eq!(
+            encode_rm(0b000, &Location::Mem(1000)),
+            Ok(vec![0b110, 0b11101000, 0b11])
+        );
+        assert_eq!(
+            encode_rm(
+                0b001,
+                &Location::Eac(EffectiveAddress::Mode(EffectiveAddressMode::BxSi))
+            ),
+            Ok(vec![0b1000])
+        );
+        assert_eq!(
+            encode_rm(
+                0b000,
+                &Location::Eac(EffectiveAddress::Byte(EffectiveAddressMode::Bp, -2))
+            ),
+            Ok(vec![0b1000110, 0b11111110])
+        );
+        assert_eq!(
+            encode_rm(
+                0b000,
+                &Location::Eac(EffectiveAddress::Word(EffectiveAddressMode::BxSi, 4999))
+            ),
+            Ok(vec![0b10000000, 0b10000111, 0b10011])
+        );
+    }
+}
@@ -0,0 +1,421 @@
+use std::fmt::Display;
+
+use crate::{
+    decoder::{
+        common::Encoding,
+        instr::Instr,
+        loc::{Location, Size},
+        mov::{MoveInstr, AL, AX},
+        op::{OpInstr, OpKind},
+        prefix::{Prefix, Rep, Segment},
+        string::StringKind,
+        xchg::XchgInstr,
+    },
+    sim::is_byte,
+};
+
+use self::loc::{encode_imm, encode_reg, encode_rm};
+
+pub mod loc;
+
+#[derive(Debug, PartialEq)]
+pub enum EncodeError {
+    /// A register that doesn't fit in a reg or r/m field
+    UnknownRegister(&'static str),
+    /// An operand that the encoding needs to be a register isn't one
+    ExpectedRegister(String),
+    /// An operand that the encoding needs to be an immediate isn't one
+    ExpectedImmediate(String),
+    /// An immediate where the encoding needs a register or memory operand
+    ImmediateAsRm(String),
+    /// A word immediate in a sign-extended byte form that doesn't sign-extend
+    /// from a byte
+    NotSignExtended(u16),
+    /// The instruction has no form with this encoding
+    UnsupportedEncoding(String),
+}
+
+impl Display for EncodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EncodeError::UnknownRegister(reg) => write!(f, "can't encode register '{}'", reg),
+            EncodeError::ExpectedRegister(loc) => write!(f, "expected a register, got {}", loc),
+            EncodeError::ExpectedImmediate(loc) => {
+                write!(f, "expected an immediate, got {}", loc)
+            }
+            EncodeError::ImmediateAsRm(loc) => {
+                write!(f, "can't encode immediate {} as register/memory", loc)
+            }
+            EncodeError::NotSignExtended(value) => write!(
+                f,
+                "immediate {} doesn't fit in a sign-extended byte",
+                *value as i16
+            ),
+            EncodeError::UnsupportedEncoding(instr) => {
+                write!(f, "'{}' has no such encoding", instr)
+            }
+        }
+    }
+}
+
+pub fn encode(instr: &Instr) -> Result<Vec<u8>, EncodeError> {
+    Ok(match instr {
+        Instr::Mov(mov) => encode_mov(mov)?,
+        Instr::Op(op) => encode_op(op)?,
+        Instr::Je(offset) => vec![0b01110100, *offset as u8],
+        Instr::Jne(offset) => vec![0b01110101, *offset as u8],
+        Instr::Jmp(offset) => vec![0b11101011, *offset as u8],
+        Instr::JmpNear(offset) => [vec![0b11101001], offset.to_le_bytes().to_vec()].concat(),
+        Instr::Call(offset) => [vec![0b11101000], offset.to_le_bytes().to_vec()].concat(),
+        Instr::Ret => vec![0b11000011],
+        Instr::Hlt => vec![0b11110100],
+        Instr::Int(vector) => vec![0b11001101, *vector],
+        Instr::Xchg(xchg) => encode_xchg(xchg)?,
+        Instr::Str(string) => vec![encode_string_kind(&string.kind) | size_w(string.size)],
+        Instr::Nop => vec![0b10010000],
+        Instr::Prefixed(prefixed) => {
+            let mut bytes = prefixed
+                .prefixes
+                .iter()
+                .map(encode_prefix)
+                .collect::<Vec<_>>();
+            bytes.extend(encode(&prefixed.instr)?);
+            bytes
+        }
+    })
+}
+
+pub fn encode_all(instrs: &[Instr]) -> Result<Vec<u8>, EncodeError> {
+    let mut bytes = vec![];
+    for instr in instrs {
+        bytes.extend(encode(instr)?);
+    }
+    Ok(bytes)
+}
+
+fn encode_mov(mov: &MoveInstr) -> Result<Vec<u8>, EncodeError> {
+    match mov.encoding {
+        Encoding::RmToFromReg { d } => encode_rm_to_from_reg(0b10001000, d, &mov.dest, &mov.src),
+        Encoding::ImmToReg => {
+            let Location::Reg(reg) = mov.dest else {
+                return Err(EncodeError::ExpectedRegister(mov.dest.to_string()));
+            };
+            let w = imm_w(&mov.src);
+            let mut bytes = vec![0b10110000 | w << 3 | encode_reg(reg)?];
+            bytes.extend(encode_imm(&mov.src)?);
+            Ok(bytes)
+        }
+        Encoding::ImmToRm { .. } => {
+            let mut bytes = vec![0b11000110 | imm_w(&mov.src)];
+            bytes.extend(encode_rm(0b000, &mov.dest)?);
+            bytes.extend(encode_imm(&mov.src)?);
+            Ok(bytes)
+        }
+        Encoding::Acc => {
+            let (d, reg, addr) = match (&mov.dest, &mov.src) {
+                (Location::Reg(reg @ (AL | AX)), Location::Mem(addr)) => (0, reg, addr),
+                (Location::Mem(addr), Location::Reg(reg @ (AL | AX))) => (1, reg, addr),
+                _ => return Err(EncodeError::UnsupportedEncoding(mov.to_string())),
+            };
+            let mut bytes = vec![0b10100000 | d << 1 | reg_w(reg)];
+            bytes.extend(addr.to_le_bytes());
+            Ok(bytes)
+        }
+        Encoding::ImmToAcc | Encoding::AccWithReg => {
+            Err(EncodeError::UnsupportedEncoding(mov.to_string()))
+        }
+    }
+}
+
+fn encode_op(op: &OpInstr) -> Result<Vec<u8>, EncodeError> {
+    let kind = encode_op_kind(&op.kind);
+    match op.encoding {
+        Encoding::RmToFromReg { d } => encode_rm_to_from_reg(kind << 3, d, &op.dest, &op.src),
+        Encoding::ImmToRm { s } => {
+            let w = imm_w(&op.src);
+            let mut bytes = vec![0b10000000 | (s as u8) << 1 | w];
+            bytes.extend(encode_rm(kind, &op.dest)?);
+            match (s, &op.src) {
+                (true, Location::Immediate16(val)) => {
+                    let byte = *val as i8;
+                    if byte as i16 as u16 != *val {
+                        return Err(EncodeError::NotSignExtended(*val));
+                    }
+                    bytes.push(byte as u8)
+                }
+                _ => bytes.extend(encode_imm(&op.src)?),
+            }
+            Ok(bytes)
+        }
+        Encoding::ImmToAcc => {
+            let mut bytes = vec![kind << 3 | 0b100 | imm_w(&op.src)];
+            bytes.extend(encode_imm(&op.src)?);
+            Ok(bytes)
+        }
+        Encoding::ImmToReg | Encoding::Acc | Encoding::AccWithReg => {
+            Err(EncodeError::UnsupportedEncoding(op.to_string()))
+        }
+    }
+}
+
+fn encode_xchg(xchg: &XchgInstr) -> Result<Vec<u8>, EncodeError> {
+    match xchg.encoding {
+        // There is no d bit, the reg field is always the register operand
+        Encoding::RmToFromReg { .. } => match (&xchg.dest, &xchg.src) {
+            (Location::Reg(_), _) => encode_rm_to_from_reg(0b10000100, true, &xchg.dest, &xchg.src),
+            _ => encode_rm_to_from_reg(0b10000100, true, &xchg.src, &xchg.dest),
+        },
+        Encoding::AccWithReg => {
+            let reg = match (&xchg.dest, &xchg.src) {
+                (Location::Reg(AX), Location::Reg(reg))
+                | (Location::Reg(reg), Location::Reg(AX)) => reg,
+                _ => {
+                    return Err(EncodeError::UnsupportedEncoding(format!(
+                        "xchg {}, {}",
+                        xchg.dest, xchg.src
+                    )))
+                }
+            };
+            Ok(vec![0b10010000 | encode_reg(reg)?])
+        }
+        _ => Err(EncodeError::UnsupportedEncoding(format!(
+            "xchg {}, {}",
+            xchg.dest, xchg.src
+        ))),
+    }
+}
+
+fn encode_rm_to_from_reg(
+    opcode: u8,
+    d: bool,
+    dest: &Location,
+    src: &Location,
+) -> Result<Vec<u8>, EncodeError> {
+    let (reg, rm) = if d { (dest, src) } else { (src, dest) };
+    let Location::Reg(reg) = reg else {
+        return Err(EncodeError::ExpectedRegister(reg.to_string()));
+    };
+    let mut bytes = vec![opcode | (d as u8) << 1 | reg_w(reg)];
+    bytes.extend(encode_rm(encode_reg(reg)?, rm)?);
+    Ok(bytes)
+}
+
+pub fn encode_prefix(prefix: &Prefix) -> u8 {
+    match prefix {
+        Prefix::Segment(Segment::Es) => 0b00100110,
+        Prefix::Segment(Segment::Cs) => 0b00101110,
+        Prefix::Segment(Segment::Ss) => 0b00110110,
+        Prefix::Segment(Segment::Ds) => 0b00111110,
+        Prefix::Lock => 0b11110000,
+        Prefix::Rep(Rep::Repne) => 0b11110010,
+        Prefix::Rep(Rep::Repe) => 0b11110011,
+    }
+}
+
+pub fn encode_string_kind(kind: &StringKind) -> u8 {
+    match kind {
+        StringKind::Movs => 0b10100100,
+        StringKind::Cmps => 0b10100110,
+        StringKind::Stos => 0b10101010,
+        StringKind::Lods => 0b10101100,
+        StringKind::Scas => 0b10101110,
+    }
+}
+
+pub fn encode_op_kind(kind: &OpKind) -> u8 {
+    match kind {
+        OpKind::Add => 0b000,
+        OpKind::Sub => 0b101,
+        OpKind::Cmp => 0b111,
+    }
+}
+
+fn reg_w(reg: &str) -> u8 {
+    if is_byte(reg) {
+        0
+    } else {
+        1
+    }
+}
+
+fn size_w(size: Size) -> u8 {
+    match size {
+        Size::Byte => 0,
+        Size::Word => 1,
+    }
+}
+
+fn imm_w(imm: &Location) -> u8 {
+    match imm {
+        Location::Immediate8(_) => 0,
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        decoder::decode,
+        decoder::{
+            common::Encoding,
+            instr::Instr,
+            loc::Location,
+            mov::{MoveInstr, AX, CX},
+            op::{OpInstr, OpKind},
+        },
+        encoder::{encode, encode_all, EncodeError},
+    };
+
+    fn assert_round_trip(bytes: Vec<u8>) {
+        let decoded = decode(bytes.clone());
+        assert_eq!(encode_all(&decoded), Ok(bytes));
+    }
+
+    #[test]
+    fn test_round_trip_prefixes() {
+        assert_round_trip(vec![
+            0b00100110, 0b11110011, 0b10100100, 0b11110000, 0b10000111, 0b1111, 0b10010110,
+            0b10010000, 0b00111110, 0b10101101,
+        ]);
+    }
+
+    #[test]
+    fn test_round_trip_mov_reg_to_reg() {
+        assert_round_trip(vec![
+            0b10001001, 0b11011001, 0b10001000, 0b11100101, 0b10001011, 0b11001011,
+        ]);
+    }
+
+    #[test]
+    fn test_round_trip_mov_eac() {
+        assert_round_trip(vec![
+            0b10001010, 0b0, 0b10001011, 0b11011, 0b10001011, 0b1010110, 0b0, 0b10001010,
+            0b1100000, 0b100, 0b10001010, 0b10000000, 0b10000111, 0b10011, 0b10001001, 0b1001,
+            0b10001000, 0b1101110, 0b0, 0b10001011, 0b1000001, 0b11011011,
+        ]);
+    }
+
+    #[test]
+    fn test_round_trip_mov_imm() {
+        assert_round_trip(vec![
+            0b10110001, 0b1100, 0b10111001, 0b11110100, 0b11111111, 0b11000110, 0b11, 0b111,
+            0b11000111, 0b10000101, 0b10000101, 0b11, 0b1011011, 0b1, 0b11000111, 0b110,
+            0b11101000, 0b11, 0b1, 0b0, 0b11000110, 0b11000011, 0b1,
+        ]);
+    }
+
+    #[test]
+    fn test_round_trip_mov_direct_and_acc() {
+        assert_round_trip(vec![
+            0b10001011, 0b101110, 0b101, 0b0, 0b10100001, 0b11111011, 0b1001, 0b10100000, 0b10000,
+            0b0, 0b10100011, 0b11111010, 0b1001, 0b10100010, 0b1111, 0b0,
+        ]);
+    }
+
+    #[test]
+    fn test_round_trip_op() {
+        assert_round_trip(vec![
+            0b11, 0b11000, 0b10, 0b1000000, 0b100, 0b101001, 0b11011000, 0b111011, 0b1001110,
+            0b11111110, 0b111001, 0b11011000,
+        ]);
+    }
+
+    #[test]
+    fn test_round_trip_op_imm() {
+        assert_round_trip(vec![
+            0b10000011, 0b11000110, 0b10, 0b10000011, 0b11000101, 0b11111110, 0b10000000, 0b111,
+            0b100010, 0b10000010, 0b11000011, 0b1, 0b10000001, 0b110, 0b1010, 0b0, 0b11101000,
+            0b11, 0b10000011, 0b1000110, 0b10, 0b11111111, 0b10000001, 0b11111110, 0b11100010,
+            0b100,
+        ]);
+    }
+
+    #[test]
+    fn test_round_trip_op_acc() {
+        assert_round_trip(vec![
+            0b101, 0b11101000, 0b11, 0b100, 0b11100010, 0b101101, 0b11101000, 0b11, 0b111100,
+            0b1001, 0b111101, 0b11101000, 0b11,
+        ]);
+    }
+
+    #[test]
+    fn test_round_trip_jumps() {
+        assert_round_trip(vec![0b01110100, 0b00000111, 0b01110101, 0b11111000]);
+    }
+
+    #[test]
+    fn test_round_trip_jmp_call_ret() {
+        assert_round_trip(vec![
+            0b11101011, 0b11111110, 0b11101001, 0b11010100, 0b11111110, 0b11101000, 0b0, 0b1,
+            0b11000011,
+        ]);
+    }
+
+    #[test]
+    fn test_encode_sign_extended_imm() {
+        let decoded = decode(vec![0b10000011, 0b11000001, 0b11111111]);
+        assert_eq!(decoded[0].to_string(), "add cx, 65535");
+        assert_eq!(
+            encode(&decoded[0]),
+            Ok(vec![0b10000011, 0b11000001, 0b11111111])
+        );
+    }
+
+    #[test]
+    fn test_encode_errors() {
+        let mov = |dest, src, encoding| {
+            Instr::Mov(MoveInstr {
+                dest,
+                src,
+                encoding,
+            })
+        };
+        assert_eq!(
+            encode(&mov(
+                Location::Mem(4),
+                Location::Immediate8(1),
+                Encoding::ImmToReg
+            )),
+            Err(EncodeError::ExpectedRegister("[4]".to_string()))
+        );
+        assert_eq!(
+            encode(&mov(Location::Reg(CX), Location::Mem(4), Encoding::Acc)),
+            Err(EncodeError::UnsupportedEncoding("mov cx, [4]".to_string()))
+        );
+        assert_eq!(
+            encode(&mov(
+                Location::Reg("es"),
+                Location::Reg(AX),
+                Encoding::RmToFromReg { d: true }
+            )),
+            Err(EncodeError::UnknownRegister("es"))
+        );
+        let op = Instr::Op(OpInstr {
+            kind: OpKind::Add,
+            dest: Location::Immediate16(1),
+            src: Location::Immediate16(2),
+            encoding: Encoding::ImmToRm { s: false },
+        });
+        assert_eq!(
+            encode(&op).unwrap_err().to_string(),
+            "can't encode immediate 1 as register/memory"
+        );
+        let sign_extended = |value| {
+            encode(&Instr::Op(OpInstr {
+                kind: OpKind::Add,
+                dest: Location::Reg(CX),
+                src: Location::Immediate16(value),
+                encoding: Encoding::ImmToRm { s: true },
+            }))
+        };
+        assert_eq!(
+            sign_extended(0xFF80),
+            Ok(vec![0b10000011, 0b11000001, 0x80])
+        );
+        assert_eq!(sign_extended(0x80), Err(EncodeError::NotSignExtended(0x80)));
+        assert_eq!(
+            sign_extended(0xFF7F).unwrap_err().to_string(),
+            "immediate -129 doesn't fit in a sign-extended byte"
+        );
+    }
+}
@@ -0,0 +1,453 @@
+use std::io::{BufRead, Write};
+
+use crate::{
+    decoder::prefix::Segment,
+    sim::{
+        error::SimError,
+        flags::Flags,
+        step::StopReason,
+        watch::{WatchKind, Watchpoint},
+        SimState,
+    },
+};
+
+use self::packet::{read_packet, write_packet, Incoming, Poll};
+
+pub mod packet;
+
+/// gdb's i386 registers, which `set architecture i8086` keeps: eax, ecx,
+/// edx, ebx, esp, ebp, esi, edi, eip, eflags, then the six segment registers
+const REGISTERS: [&str; 16] = [
+    "ax", "cx", "dx", "bx", "sp", "bp", "si", "di", "ip", "flags", "cs", "ss", "ds", "es", "fs",
+    "gs",
+];
+
+/// Bit 1 of the flags register always reads as set
+const RESERVED_FLAG: u32 = 1 << 1;
+
+const SIGTRAP: u8 = 5;
+const SIGILL: u8 = 4;
+const SIGINT: u8 = 2;
+
+/// How many instructions a continue runs between checks for a ctrl-c
+const STEPS_BETWEEN_POLLS: usize = 10_000;
+
+/// Serves gdb's remote serial protocol for a [SimState], enough for
+/// registers, memory, stepping, continuing and breakpoints. gdb sees one flat
+/// physical address space: pc is cs:ip's linear address, and breakpoints,
+/// memory and watchpoints all use physical addresses.
+pub struct GdbServer {
+    state: SimState,
+    /// Cleared once gdb asks for QStartNoAckMode
+    ack: bool,
+}
+
+/// What to do after answering a packet
+#[derive(Debug, PartialEq)]
+enum Reply {
+    Send(String),
+    /// Reply then hang up, for a detach
+    SendAndClose(String),
+    /// Run until the program stops or gdb interrupts it, then reply
+    Continue,
+    /// A kill, which has no reply
+    Close,
+}
+
+impl GdbServer {
+    pub fn new(state: SimState) -> Self {
+        Self { state, ack: true }
+    }
+
+    pub fn state(&self) -> &SimState {
+        &self.state
+    }
+
+    /// Answers packets until gdb detaches, kills the program or hangs up
+    pub fn serve(
+        &mut self,
+        mut input: impl BufRead + Poll,
+        mut output: impl Write,
+    ) -> std::io::Result<()> {
+        while let Some(incoming) = read_packet(&mut input, &mut output, self.ack)? {
+            let reply = match incoming {
+                // An interrupt that arrives while stopped has nothing to stop
+                Incoming::Interrupt => Reply::Send(format!("S{:02x}", SIGINT)),
+                Incoming::Packet(packet) => self.handle(&packet),
+            };
+            match reply {
+                Reply::Send(data) => write_packet(&mut output, &data)?,
+                Reply::SendAndClose(data) => return write_packet(&mut output, &data),
+                Reply::Continue => match self.resume(&mut input, &mut output)? {
+                    Some(data) => write_packet(&mut output, &data)?,
+                    None => return Ok(()),
+                },
+                Reply::Close => return Ok(()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs in bursts, checking for a ctrl-c from gdb in between, and
+    /// returns the stop reply. Returns `None` if gdb hangs up.
+    fn resume(
+        &mut self,
+        input: &mut (impl BufRead + Poll),
+        output: &mut impl Write,
+    ) -> std::io::Result<Option<String>> {
+        loop {
+            for _ in 0..STEPS_BETWEEN_POLLS {
+                match self.state.step() {
+                    Ok(result) if result.stop.is_none() => {}
+                    result => return Ok(Some(self.stop_reply(result.map(|result| result.stop)))),
+                }
+            }
+            if !input.has_input()? {
+                continue;
+            }
+            // gdb only sends a ctrl-c while the program runs, anything else
+            // is dropped
+            match read_packet(input, output, self.ack)? {
+                Some(Incoming::Interrupt) => return Ok(Some(format!("S{:02x}", SIGINT))),
+                Some(Incoming::Packet(_)) => {}
+                None => return Ok(None),
+            }
+        }
+    }
+
+    fn handle(&mut self, packet: &str) -> Reply {
+        let (command, args) = packet.split_at(packet.chars().next().map_or(0, char::len_utf8));
+        let reply = match command {
+            "?" => format!("S{:02x}", SIGTRAP),
+            "g" => REGISTERS
+                .iter()
+                .map(|reg| hex_u32(self.register(reg)))
+                .collect(),
+            "G" => self.write_registers(args),
+            // Leaving the x87 and SSE registers unsupported makes gdb treat
+            // them as unavailable
+            "p" => match parse_hex(args).and_then(|index| REGISTERS.get(index as usize)) {
+                Some(reg) => hex_u32(self.register(reg)),
+                None => String::new(),
+            },
+            "P" => self.write_register(args),
+            "m" => self.read_memory(args),
+            "M" => self.write_memory(args),
+            "s" | "c" => {
+                if !args.is_empty() {
+                    match parse_hex(args) {
+                        Some(addr) => self.state.set_ip(self.code_offset(addr)),
+                        None => return Reply::Send(error()),
+                    }
+                }
+                if command == "c" {
+                    return Reply::Continue;
+                }
+                let result = self.state.step().map(|result| result.stop);
+                self.stop_reply(result)
+            }
+            "Z" | "z" => self.set_breakpoint(command == "Z", args),
+            "H" => "OK".to_string(),
+            "k" => return Reply::Close,
+            "D" => return Reply::SendAndClose("OK".to_string()),
+            "q" | "Q" => self.query(packet),
+            _ => String::new(),
+        };
+        Reply::Send(reply)
+    }
+
+    fn query(&mut self, packet: &str) -> String {
+        match packet {
+            _ if packet.starts_with("qSupported") => "PacketSize=1000;QStartNoAckMode+".to_string(),
+            "QStartNoAckMode" => {
+                self.ack = false;
+                "OK".to_string()
+            }
+            "qAttached" => "1".to_string(),
+            "qC" => "QC1".to_string(),
+            "qfThreadInfo" => "m1".to_string(),
+            "qsThreadInfo" => "l".to_string(),
+            _ => String::new(),
+        }
+    }
+
+    /// The 8086 has no fs or gs, so they read as 0
+    fn register(&self, reg: &str) -> u32 {
+        match reg {
+            "ip" => self.state.physical(Segment::Cs, self.state.ip()),
+            "flags" => RESERVED_FLAG | self.state.flags().bits() as u32,
+            "fs" | "gs" => 0,
+            reg => self.state.get_register_16(reg) as u32,
+        }
+    }
+
+    fn set_register(&mut self, reg: &str, value: u32) {
+        match reg {
+            "ip" => self.state.set_ip(self.code_offset(value)),
+            "flags" => self.state.set_flags(Flags::from_bits(value as u16)),
+            "fs" | "gs" => {}
+            reg => self.state.set_register_16(reg, value as u16),
+        }
+    }
+
+    fn write_registers(&mut self, args: &str) -> String {
+        let Some(values) = decode_hex(args) else {
+            return error();
+        };
+        for (reg, value) in REGISTERS.iter().zip(values.chunks_exact(4)) {
+            let value = u32::from_le_bytes([value[0], value[1], value[2], value[3]]);
+            self.set_register(reg, value);
+        }
+        "OK".to_string()
+    }
+
+    /// `P<index>=<little endian value>`
+    fn write_register(&mut self, args: &str) -> String {
+        let Some((index, value)) = args.split_once('=') else {
+            return error();
+        };
+        let reg = parse_hex(index).and_then(|index| REGISTERS.get(index as usize));
+        let (Some(reg), Some(value)) = (reg, decode_hex(value)) else {
+            return error();
+        };
+        let value = value
+            .iter()
+            .rev()
+            .fold(0, |value, byte| value << 8 | *byte as u32);
+        self.set_register(reg, value);
+        "OK".to_string()
+    }
+
+    /// Where the physical address `addr` is in the code segment, for a pc or
+    /// breakpoint from gdb
+    fn code_offset(&self, addr: u32) -> u16 {
+        addr.wrapping_sub(self.state.physical(Segment::Cs, 0)) as u16
+    }
+
+    /// `m<addr>,<len>`, where addresses are physical and wrap at 1MB
+    fn read_memory(&self, args: &str) -> String {
+        let Some((addr, len)) = parse_range(args) else {
+            return error();
+        };
+        let memory = self.state.memory();
+        (0..len as usize)
+            .map(|offset| format!("{:02x}", memory[(addr as usize + offset) % memory.len()]))
+            .collect()
+    }
+
+    /// `M<addr>,<len>:<bytes>`
+    fn write_memory(&mut self, args: &str) -> String {
+        let Some((range, bytes)) = args.split_once(':') else {
+            return error();
+        };
+        match (parse_range(range), decode_hex(bytes)) {
+            (Some((addr, len)), Some(bytes)) if bytes.len() == len as usize => {
+                self.state.set_memory(addr, &bytes);
+                "OK".to_string()
+            }
+            _ => error(),
+        }
+    }
+
+    /// `Z<type>,<addr>,<kind>`: 0 and 1 are breakpoints, 2 to 4 write, read
+    /// and access watchpoints `kind` bytes long, all at physical addresses
+    fn set_breakpoint(&mut self, insert: bool, args: &str) -> String {
+        let mut fields = args.split(',');
+        let (Some(kind), Some(addr), Some(len)) = (
+            fields.next(),
+            fields.next().and_then(parse_hex),
+            fields.next().and_then(parse_hex),
+        ) else {
+            return error();
+        };
+        let watch = match kind {
+            "0" | "1" if insert => {
+                self.state.add_breakpoint(self.code_offset(addr));
+                return "OK".to_string();
+            }
+            "0" | "1" => {
+                self.state.remove_breakpoint(self.code_offset(addr));
+                return "OK".to_string();
+            }
+            "2" => WatchKind::Write,
+            "3" => WatchKind::Read,
+            "4" => WatchKind::Access,
+            _ => return String::new(),
+        };
+        if insert {
+            self.state
+                .add_watchpoint(Watchpoint::physical(addr, len, watch));
+        } else {
+            self.state.remove_watchpoint(addr);
+        }
+        "OK".to_string()
+    }
+
+    /// A halt or running off the end of the program look like the process
+    /// exiting
+    fn stop_reply(&self, result: Result<Option<StopReason>, SimError>) -> String {
+        match result {
+            Ok(Some(StopReason::Halt | StopReason::Exit | StopReason::EndOfProgram))
+            | Err(SimError::Halted | SimError::Exited) => "W00".to_string(),
+            Ok(Some(StopReason::Watchpoint(hit))) => {
+                let kind = self
+                    .state
+                    .watchpoints()
+                    .iter()
+                    .find(|watchpoint| watchpoint.contains(hit.addr))
+                    .map_or(WatchKind::Write, |watchpoint| watchpoint.kind);
+                let name = match kind {
+                    WatchKind::Write => "watch",
+                    WatchKind::Read => "rwatch",
+                    WatchKind::Access => "awatch",
+                };
+                format!("T{:02x}{}:{:x};", SIGTRAP, name, hit.addr)
+            }
+            Ok(_) => format!("S{:02x}", SIGTRAP),
+            Err(_) => format!("S{:02x}", SIGILL),
+        }
+    }
+}
+
+fn error() -> String {
+    "E01".to_string()
+}
+
+/// 32 bits as little endian hex, the way gdb sends register values
+fn hex_u32(value: u32) -> String {
+    value
+        .to_le_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn parse_hex(value: &str) -> Option<u32> {
+    u32::from_str_radix(value, 16).ok()
+}
+
+fn parse_range(args: &str) -> Option<(u32, u32)> {
+    let (addr, len) = args.split_once(',')?;
+    Some((parse_hex(addr)?, parse_hex(len)?))
+}
+
+fn decode_hex(value: &str) -> Option<Vec<u8>> {
+    if !value.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(value.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        decoder::mov::{BX, CX},
+        gdb::{GdbServer, Reply},
+        sim::SimState,
+    };
+
+    fn send(server: &mut GdbServer, packet: &str) -> String {
+        match server.handle(packet) {
+            Reply::Send(data) => data,
+            Reply::Continue => server.resume(&mut &b""[..], &mut vec![]).unwrap().unwrap(),
+            reply => panic!("unexpected {:?}", reply),
+        }
+    }
+
+    #[test]
+    fn test_registers() {
+        let mut server = GdbServer::new(SimState::new(vec![0xB9, 0x03, 0x00]));
+
+        assert_eq!(send(&mut server, "s"), "W00");
+        let registers = send(&mut server, "g");
+        assert_eq!(registers.len(), 16 * 8);
+        assert_eq!(&registers[8..16], "03000000");
+        assert_eq!(&registers[64..80], "0300000002000000");
+
+        assert_eq!(send(&mut server, "P3=34120000"), "OK");
+        assert_eq!(server.state().get_register_16(BX), 0x1234);
+        assert_eq!(send(&mut server, "p3"), "34120000");
+        assert_eq!(send(&mut server, "p20"), "");
+
+        let mut registers = "0".repeat(16 * 8);
+        registers.replace_range(8..12, "0500");
+        registers.replace_range(72..76, "4200");
+        assert_eq!(send(&mut server, &format!("G{}", registers)), "OK");
+        assert_eq!(server.state().get_register_16(CX), 5);
+        assert!(server.state().flags().zero);
+    }
+
+    #[test]
+    fn test_memory_and_breakpoints() {
+        // mov cx, 3; mov [0x100], cx; hlt
+        let mut server = GdbServer::new(SimState::new(vec![
+            0xB9, 0x03, 0x00, 0x89, 0x0E, 0x00, 0x01, 0xF4,
+        ]));
+
+        assert_eq!(send(&mut server, "m0,3"), "b90300");
+        assert_eq!(send(&mut server, "M1,2:0500"), "OK");
+        assert_eq!(send(&mut server, "Z0,3,1"), "OK");
+        assert_eq!(send(&mut server, "c"), "S05");
+        assert_eq!(server.state().ip(), 3);
+        assert_eq!(server.state().get_register_16(CX), 5);
+
+        assert_eq!(send(&mut server, "z0,3,1"), "OK");
+        assert_eq!(send(&mut server, "Z2,100,2"), "OK");
+        assert_eq!(send(&mut server, "c"), "T05watch:100;");
+        assert_eq!(send(&mut server, "m100,2"), "0500");
+        assert_eq!(send(&mut server, "c"), "W00");
+        assert_eq!(send(&mut server, "vMustReplyEmpty"), "");
+        assert_eq!(send(&mut server, "Mzz"), "E01");
+    }
+
+    #[test]
+    fn test_com_addresses() {
+        // mov cx, 3; int 20h, loaded with cs at 0x1000
+        let state = SimState::load_com(&[0xB9, 0x03, 0x00, 0xCD, 0x20], 0x1000, "").unwrap();
+        let mut server = GdbServer::new(state);
+
+        // pc is cs:ip's physical address, where the program's bytes are
+        assert_eq!(send(&mut server, "p8"), "00010100");
+        assert_eq!(send(&mut server, "m10100,3"), "b90300");
+        assert_eq!(send(&mut server, "Z0,10103,1"), "OK");
+        assert_eq!(send(&mut server, "c"), "S05");
+        assert_eq!(server.state().ip(), 0x103);
+        assert_eq!(send(&mut server, "p8"), "03010100");
+
+        assert_eq!(send(&mut server, "P8=00010100"), "OK");
+        assert_eq!(server.state().ip(), 0x100);
+        assert_eq!(send(&mut server, "z0,10103,1"), "OK");
+        assert_eq!(send(&mut server, "c"), "W00");
+        assert_eq!(server.state().get_register_16(CX), 3);
+        assert_eq!(server.state().ip(), 0x105);
+    }
+
+    #[test]
+    fn test_serve() {
+        let mut server = GdbServer::new(SimState::new(vec![0xF4]));
+        let input = &b"+$qSupported:multiprocess+#c6+$QStartNoAckMode#b0+$?#3f$D#44"[..];
+        let mut output = vec![];
+        server.serve(input, &mut output).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "+$PacketSize=1000;QStartNoAckMode+#07+$OK#9a$S05#b8$OK#9a"
+        );
+    }
+
+    #[test]
+    fn test_interrupt() {
+        // jmp $
+        let mut server = GdbServer::new(SimState::new(vec![0xEB, 0xFE]));
+        let input = &b"+$c#63\x03+$?#3f"[..];
+        let mut output = vec![];
+        server.serve(input, &mut output).unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), "+$S02#b5+$S05#b8");
+        assert_eq!(server.state().ip(), 0);
+        assert!(server.state().steps() >= 10_000);
+    }
+}
@@ -0,0 +1,125 @@
+use std::{
+    io::{BufRead, BufReader, ErrorKind, Write},
+    net::TcpStream,
+};
+
+/// What arrived from the debugger
+#[derive(Debug, PartialEq)]
+pub enum Incoming {
+    /// The data between `$` and `#` of a packet with a good checksum
+    Packet(String),
+    /// A ctrl-c, sent outside of any packet
+    Interrupt,
+}
+
+/// Input that can be checked for waiting bytes without blocking, so a
+/// running program can be interrupted
+pub trait Poll {
+    /// Whether a read would return straight away, including at the end of
+    /// the input
+    fn has_input(&mut self) -> std::io::Result<bool>;
+}
+
+impl Poll for &[u8] {
+    fn has_input(&mut self) -> std::io::Result<bool> {
+        Ok(true)
+    }
+}
+
+impl Poll for BufReader<TcpStream> {
+    fn has_input(&mut self) -> std::io::Result<bool> {
+        if !self.buffer().is_empty() {
+            return Ok(true);
+        }
+        let stream = self.get_ref();
+        stream.set_nonblocking(true)?;
+        let peeked = stream.peek(&mut [0]);
+        stream.set_nonblocking(false)?;
+        match peeked {
+            Ok(_) => Ok(true),
+            Err(err) if err.kind() == ErrorKind::WouldBlock => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/// Reads up to the next packet, skipping acks and answering a bad checksum
+/// with `-` so it's resent. Returns `None` once the connection is closed.
+pub fn read_packet(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    ack: bool,
+) -> std::io::Result<Option<Incoming>> {
+    loop {
+        let Some(byte) = read_byte(input)? else {
+            return Ok(None);
+        };
+        match byte {
+            b'$' => {}
+            0x03 => return Ok(Some(Incoming::Interrupt)),
+            _ => continue,
+        }
+        let mut data = vec![];
+        if input.read_until(b'#', &mut data)? == 0 || data.pop() != Some(b'#') {
+            return Ok(None);
+        }
+        let mut checksum = [0; 2];
+        input.read_exact(&mut checksum)?;
+        let expected = std::str::from_utf8(&checksum)
+            .ok()
+            .and_then(|checksum| u8::from_str_radix(checksum, 16).ok());
+        if ack {
+            let valid = expected == Some(checksum_of(&data));
+            output.write_all(if valid { b"+" } else { b"-" })?;
+            output.flush()?;
+            if !valid {
+                continue;
+            }
+        }
+        return Ok(Some(Incoming::Packet(
+            String::from_utf8_lossy(&data).into_owned(),
+        )));
+    }
+}
+
+/// Sends `$data#checksum`. The acknowledgement is skipped over by the next
+/// [read_packet].
+pub fn write_packet(output: &mut impl Write, data: &str) -> std::io::Result<()> {
+    write!(output, "${}#{:02x}", data, checksum_of(data.as_bytes()))?;
+    output.flush()
+}
+
+fn read_byte(input: &mut impl BufRead) -> std::io::Result<Option<u8>> {
+    let mut byte = [0];
+    match input.read(&mut byte)? {
+        0 => Ok(None),
+        _ => Ok(Some(byte[0])),
+    }
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, byte| sum.wrapping_add(*byte))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::gdb::packet::{read_packet, write_packet, Incoming};
+
+    #[test]
+    fn test_packets() {
+        let mut input = &b"+$g#67$m0,2#fb$?#00\x03"[..];
+        let mut output = vec![];
+        let mut read = || read_packet(&mut input, &mut output, true).unwrap();
+
+        assert_eq!(read(), Some(Incoming::Packet("g".to_string())));
+        assert_eq!(read(), Some(Incoming::Packet("m0,2".to_string())));
+        // The bad checksum on ? is nacked and skipped
+        assert_eq!(read(), Some(Incoming::Interrupt));
+        assert_eq!(read(), None);
+        assert_eq!(output, b"++-");
+
+        let mut output = vec![];
+        write_packet(&mut output, "S05").unwrap();
+        assert_eq!(output, b"$S05#b8");
+    }
+}
@@ -0,0 +1,8 @@
+pub mod assembler;
+pub mod debugger;
+pub mod decoder;
+pub mod encoder;
+pub mod gdb;
+pub mod json;
+pub mod sim;
+pub mod verify;
@@ -4,8 +4,6 @@ use crate::cli::Command;
 
 #[cfg(not(tarpaulin_include))]
 mod cli;
-mod decoder;
-mod sim;
 
 #[cfg(not(tarpaulin_include))]
 fn main() {
@@ -0,0 +1,237 @@
+use std::str::FromStr;
+
+use super::timing::Cpu;
+
+/// Clocks in a bus cycle, T1 to T4
+pub const BUS_CYCLE: u32 = 4;
+
+/// How the clocks each instruction takes are worked out
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum TimingMode {
+    /// Only the 8086 timing tables, as if the next instruction was always
+    /// already in the queue
+    #[default]
+    Table,
+    /// The timing tables plus the clocks the execution unit waits on the
+    /// bus interface unit, stepped clock by clock through each bus cycle's
+    /// T-states: for instruction bytes not yet in the queue, and for a fetch
+    /// under way to finish before its own memory transfers. See [Biu].
+    Prefetch,
+}
+
+impl FromStr for TimingMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(TimingMode::Table),
+            "prefetch" => Ok(TimingMode::Prefetch),
+            _ => Err(format!(
+                "unknown timing '{}', expected table or prefetch",
+                s
+            )),
+        }
+    }
+}
+
+/// Memory transfers made by the instruction being executed, not counting
+/// reads made by observers
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Transfers {
+    /// Every byte read or written, including those of words
+    pub bytes: u32,
+    pub words: u32,
+    /// Words that took two bus cycles, see [Cpu::word_penalty]
+    pub slow_words: u32,
+}
+
+impl Transfers {
+    /// Bus cycles the transfers took, a word takes one unless it is slow
+    pub fn bus_cycles(&self) -> u32 {
+        self.bytes - self.words + self.slow_words
+    }
+}
+
+/// The bus interface unit, which fetches instruction bytes into its queue
+/// whenever the execution unit leaves the bus idle. It is stepped a clock at
+/// a time, a fetch taking the four T-states of a bus cycle, and starts one
+/// as soon as the queue has room for what it fetches.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Biu {
+    queued: u32,
+    /// T-states left in the fetch on the bus, 0 when the bus is idle
+    fetching: u32,
+    /// Whether the bytes being fetched go in the queue, a jump while a
+    /// fetch is under way leaves it to finish for nothing
+    wanted: bool,
+}
+
+impl Biu {
+    /// Instruction bytes waiting in the queue
+    pub fn queued(&self) -> u32 {
+        self.queued
+    }
+
+    /// Advances the bus a clock
+    fn tick(&mut self, cpu: Cpu) {
+        if self.fetching == 0 && self.queued + cpu.fetch_width() <= cpu.queue_size() {
+            self.fetching = BUS_CYCLE;
+            self.wanted = true;
+        }
+        if self.fetching > 0 {
+            self.fetching -= 1;
+            if self.fetching == 0 && self.wanted {
+                self.queued += cpu.fetch_width();
+            }
+        }
+    }
+
+    /// Takes an instruction of `len` bytes from the queue as they arrive,
+    /// returning the clocks the execution unit waited for them
+    pub fn take(&mut self, cpu: Cpu, len: u32) -> u32 {
+        let mut needed = len;
+        let mut wait = 0;
+        loop {
+            let taken = needed.min(self.queued);
+            self.queued -= taken;
+            needed -= taken;
+            if needed == 0 {
+                return wait;
+            }
+            self.tick(cpu);
+            wait += 1;
+        }
+    }
+
+    /// Runs the `exec` clocks the execution unit takes, `data_cycles` bus
+    /// cycles of which it uses itself. Those run back to back once the `ea`
+    /// clocks working out the address are done, first waiting for any fetch
+    /// under way to finish, and the bus interface unit fetches during the
+    /// rest. Returns the clocks the execution unit waited for the bus.
+    pub fn execute(&mut self, cpu: Cpu, exec: u32, ea: u32, data_cycles: u32) -> u32 {
+        let data = (data_cycles * BUS_CYCLE).min(exec);
+        let internal = exec - data;
+        let before = ea.min(internal);
+        for _ in 0..before {
+            self.tick(cpu);
+        }
+        let mut wait = 0;
+        if data > 0 {
+            while self.fetching > 0 {
+                self.tick(cpu);
+                wait += 1;
+            }
+        }
+        for _ in before..internal {
+            self.tick(cpu);
+        }
+        wait
+    }
+
+    /// Empties the queue after a jump, the bytes fetched were for the wrong
+    /// address
+    pub fn flush(&mut self) {
+        self.queued = 0;
+        self.wanted = false;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sim::{
+        bus::{Biu, TimingMode, Transfers},
+        timing::Cpu,
+        SimState,
+    };
+
+    fn run(program: &[u8], cpu: Cpu, steps: usize) -> Vec<(u32, u32)> {
+        let mut state = SimState::new(program.to_vec());
+        state.set_cpu(cpu);
+        state.set_timing(TimingMode::Prefetch);
+        (0..steps)
+            .map(|_| {
+                let clocks = state.trace_step().unwrap().clocks;
+                (clocks.prefetch, clocks.total())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_queue_overlap() {
+        // mov cx, 3; add [bx], cx; mov bx, cx; jmp $+2; mov bx, cx
+        let program = [
+            0xB9, 0x03, 0x00, 0x01, 0x0F, 0x89, 0xCB, 0xEB, 0x00, 0x89, 0xCB,
+        ];
+
+        assert_eq!(
+            run(&program, Cpu::I8086, 5),
+            vec![(8, 12), (3, 24), (0, 2), (0, 15), (4, 6)]
+        );
+        assert_eq!(
+            run(&program, Cpu::I8088, 5),
+            vec![(12, 16), (7, 36), (0, 2), (0, 15), (8, 10)]
+        );
+    }
+
+    fn totals(program: &[u8], cpu: Cpu, steps: usize) -> Vec<u32> {
+        run(program, cpu, steps)
+            .into_iter()
+            .map(|(_, total)| total)
+            .collect()
+    }
+
+    // Runs of short register instructions settle at the rate published for
+    // real chips: on the 8088 each byte costs a 4 clock bus cycle, so nop
+    // takes 4 and a 2 byte, 2 clock instruction like mov bx, cx takes 8
+    // (Abrash, Zen of Assembly Language, ch. 4). The 8086 fetches two bytes
+    // a bus cycle, fast enough for nop to run at its table time of 3.
+    #[test]
+    fn test_reference_timings() {
+        let nops = [0x90; 16];
+        let movs = [0x89, 0xCB].repeat(8);
+
+        assert!(totals(&nops, Cpu::I8088, 16)[8..].iter().all(|&t| t == 4));
+        assert!(totals(&movs, Cpu::I8088, 8)[4..].iter().all(|&t| t == 8));
+        assert!(totals(&nops, Cpu::I8086, 16)[8..].iter().all(|&t| t == 3));
+        assert!(totals(&movs, Cpu::I8086, 8)[4..].iter().all(|&t| t == 4));
+    }
+
+    // A slow instruction leaves the bus idle long enough to fill the queue,
+    // so what follows runs at table speed until the queue drains and the
+    // next has to wait for its second byte
+    #[test]
+    fn test_full_queue_runs_at_table_speed() {
+        // xchg [bx], cx; mov bx, cx; mov bx, cx; mov bx, cx
+        let program = [0x87, 0x0F, 0x89, 0xCB, 0x89, 0xCB, 0x89, 0xCB];
+
+        assert_eq!(totals(&program, Cpu::I8088, 4)[1..], [2, 2, 6]);
+    }
+
+    #[test]
+    fn test_table_mode_has_no_prefetch() {
+        let mut state = SimState::new(vec![0x89, 0xCB]);
+        state.set_cpu(Cpu::I8088);
+
+        assert_eq!(state.trace_step().unwrap().clocks.prefetch, 0);
+        assert_eq!("prefetch".parse(), Ok(TimingMode::Prefetch));
+        assert!("bus".parse::<TimingMode>().is_err());
+    }
+
+    #[test]
+    fn test_biu() {
+        let mut biu = Biu::default();
+
+        assert_eq!(biu.take(Cpu::I8088, 3), 12);
+        assert_eq!(biu.execute(Cpu::I8088, 40, 0, 2), 0);
+        assert_eq!(biu.queued(), 4);
+        biu.flush();
+        assert_eq!(biu.queued(), 0);
+
+        let transfers = Transfers {
+            bytes: 5,
+            words: 2,
+            slow_words: 1,
+        };
+        assert_eq!(transfers.bus_cycles(), 4);
+    }
+}
@@ -0,0 +1,298 @@
+use std::{fmt::Display, str::FromStr};
+
+use crate::decoder::{loc::Size, prefix::Segment};
+
+use super::{is_byte, trace::REGISTERS, SimState, SEGMENT_REGISTERS};
+
+/// Operators, longest first so `<=` isn't read as `<`
+const OPERATORS: [&str; 14] = [
+    "||", "&&", "==", "!=", "<=", ">=", "<", ">", "+", "-", "!", "(", ")", "[",
+];
+
+#[derive(Debug, PartialEq, Clone)]
+enum Token {
+    Number(i64),
+    Ident(String),
+    Op(&'static str),
+    Close,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Expr {
+    Num(i64),
+    /// A 16 or 8 bit register, a segment register or ip
+    Reg(String),
+    Zf,
+    Sf,
+    Memory(Box<Expr>, Size),
+    Not(Box<Expr>),
+    Neg(Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+}
+
+/// A breakpoint condition over registers, flags and memory, e.g.
+/// `cx == 64 && ZF` or `word [bx + 2] != 0`. A bare `[addr]` reads a byte from
+/// the data segment and anything non-zero counts as true.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Condition {
+    text: String,
+    expr: Expr,
+}
+
+impl Condition {
+    /// Reads memory directly, so watchpoints and observers don't see it
+    pub fn holds(&self, state: &SimState) -> bool {
+        self.expr.eval(state) != 0
+    }
+}
+
+impl Display for Condition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(s)?;
+        let mut pos = 0;
+        let expr = parse_or(&tokens, &mut pos)?;
+        if pos < tokens.len() {
+            return Err(format!(
+                "unexpected {} in condition",
+                describe(&tokens[pos])
+            ));
+        }
+        Ok(Condition {
+            text: s.split_whitespace().collect::<Vec<_>>().join(" "),
+            expr,
+        })
+    }
+}
+
+impl Expr {
+    fn eval(&self, state: &SimState) -> i64 {
+        match self {
+            Expr::Num(value) => *value,
+            Expr::Reg(reg) if is_byte(reg) => state.try_get_register_8(reg).unwrap_or(0) as i64,
+            Expr::Reg(reg) => state.try_get_register_16(reg).unwrap_or(0) as i64,
+            Expr::Zf => state.flags().zero as i64,
+            Expr::Sf => state.flags().sign as i64,
+            Expr::Memory(addr, size) => {
+                let byte =
+                    |offset: u16| state.memory()[state.physical(Segment::Ds, offset) as usize];
+                let addr = addr.eval(state) as u16;
+                let low = byte(addr) as i64;
+                match size {
+                    Size::Byte => low,
+                    Size::Word => (byte(addr.wrapping_add(1)) as i64) << 8 | low,
+                }
+            }
+            Expr::Not(expr) => (expr.eval(state) == 0) as i64,
+            Expr::Neg(expr) => expr.eval(state).wrapping_neg(),
+            Expr::Binary(op, left, right) => {
+                let left = left.eval(state);
+                let right = right.eval(state);
+                match *op {
+                    "||" => (left != 0 || right != 0) as i64,
+                    "&&" => (left != 0 && right != 0) as i64,
+                    "==" => (left == right) as i64,
+                    "!=" => (left != right) as i64,
+                    "<" => (left < right) as i64,
+                    "<=" => (left <= right) as i64,
+                    ">" => (left > right) as i64,
+                    ">=" => (left >= right) as i64,
+                    "+" => left.wrapping_add(right),
+                    "-" => left.wrapping_sub(right),
+                    _ => unreachable!("Unknown operator: {}", op),
+                }
+            }
+        }
+    }
+}
+
+fn tokenize(src: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut rest = src.trim_start();
+    while !rest.is_empty() {
+        if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+            tokens.push(Token::Op(op));
+            rest = &rest[op.len()..];
+        } else if let Some(after) = rest.strip_prefix(']') {
+            tokens.push(Token::Close);
+            rest = after;
+        } else {
+            let len = rest
+                .find(|c: char| !c.is_ascii_alphanumeric())
+                .unwrap_or(rest.len());
+            if len == 0 {
+                let c = rest.chars().next().unwrap_or_default();
+                return Err(format!("unexpected character '{}' in condition", c));
+            }
+            let word = &rest[..len];
+            tokens.push(if word.starts_with(|c: char| c.is_ascii_digit()) {
+                Token::Number(parse_number(word)?)
+            } else {
+                Token::Ident(word.to_ascii_lowercase())
+            });
+            rest = &rest[len..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+fn parse_number(word: &str) -> Result<i64, String> {
+    let lower = word.to_ascii_lowercase();
+    let parsed = match lower.strip_prefix("0x") {
+        Some(hex) => i64::from_str_radix(hex, 16),
+        None => lower.parse(),
+    };
+    parsed.map_err(|_| format!("invalid number '{}'", word))
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Number(value) => format!("'{}'", value),
+        Token::Ident(name) => format!("'{}'", name),
+        Token::Op(op) => format!("'{}'", op),
+        Token::Close => "']'".to_string(),
+    }
+}
+
+/// Parses one level of left associative binary operators, each level binding
+/// tighter than the one before
+fn parse_binary(
+    tokens: &[Token],
+    pos: &mut usize,
+    ops: &[&str],
+    next: fn(&[Token], &mut usize) -> Result<Expr, String>,
+) -> Result<Expr, String> {
+    let mut left = next(tokens, pos)?;
+    while let Some(Token::Op(op)) = tokens.get(*pos).filter(|token| match token {
+        Token::Op(op) => ops.contains(op),
+        _ => false,
+    }) {
+        *pos += 1;
+        let right = next(tokens, pos)?;
+        left = Expr::Binary(op, Box::new(left), Box::new(right));
+    }
+    Ok(left)
+}
+
+fn parse_or(tokens: &[Token], pos: &mut usize) -> Result<Expr, String> {
+    parse_binary(tokens, pos, &["||"], parse_and)
+}
+
+fn parse_and(tokens: &[Token], pos: &mut usize) -> Result<Expr, String> {
+    parse_binary(tokens, pos, &["&&"], parse_comparison)
+}
+
+fn parse_comparison(tokens: &[Token], pos: &mut usize) -> Result<Expr, String> {
+    parse_binary(tokens, pos, &["==", "!=", "<", "<=", ">", ">="], parse_sum)
+}
+
+fn parse_sum(tokens: &[Token], pos: &mut usize) -> Result<Expr, String> {
+    parse_binary(tokens, pos, &["+", "-"], parse_unary)
+}
+
+fn parse_unary(tokens: &[Token], pos: &mut usize) -> Result<Expr, String> {
+    let token = tokens.get(*pos).ok_or("expected expression in condition")?;
+    *pos += 1;
+    match token {
+        Token::Number(value) => Ok(Expr::Num(*value)),
+        Token::Op("!") => Ok(Expr::Not(Box::new(parse_unary(tokens, pos)?))),
+        Token::Op("-") => Ok(Expr::Neg(Box::new(parse_unary(tokens, pos)?))),
+        Token::Op("(") => {
+            let expr = parse_or(tokens, pos)?;
+            expect(tokens, pos, &Token::Op(")"))?;
+            Ok(expr)
+        }
+        Token::Op("[") => parse_memory(tokens, pos, Size::Byte),
+        Token::Ident(name) => match name.as_str() {
+            "zf" => Ok(Expr::Zf),
+            "sf" => Ok(Expr::Sf),
+            "byte" | "word" => {
+                expect(tokens, pos, &Token::Op("["))?;
+                let size = if name == "byte" {
+                    Size::Byte
+                } else {
+                    Size::Word
+                };
+                parse_memory(tokens, pos, size)
+            }
+            reg if reg == "ip"
+                || is_byte(reg)
+                || REGISTERS.contains(&reg)
+                || SEGMENT_REGISTERS.contains(&reg) =>
+            {
+                Ok(Expr::Reg(reg.to_string()))
+            }
+            _ => Err(format!("unknown register or flag '{}'", name)),
+        },
+        token => Err(format!("unexpected {} in condition", describe(token))),
+    }
+}
+
+/// Parses the address after a `[` and the `]` that closes it
+fn parse_memory(tokens: &[Token], pos: &mut usize, size: Size) -> Result<Expr, String> {
+    let addr = parse_sum(tokens, pos)?;
+    expect(tokens, pos, &Token::Close)?;
+    Ok(Expr::Memory(Box::new(addr), size))
+}
+
+fn expect(tokens: &[Token], pos: &mut usize, expected: &Token) -> Result<(), String> {
+    if tokens.get(*pos) != Some(expected) {
+        return Err(format!("expected {} in condition", describe(expected)));
+    }
+    *pos += 1;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        decoder::mov::{BX, CX},
+        sim::{condition::Condition, SimState},
+    };
+
+    fn holds(condition: &str, state: &SimState) -> bool {
+        condition.parse::<Condition>().unwrap().holds(state)
+    }
+
+    #[test]
+    fn test_conditions() {
+        // mov word [0x100], 0x1234; sub cx, cx
+        let mut state = SimState::new(vec![0xC7, 0x06, 0x00, 0x01, 0x34, 0x12, 0x29, 0xC9]);
+        state.run().unwrap();
+        state.set_register_16(CX, 64);
+        state.set_register_16(BX, 0xFE);
+
+        assert!(holds("cx == 64 && ZF", &state));
+        assert!(holds("CX==0x40&&zf", &state));
+        assert!(!holds("cx == 64 && !ZF", &state));
+        assert!(holds("sf || cl < ch + 65", &state));
+        assert!(holds("[0x100] == 0x34 && word [bx + 2] == 0x1234", &state));
+        assert!(holds("byte [0x101] - 2 >= 0x10", &state));
+        assert!(holds("(ip > 7) == 1", &state));
+        assert!(holds("-1 < 0", &state));
+    }
+
+    #[test]
+    fn test_condition_errors() {
+        let err = |condition: &str| condition.parse::<Condition>().unwrap_err();
+
+        assert_eq!(err("cx =="), "expected expression in condition");
+        assert_eq!(err("cx == 1 1"), "unexpected '1' in condition");
+        assert_eq!(err("qx == 1"), "unknown register or flag 'qx'");
+        assert_eq!(err("[0x100"), "expected ']' in condition");
+        assert_eq!(err("cx = 1"), "unexpected character '=' in condition");
+        assert_eq!(err("0xzz"), "invalid number '0xzz'");
+        assert_eq!(
+            "cx  ==  64".parse::<Condition>().unwrap().to_string(),
+            "cx == 64"
+        );
+    }
+}
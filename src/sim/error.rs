@@ -0,0 +1,108 @@
+use std::fmt::Display;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum SimError {
+    /// The bytes at `addr` don't decode to an instruction
+    UnknownInstruction { addr: u16, byte: u8 },
+    /// The instruction can't be run with these operands, e.g. writing to an
+    /// immediate
+    InvalidOperand(String),
+    /// An address past the end of memory
+    MemoryOutOfRange(usize),
+    /// A step was attempted after a hlt
+    Halted,
+    /// A step was attempted after the program exited
+    Exited,
+    /// An int whose handler isn't simulated
+    UnhandledInterrupt(u8),
+}
+
+impl Display for SimError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SimError::UnknownInstruction { addr, byte } => {
+                write!(f, "unknown instruction {:#04x} at {:#x}", byte, addr)
+            }
+            SimError::InvalidOperand(message) => write!(f, "invalid operand: {}", message),
+            SimError::MemoryOutOfRange(addr) => {
+                write!(f, "memory address {:#x} is out of range", addr)
+            }
+            SimError::Halted => write!(f, "the cpu is halted"),
+            SimError::Exited => write!(f, "the program has exited"),
+            SimError::UnhandledInterrupt(vector) => {
+                write!(f, "interrupt {:#04x} isn't simulated", vector)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        decoder::{
+            common::Encoding,
+            instr::Instr,
+            loc::Location,
+            mov::{MoveInstr, CX},
+        },
+        sim::{error::SimError, SimState},
+    };
+
+    #[test]
+    fn test_unknown_instruction() {
+        // mov cx, 1; then an unknown opcode
+        let mut state = SimState::new(vec![0b10111001, 0b1, 0b0, 0b11111111, 0b11111111]);
+
+        assert_eq!(
+            state.run(),
+            Err(SimError::UnknownInstruction {
+                addr: 3,
+                byte: 0xFF
+            })
+        );
+        assert_eq!(state.ip(), 3);
+        assert_eq!(state.get_register_16(CX), 1);
+        assert_eq!(
+            state.run().unwrap_err().to_string(),
+            "unknown instruction 0xff at 0x3"
+        );
+    }
+
+    #[test]
+    fn test_invalid_operand() {
+        let mut state = SimState::new(vec![]);
+        let instr = Instr::Mov(MoveInstr {
+            dest: Location::Immediate8(1),
+            src: Location::Reg("al"),
+            encoding: Encoding::ImmToReg,
+        });
+
+        assert_eq!(
+            state.execute(&instr),
+            Err(SimError::InvalidOperand(
+                "cannot set value to immediate".to_string()
+            ))
+        );
+        assert_eq!(
+            state.get_value_word(&Location::Reg("xx")),
+            Err(SimError::InvalidOperand(
+                "unknown register 'xx'".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_program_too_big() {
+        assert_eq!(
+            SimState::try_new(vec![0; 0x10001]).err(),
+            Some(SimError::MemoryOutOfRange(0x10000))
+        );
+
+        // A program filling the whole segment still runs
+        let mut state = SimState::try_new(vec![0x90; 0x10000]).unwrap();
+        assert_eq!(state.step().unwrap().stop, None);
+        state.set_ip(0xFFFE);
+        assert_eq!(state.step().unwrap().stop, None);
+        assert_eq!(state.ip(), 0xFFFF);
+    }
+}
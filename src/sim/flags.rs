@@ -1,58 +1,93 @@
-use crate::decoder::{loc::Location, op::OpInstr};
+use crate::{
+    decoder::{
+        loc::Size,
+        op::{OpInstr, OpKind},
+    },
+    json::Json,
+};
 
-use super::{is_byte, SimState};
+use super::{error::SimError, SimState};
 
-#[derive(Default)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Default)]
 pub struct Flags {
     pub zero: bool,
     pub sign: bool,
 }
 
+/// Where each flag sits in the flags register
+const ZERO_BIT: u16 = 1 << 6;
+const SIGN_BIT: u16 = 1 << 7;
+
+impl Flags {
+    /// The flags register, with the flags that aren't simulated clear
+    pub fn bits(&self) -> u16 {
+        let zero = if self.zero { ZERO_BIT } else { 0 };
+        let sign = if self.sign { SIGN_BIT } else { 0 };
+        zero | sign
+    }
+
+    pub fn from_bits(bits: u16) -> Self {
+        Self {
+            zero: bits & ZERO_BIT != 0,
+            sign: bits & SIGN_BIT != 0,
+        }
+    }
+
+    pub fn values(&self) -> [(&'static str, bool); 2] {
+        [("zero", self.zero), ("sign", self.sign)]
+    }
+
+    /// The set flags as letters, e.g. `ZS`
+    pub fn letters(&self) -> String {
+        [('Z', self.zero), ('S', self.sign)]
+            .iter()
+            .filter(|(_, set)| *set)
+            .map(|(letter, _)| letter)
+            .collect()
+    }
+
+    pub fn to_json(&self) -> Json {
+        Json::Object(
+            self.values()
+                .into_iter()
+                .map(|(flag, value)| (flag.to_string(), value.into()))
+                .collect(),
+        )
+    }
+}
+
 impl OpInstr {
-    pub fn execute(&self, state: &mut SimState) {
-        match (&self.dest, &self.src) {
-            (Location::Reg(dest), Location::Reg(src)) => {
-                if is_byte(dest) {
-                    let value = state.get_register_8(src);
-                    let current = state.get_register_8(dest);
-                    let res = self.kind.execute_byte(state, current, value);
-                    state.set_register_8(dest, res);
-                } else {
-                    let value = state.get_register_16(src);
-                    let current = state.get_register_16(dest);
-                    let res = self.kind.execute_word(state, current, value);
-                    state.set_register_16(dest, res);
-                }
-            }
-            (Location::Reg(dest), Location::Immediate8(value)) => {
-                if is_byte(dest) {
-                    let current = state.get_register_8(dest);
-                    let res = self.kind.execute_byte(state, current, *value);
-                    state.set_register_8(dest, res);
-                } else {
-                    let current = state.get_register_16(dest);
-                    let res = self.kind.execute_word(state, current, *value as u16);
-                    state.set_register_16(dest, res);
-                }
-            }
-            (Location::Reg(dest), Location::Immediate16(value)) => {
-                let current = state.get_register_16(dest);
-                let res = self.kind.execute_word(state, current, *value);
-                state.set_register_16(dest, res);
-            }
-            _ => unimplemented!(),
+    pub fn execute(&self, state: &mut SimState) -> Result<(), SimError> {
+        // An imm8 added to a word register is still a word operation
+        let size = self
+            .dest
+            .implied_size()
+            .or(self.src.implied_size())
+            .ok_or_else(|| {
+                SimError::InvalidOperand(format!("can't tell the size of {}", self.kind))
+            })?;
+        let current = state.get_value(&self.dest, size)?;
+        let value = state.get_value(&self.src, size)?;
+        let res = match size {
+            Size::Byte => self.kind.execute_byte(state, current as u8, value as u8) as u16,
+            Size::Word => self.kind.execute_word(state, current, value),
+        };
+        // cmp only sets flags, so there's nothing to write back
+        if self.kind != OpKind::Cmp {
+            state.set_value(&self.dest, res, size)?;
         }
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::sim::SimState;
+    use crate::{decoder::loc::Location, sim::SimState};
 
     #[test]
     fn test_add_imm_to_reg() {
         let mut state = SimState::new(vec![0b10000011, 0b11000000, 0b1]);
-        state.run();
+        state.run().unwrap();
         assert_eq!(state.get_register_16("ax"), 1);
         assert!(!state.flags.zero, "zero flag should be false");
         assert!(!state.flags.sign, "sign flag should be false");
@@ -61,7 +96,7 @@ mod test {
     #[test]
     fn test_add_imm_to_reg_byte() {
         let mut state = SimState::new(vec![0b10000011, 0b11000000, 0b1]);
-        state.run();
+        state.run().unwrap();
         assert_eq!(state.get_register_8("al"), 1);
         assert!(!state.flags.zero, "zero flag should be false");
         assert!(!state.flags.sign, "sign flag should be false");
@@ -70,7 +105,7 @@ mod test {
     #[test]
     fn test_add_imm_to_reg_byte_plus() {
         let mut state = SimState::new(vec![0b101, 0b11101000, 0b11]);
-        state.run();
+        state.run().unwrap();
         assert_eq!(state.get_register_16("ax"), 1000);
         assert!(!state.flags.zero, "zero flag should be false");
         assert!(!state.flags.sign, "sign flag should be false");
@@ -80,7 +115,7 @@ mod test {
     fn test_add_reg_to_reg_byte() {
         let mut state = SimState::new(vec![0b100, 0b1]);
         state.set_register_8("bl", 1);
-        state.run();
+        state.run().unwrap();
         assert_eq!(state.get_register_8("al"), 1);
         assert!(!state.flags.zero, "zero flag should be false");
         assert!(!state.flags.sign, "sign flag should be false");
@@ -90,7 +125,7 @@ mod test {
     fn test_add_reg_to_reg_byte_twice() {
         let mut state = SimState::new(vec![0b0, 0b11011000, 0b0, 0b11011000]);
         state.set_register_8("bl", 1);
-        state.run();
+        state.run().unwrap();
         assert_eq!(state.get_register_8("al"), 2);
         assert!(!state.flags.zero, "zero flag should be false");
         assert!(!state.flags.sign, "sign flag should be false");
@@ -100,7 +135,7 @@ mod test {
     fn test_add_reg_to_reg_word() {
         let mut state = SimState::new(vec![0b1, 0b11011000]);
         state.set_register_16("bx", 1);
-        state.run();
+        state.run().unwrap();
         assert_eq!(state.get_register_16("ax"), 1);
         assert!(!state.flags.zero, "zero flag should be false");
         assert!(!state.flags.sign, "sign flag should be false");
@@ -110,9 +145,23 @@ mod test {
     fn test_add_reg_to_reg_word_twice() {
         let mut state = SimState::new(vec![0b1, 0b11011000, 0b1, 0b11011000]);
         state.set_register_16("bx", 1);
-        state.run();
+        state.run().unwrap();
         assert_eq!(state.get_register_16("ax"), 2);
         assert!(!state.flags.zero, "zero flag should be false");
         assert!(!state.flags.sign, "sign flag should be false");
     }
+
+    #[test]
+    fn test_op_to_memory() {
+        // add word [1000], 5; add byte [1000], 5; sub [1000], cx; cmp word [1000], 300
+        let mut state = SimState::new(vec![
+            0x83, 0x06, 0xE8, 0x03, 0x05, 0x80, 0x06, 0xE8, 0x03, 0x05, 0x29, 0x0E, 0xE8, 0x03,
+            0x81, 0x3E, 0xE8, 0x03, 0x2C, 0x01,
+        ]);
+        state.set_register_16("cx", 2);
+        state.run().unwrap();
+        assert_eq!(state.get_value_word(&Location::Mem(1000)), Ok(8));
+        assert!(!state.flags.zero, "zero flag should be false");
+        assert!(state.flags.sign, "sign flag should be true");
+    }
 }
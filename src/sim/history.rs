@@ -0,0 +1,213 @@
+use std::collections::VecDeque;
+
+use super::{
+    flags::Flags, register_16, step::StopReason, trace::MemWrite, trace::REGISTERS, SimState,
+};
+
+/// What an instruction changed, enough to put the machine back how it was
+/// before it ran
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct Undo {
+    pub(crate) ip: u16,
+    /// In [REGISTERS] order
+    pub(crate) registers: [u16; 8],
+    /// Indexed by [Segment](crate::decoder::prefix::Segment)
+    pub(crate) segments: [u16; 4],
+    pub(crate) flags: Flags,
+    pub(crate) steps: u64,
+    pub(crate) cycles: u64,
+    pub(crate) writes: Vec<MemWrite>,
+}
+
+/// The undo log, oldest first, dropping the oldest instruction once there
+/// are more than `budget`
+#[derive(Default)]
+pub(crate) struct History {
+    undos: VecDeque<Undo>,
+    budget: usize,
+}
+
+impl History {
+    pub(crate) fn is_recording(&self) -> bool {
+        self.budget > 0
+    }
+
+    pub(crate) fn record(&mut self, undo: Undo) {
+        if self.undos.len() == self.budget {
+            self.undos.pop_front();
+        }
+        self.undos.push_back(undo);
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.undos.clear();
+    }
+}
+
+/// The most recent write to an address still in the undo log
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct LastWrite {
+    /// Where the instruction that wrote it starts
+    pub instr_addr: u16,
+    /// How many instructions had run before it
+    pub step: u64,
+    pub write: MemWrite,
+}
+
+impl SimState {
+    /// Records what each instruction changes, keeping the last `budget`
+    /// instructions so they can be stepped back through. 0, the default,
+    /// turns recording off.
+    pub fn set_history_budget(&mut self, budget: usize) {
+        let history = &mut self.history;
+        history.budget = budget;
+        while history.undos.len() > budget {
+            history.undos.pop_front();
+        }
+    }
+
+    /// How many instructions can be stepped back through
+    pub fn history_len(&self) -> usize {
+        self.history.undos.len()
+    }
+
+    /// Undoes the last instruction, returning false if there's no history
+    /// left. Changes made other than by instructions, like
+    /// [SimState::set_memory], aren't undone, and observers aren't told.
+    pub fn step_back(&mut self) -> bool {
+        let Some(undo) = self.history.undos.pop_back() else {
+            return false;
+        };
+        self.revert(undo);
+        true
+    }
+
+    /// Puts the machine back how `undo` says it was
+    pub(crate) fn revert(&mut self, undo: Undo) {
+        for write in undo.writes.iter().rev() {
+            self.memory[write.addr as usize] = write.before;
+        }
+        if !undo.writes.is_empty() {
+            self.loops.memory_changed();
+        }
+        for (reg, value) in REGISTERS.iter().zip(undo.registers) {
+            let (_, index) = register_16(reg).unwrap();
+            self.registers[index] = value;
+        }
+        self.segments = undo.segments;
+        self.set_ip(undo.ip);
+        self.flags = undo.flags;
+        self.steps = undo.steps;
+        self.cycles = undo.cycles;
+        self.halted = false;
+        self.exited = false;
+    }
+
+    /// Steps back until ip reaches a breakpoint whose condition holds, or
+    /// the history runs out
+    pub fn reverse_continue(&mut self) -> StopReason {
+        while self.step_back() {
+            if self.breakpoint_hit() {
+                return StopReason::Breakpoint(self.ip);
+            }
+        }
+        StopReason::StartOfHistory
+    }
+
+    /// Which instruction in the history last wrote the physical address
+    /// `addr`
+    pub fn last_write(&self, addr: u32) -> Option<LastWrite> {
+        self.history.undos.iter().rev().find_map(|undo| {
+            let write = undo.writes.iter().rev().find(|write| write.addr == addr)?;
+            Some(LastWrite {
+                instr_addr: undo.ip,
+                step: undo.steps,
+                write: *write,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        decoder::mov::CX,
+        sim::{history::LastWrite, step::StopReason, trace::MemWrite, SimState},
+    };
+
+    // mov cx, 3; mov [0x100], cx; sub cx, 1; jne $-7; hlt
+    const PROGRAM: [u8; 13] = [
+        0xB9, 0x03, 0x00, 0x89, 0x0E, 0x00, 0x01, 0x83, 0xE9, 0x01, 0x75, 0xF7, 0xF4,
+    ];
+
+    #[test]
+    fn test_step_back() {
+        let mut state = SimState::new(PROGRAM.to_vec());
+        state.set_history_budget(100);
+        let start = state.snapshot();
+        for _ in 0..4 {
+            state.step().unwrap();
+        }
+        // From here on mov [0x100], cx writes to 0x200
+        state.set_register_16("ds", 0x10);
+        let middle = state.snapshot();
+        assert_eq!(state.run(), Ok(StopReason::Halt));
+        assert_eq!(state.memory()[0x100], 3);
+        assert_eq!(state.memory()[0x200], 1);
+
+        while state.history_len() > 4 {
+            assert!(state.step_back());
+        }
+        assert_eq!(state.snapshot(), middle);
+        assert_eq!(state.ip(), 3);
+        assert_eq!(state.get_register_16(CX), 2);
+        assert_eq!(state.get_register_16("ds"), 0x10);
+
+        while state.step_back() {}
+        assert_eq!(state.snapshot(), start);
+        assert_eq!(state.get_register_16("ds"), 0);
+    }
+
+    #[test]
+    fn test_reverse_continue_and_last_write() {
+        let mut state = SimState::new(PROGRAM.to_vec());
+        state.set_history_budget(100);
+        state.run().unwrap();
+
+        assert_eq!(
+            state.last_write(0x100),
+            Some(LastWrite {
+                instr_addr: 3,
+                step: 7,
+                write: MemWrite {
+                    addr: 0x100,
+                    before: 2,
+                    after: 1
+                }
+            })
+        );
+        assert_eq!(state.last_write(0x102), None);
+
+        state.add_conditional_breakpoint(3, Some("cx == 2".parse().unwrap()));
+        assert_eq!(state.reverse_continue(), StopReason::Breakpoint(3));
+        assert_eq!(state.memory()[0x100], 3);
+        assert_eq!(state.reverse_continue(), StopReason::StartOfHistory);
+        assert_eq!(state.ip(), 0);
+    }
+
+    #[test]
+    fn test_history_budget() {
+        let mut state = SimState::new(PROGRAM.to_vec());
+        state.run().unwrap();
+        assert!(!state.step_back());
+
+        let mut state = SimState::new(PROGRAM.to_vec());
+        state.set_history_budget(3);
+        state.run().unwrap();
+        assert_eq!(state.history_len(), 3);
+        state.set_history_budget(1);
+        assert!(state.step_back());
+        assert!(!state.step_back());
+        assert_eq!(state.ip(), 12);
+    }
+}
@@ -12,6 +12,19 @@ impl SimState {
             self.ip = self.ip.wrapping_add_signed(offset.into());
         }
     }
+
+    pub fn execute_jmp(&mut self, offset: i16) {
+        self.ip = self.ip.wrapping_add_signed(offset);
+    }
+
+    pub fn execute_call(&mut self, offset: i16) {
+        self.push_word(self.ip);
+        self.ip = self.ip.wrapping_add_signed(offset);
+    }
+
+    pub fn execute_ret(&mut self) {
+        self.ip = self.pop_word();
+    }
 }
 
 #[cfg(test)]
@@ -24,8 +37,23 @@ mod test {
             0b10111001, 0b11, 0b0, 0b10111011, 0b11101000, 0b11, 0b10000011, 0b11000011, 0b1010,
             0b10000011, 0b11101001, 0b1, 0b1110101, 0b11111000,
         ]);
-        state.run();
+        state.run().unwrap();
         assert_eq!(state.get_register_16(BX), 1030);
         assert_eq!(state.ip, 14);
     }
+
+    #[test]
+    fn test_call_ret() {
+        // call 5; jmp short 4; mov bx, 1; ret; mov cx, 2
+        let mut state = SimState::new(vec![
+            0b11101000, 0b10, 0b0, 0b11101011, 0b100, 0b10111011, 0b1, 0b0, 0b11000011, 0b10111001,
+            0b10, 0b0,
+        ]);
+        state.set_register_16("sp", 0x100);
+        state.run().unwrap();
+        assert_eq!(state.get_register_16(BX), 1);
+        assert_eq!(state.get_register_16("cx"), 2);
+        assert_eq!(state.get_register_16("sp"), 0x100);
+        assert_eq!(state.ip, 12);
+    }
 }
@@ -0,0 +1,135 @@
+use std::collections::HashSet;
+
+use super::{flags::Flags, step::StopReason, SimState};
+
+/// How many machine states are remembered when looking for a loop, so only
+/// loops shorter than this are found
+const LOOP_WINDOW: usize = 4096;
+
+/// When to stop a program that would otherwise run forever
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Limits {
+    pub max_steps: Option<u64>,
+    pub max_cycles: Option<u64>,
+    /// Stop once the registers, flags, ip and memory are the same as they
+    /// were at an earlier step
+    pub detect_loops: bool,
+}
+
+#[derive(Default)]
+pub(crate) struct LoopDetector {
+    seen: HashSet<([u16; 8], u16, Flags, u64)>,
+    /// Bumped whenever a write changes memory, so memory doesn't have to be
+    /// compared. Writing a byte back to an earlier value still counts as a
+    /// change, which can only delay finding a loop.
+    memory_version: u64,
+}
+
+impl LoopDetector {
+    pub(crate) fn memory_changed(&mut self) {
+        self.memory_version += 1;
+        self.seen.clear();
+    }
+}
+
+impl SimState {
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// How many instructions have been executed
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Checked after every step, before breakpoints
+    pub(crate) fn check_limits(&mut self) -> Option<StopReason> {
+        if let Some(max_steps) = self.limits.max_steps.filter(|max| self.steps >= *max) {
+            return Some(StopReason::StepLimit(max_steps));
+        }
+        if let Some(max_cycles) = self.limits.max_cycles.filter(|max| self.cycles >= *max) {
+            return Some(StopReason::CycleLimit(max_cycles));
+        }
+        if self.limits.detect_loops {
+            let detector = &mut self.loops;
+            if detector.seen.len() >= LOOP_WINDOW {
+                detector.seen.clear();
+            }
+            let state = (self.registers, self.ip, self.flags, detector.memory_version);
+            if !detector.seen.insert(state) {
+                return Some(StopReason::Loop(self.ip));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        decoder::mov::CX,
+        sim::{limits::Limits, step::StopReason, SimState},
+    };
+
+    #[test]
+    fn test_max_steps() {
+        // jmp $
+        let mut state = SimState::new(vec![0b11101011, 0b11111110]);
+        state.set_limits(Limits {
+            max_steps: Some(10),
+            ..Limits::default()
+        });
+
+        assert_eq!(state.run(), Ok(StopReason::StepLimit(10)));
+        assert_eq!(state.steps(), 10);
+    }
+
+    #[test]
+    fn test_max_cycles() {
+        // jmp $, 15 clocks each time round
+        let mut state = SimState::new(vec![0b11101011, 0b11111110]);
+        state.set_limits(Limits {
+            max_cycles: Some(100),
+            ..Limits::default()
+        });
+
+        assert_eq!(state.run(), Ok(StopReason::CycleLimit(100)));
+        assert_eq!(state.steps(), 7);
+        assert_eq!(state.cycles(), 105);
+    }
+
+    #[test]
+    fn test_detect_tight_loop() {
+        // mov cx, 3; sub cx, 1; jne $-3; jmp $
+        let mut state = SimState::new(vec![
+            0b10111001, 0b11, 0b0, 0b10000011, 0b11101001, 0b1, 0b1110101, 0b11111011, 0b11101011,
+            0b11111110,
+        ]);
+        state.set_limits(Limits {
+            detect_loops: true,
+            ..Limits::default()
+        });
+
+        // The countdown changes cx every time round, so it isn't a loop
+        assert_eq!(state.run(), Ok(StopReason::Loop(8)));
+        assert_eq!(state.get_register_16(CX), 0);
+        assert_eq!(state.steps(), 8);
+    }
+
+    #[test]
+    fn test_memory_write_breaks_loop() {
+        // add word [1000], 1; jmp $-5
+        let mut state = SimState::new(vec![0x83, 0x06, 0xE8, 0x03, 0x01, 0b11101011, 0b11111001]);
+        state.set_limits(Limits {
+            max_steps: Some(100),
+            detect_loops: true,
+            ..Limits::default()
+        });
+
+        assert_eq!(state.run(), Ok(StopReason::StepLimit(100)));
+    }
+}
@@ -0,0 +1,88 @@
+use crate::{
+    decoder::{mov::SP, prefix::Segment},
+    sim::SimState,
+};
+
+use super::{psp, LoadError, MEMORY_TOP_SEGMENT, PSP_SIZE};
+
+/// Where sp starts, below a 0 word so a ret from the program lands on the
+/// int 20h at the start of the PSP
+const STACK_TOP: u16 = 0xFFFE;
+
+impl SimState {
+    /// Loads a DOS .com at offset 0x100 of `segment`, after a PSP with
+    /// `args` as its command tail. Every segment register is `segment`, sp
+    /// is 0xFFFE and ip 0x100, and the program ends once it runs int 20h.
+    pub fn load_com(bytes: &[u8], segment: u16, args: &str) -> Result<Self, LoadError> {
+        let max = (STACK_TOP - PSP_SIZE) as usize;
+        if bytes.len() > max {
+            return Err(LoadError::ProgramTooBig {
+                size: bytes.len(),
+                max,
+            });
+        }
+        // DOS gives a .com all the memory there is
+        let psp = psp(MEMORY_TOP_SEGMENT, args)?;
+        let mut state = SimState::default();
+        for reg in [Segment::Es, Segment::Cs, Segment::Ss, Segment::Ds] {
+            state.set_segment(reg, segment);
+        }
+        let base = state.physical(Segment::Cs, 0);
+        state.set_memory(base, &psp);
+        state.set_memory(base + PSP_SIZE as u32, bytes);
+        state.program_size = PSP_SIZE as usize + bytes.len();
+        state.set_register_16(SP, STACK_TOP);
+        state.set_ip(PSP_SIZE);
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        decoder::{
+            mov::{AX, SP},
+            prefix::Segment,
+        },
+        sim::{error::SimError, load::LoadError, step::StopReason, SimState},
+    };
+
+    #[test]
+    fn test_load_com() {
+        // mov al, [0x80]; mov [0x200], al; ret
+        let program = [0xA0, 0x80, 0x00, 0xA2, 0x00, 0x02, 0xC3];
+        let mut state = SimState::load_com(&program, 0x1000, "abc").unwrap();
+        assert_eq!(state.ip(), 0x100);
+        assert_eq!(state.get_register_16(SP), 0xFFFE);
+        assert_eq!(state.segment(Segment::Ss), 0x1000);
+        assert_eq!(state.memory()[0x10100], 0xA0);
+        assert_eq!(&state.memory()[0x10080..0x10086], b"\x04 abc\r");
+
+        // The ret lands on the int 20h at the start of the PSP
+        assert_eq!(state.run(), Ok(StopReason::Exit));
+        assert_eq!(state.ip(), 2);
+        assert_eq!(state.get_register_16(AX), 4);
+        assert_eq!(state.memory()[0x10200], 4);
+        assert_eq!(state.step().unwrap_err(), SimError::Exited);
+    }
+
+    #[test]
+    fn test_load_com_errors() {
+        assert_eq!(
+            SimState::load_com(&[0; 0xFF00], 0x1000, "").err(),
+            Some(LoadError::ProgramTooBig {
+                size: 0xFF00,
+                max: 0xFEFE
+            })
+        );
+
+        // int 21h
+        let mut state = SimState::load_com(&[0xCD, 0x21], 0x1000, "").unwrap();
+        assert_eq!(state.run(), Err(SimError::UnhandledInterrupt(0x21)));
+        assert_eq!(
+            SimError::UnhandledInterrupt(0x21).to_string(),
+            "interrupt 0x21 isn't simulated"
+        );
+        assert_eq!(state.ip(), 0x100);
+    }
+}
@@ -0,0 +1,276 @@
+use crate::{
+    decoder::{mov::SP, prefix::Segment},
+    sim::SimState,
+};
+
+use super::{psp, LoadError, MEMORY_TOP_SEGMENT, PSP_SIZE};
+
+/// The fixed part of the MZ header, before the relocation table
+const HEADER_SIZE: usize = 0x1C;
+
+/// Files are counted in 512 byte pages
+const PAGE_SIZE: usize = 512;
+
+/// The header fields the loader needs, offsets into the file are in bytes
+/// and sizes in paragraphs
+#[derive(Debug, PartialEq)]
+struct Header {
+    /// Where the header ends and the image starts
+    image_start: usize,
+    /// Where the image ends, past which the file is overlay data DOS doesn't
+    /// load
+    image_end: usize,
+    relocations: usize,
+    relocation_count: usize,
+    min_alloc: u16,
+    max_alloc: u16,
+    ss: u16,
+    sp: u16,
+    cs: u16,
+    ip: u16,
+}
+
+fn word(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+impl Header {
+    fn parse(bytes: &[u8]) -> Result<Self, LoadError> {
+        // DOS takes the signature either way round
+        if !bytes.starts_with(b"MZ") && !bytes.starts_with(b"ZM") {
+            return Err(LoadError::NotAnExe);
+        }
+        if bytes.len() < HEADER_SIZE {
+            return Err(LoadError::TruncatedHeader(bytes.len()));
+        }
+        let last_page = word(bytes, 2);
+        if last_page as usize >= PAGE_SIZE {
+            return Err(LoadError::InvalidLastPage(last_page));
+        }
+        // A last page of 0 is a full one
+        let mut image_end = word(bytes, 4) as usize * PAGE_SIZE;
+        if last_page != 0 {
+            image_end = image_end.saturating_sub(PAGE_SIZE - last_page as usize);
+        }
+        if image_end > bytes.len() {
+            return Err(LoadError::TruncatedImage {
+                size: bytes.len(),
+                expected: image_end,
+            });
+        }
+        let image_start = word(bytes, 8) as usize * 16;
+        if image_start < HEADER_SIZE {
+            return Err(LoadError::HeaderTooSmall(image_start));
+        }
+        if image_start > image_end {
+            return Err(LoadError::HeaderTooBig {
+                header: image_start,
+                size: image_end,
+            });
+        }
+        let count = word(bytes, 6);
+        let offset = word(bytes, 0x18);
+        if offset as usize + count as usize * 4 > bytes.len() {
+            return Err(LoadError::RelocationsOutOfRange { offset, count });
+        }
+        Ok(Self {
+            image_start,
+            image_end,
+            relocations: offset as usize,
+            relocation_count: count as usize,
+            min_alloc: word(bytes, 0xA),
+            max_alloc: word(bytes, 0xC),
+            ss: word(bytes, 0xE),
+            sp: word(bytes, 0x10),
+            ip: word(bytes, 0x14),
+            cs: word(bytes, 0x16),
+        })
+    }
+}
+
+impl SimState {
+    /// Loads a DOS MZ .exe after a PSP at `segment` with `args` as its
+    /// command tail. The image goes in the paragraph after the PSP and every
+    /// segment in the relocation table is moved there. ds and es point at
+    /// the PSP and cs:ip and ss:sp come from the header.
+    pub fn load_exe(bytes: &[u8], segment: u16, args: &str) -> Result<Self, LoadError> {
+        let header = Header::parse(bytes)?;
+        let mut image = bytes[header.image_start..header.image_end].to_vec();
+        if header.cs as usize * 16 + header.ip as usize >= image.len() {
+            return Err(LoadError::EntryOutsideImage {
+                cs: header.cs,
+                ip: header.ip,
+                size: image.len(),
+            });
+        }
+
+        let paragraphs = image.len().div_ceil(16) as u32;
+        let load = segment as u32 + PSP_SIZE as u32 / 16;
+        let available = (MEMORY_TOP_SEGMENT as u32).saturating_sub(segment as u32);
+        let needed = load - segment as u32 + paragraphs + header.min_alloc as u32;
+        if needed > available {
+            return Err(LoadError::OutOfMemory { needed, available });
+        }
+        // DOS hands out as much as max alloc asks for, up to what's free
+        let end = (load + paragraphs + header.max_alloc as u32).min(MEMORY_TOP_SEGMENT as u32);
+
+        let load = load as u16;
+        for entry in 0..header.relocation_count {
+            let at = header.relocations + entry * 4;
+            let (offset, reloc_segment) = (word(bytes, at), word(bytes, at + 2));
+            let target = reloc_segment as usize * 16 + offset as usize;
+            if target + 2 > image.len() {
+                return Err(LoadError::RelocationOutsideImage {
+                    segment: reloc_segment,
+                    offset,
+                });
+            }
+            let value = word(&image, target).wrapping_add(load);
+            image[target..target + 2].copy_from_slice(&value.to_le_bytes());
+        }
+
+        let psp = psp(end as u16, args)?;
+        let mut state = SimState::default();
+        state.set_segment(Segment::Es, segment);
+        state.set_segment(Segment::Ds, segment);
+        state.set_segment(Segment::Cs, load.wrapping_add(header.cs));
+        state.set_segment(Segment::Ss, load.wrapping_add(header.ss));
+        state.set_memory(state.physical(Segment::Es, 0), &psp);
+        let base = (load as u32) << 4;
+        state.set_memory(base, &image);
+        // Where the image ends relative to cs, as far as cs reaches
+        let code = (state.segment(Segment::Cs) as u32) << 4;
+        state.program_size = (base + image.len() as u32 - code).min(0x10000) as usize;
+        state.set_register_16(SP, header.sp);
+        state.set_ip(header.ip);
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        decoder::{
+            mov::{CX, SP},
+            prefix::Segment,
+        },
+        sim::{load::LoadError, step::StopReason, SimState},
+    };
+
+    /// A 64 byte exe with a 32 byte header and one relocation, of the word
+    /// at 0x10 in the image
+    fn exe() -> Vec<u8> {
+        let mut bytes = vec![0; 64];
+        bytes[..0x1C].copy_from_slice(&[
+            b'M', b'Z', 64, 0, 1, 0, 1, 0, 2, 0, 0x10, 0, 0xFF, 0xFF, 2, 0, 0, 1, 0, 0, 0, 0, 0, 0,
+            0x1C, 0, 0, 0,
+        ]);
+        // Relocation of 0000:0010
+        bytes[0x1C..0x20].copy_from_slice(&[0x10, 0, 0, 0]);
+        // mov cx, 5; int 20h
+        bytes[0x20..0x25].copy_from_slice(&[0xB9, 0x05, 0x00, 0xCD, 0x20]);
+        bytes[0x30..0x32].copy_from_slice(&[0x01, 0x00]);
+        bytes
+    }
+
+    #[test]
+    fn test_load_exe() {
+        let mut state = SimState::load_exe(&exe(), 0x1000, "x").unwrap();
+        assert_eq!(state.segment(Segment::Ds), 0x1000);
+        assert_eq!(state.segment(Segment::Es), 0x1000);
+        assert_eq!(state.segment(Segment::Cs), 0x1010);
+        assert_eq!(state.segment(Segment::Ss), 0x1012);
+        assert_eq!(state.get_register_16(SP), 0x100);
+        assert_eq!(state.ip(), 0);
+        // The relocated word is now the load segment plus one
+        assert_eq!(state.memory()[0x10110..0x10112], [0x11, 0x10]);
+        // The PSP's end of memory is the top, as max alloc asks for all
+        assert_eq!(state.memory()[0x10002..0x10004], [0x00, 0xA0]);
+        assert_eq!(&state.memory()[0x10080..0x10083], b"\x02 x");
+
+        assert_eq!(state.run(), Ok(StopReason::Exit));
+        assert_eq!(state.get_register_16(CX), 5);
+    }
+
+    #[test]
+    fn test_load_exe_errors() {
+        let load = |bytes: &[u8]| SimState::load_exe(bytes, 0x1000, "").err();
+        let patched = |offset: usize, patch: &[u8]| {
+            let mut bytes = exe();
+            bytes[offset..offset + patch.len()].copy_from_slice(patch);
+            load(&bytes)
+        };
+        assert_eq!(load(&[0xB9, 0x05, 0x00]), Some(LoadError::NotAnExe));
+        assert!(patched(0, b"ZM").is_none());
+        assert_eq!(load(b"MZ\0\0"), Some(LoadError::TruncatedHeader(4)));
+        assert_eq!(patched(2, &[0, 2]), Some(LoadError::InvalidLastPage(512)));
+        assert_eq!(
+            load(&exe()[..60]),
+            Some(LoadError::TruncatedImage {
+                size: 60,
+                expected: 64
+            })
+        );
+        assert_eq!(
+            patched(8, &[5, 0]),
+            Some(LoadError::HeaderTooBig {
+                header: 80,
+                size: 64
+            })
+        );
+        assert_eq!(patched(8, &[1, 0]), Some(LoadError::HeaderTooSmall(16)));
+        assert_eq!(
+            patched(6, &[10, 0]),
+            Some(LoadError::RelocationsOutOfRange {
+                offset: 0x1C,
+                count: 10
+            })
+        );
+        assert_eq!(
+            patched(0x1C, &[0x1F, 0, 0, 0]),
+            Some(LoadError::RelocationOutsideImage {
+                segment: 0,
+                offset: 0x1F
+            })
+        );
+        assert_eq!(
+            patched(0xA, &[0, 0x90]),
+            Some(LoadError::OutOfMemory {
+                needed: 0x9012,
+                available: 0x9000
+            })
+        );
+        // The image is 32 bytes, so 0001:0010 is just past it
+        assert_eq!(
+            patched(0x14, &[0x10, 0, 1, 0]),
+            Some(LoadError::EntryOutsideImage {
+                cs: 1,
+                ip: 0x10,
+                size: 32
+            })
+        );
+        assert_eq!(
+            LoadError::EntryOutsideImage {
+                cs: 1,
+                ip: 0x10,
+                size: 32
+            }
+            .to_string(),
+            "entry point 0001:0010 is outside the 32 byte image"
+        );
+        assert_eq!(
+            LoadError::OutOfMemory {
+                needed: 0x9012,
+                available: 0x9000
+            }
+            .to_string(),
+            "program needs 0x9012 paragraphs of memory, but only 0x9000 are free"
+        );
+
+        // A max alloc that fits ends the program's memory early
+        let mut bytes = exe();
+        bytes[0xC..0xE].copy_from_slice(&[0x20, 0]);
+        let state = SimState::load_exe(&bytes, 0x1000, "").unwrap();
+        assert_eq!(state.memory()[0x10002..0x10004], [0x32, 0x10]);
+    }
+}
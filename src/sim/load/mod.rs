@@ -0,0 +1,227 @@
+use std::{fmt::Display, str::FromStr};
+
+use super::SimState;
+
+pub mod com;
+pub mod exe;
+
+/// Where DOS programs are loaded unless another segment is given
+pub const DEFAULT_LOAD_SEGMENT: u16 = 0x1000;
+
+/// The first segment past conventional memory, which DOS gives every program
+/// up to
+const MEMORY_TOP_SEGMENT: u16 = 0xA000;
+
+/// The Program Segment Prefix is the first 256 bytes of a DOS program's
+/// memory
+pub const PSP_SIZE: u16 = 0x100;
+
+/// Where the command tail's length is in the PSP, followed by the tail
+const COMMAND_TAIL: usize = 0x80;
+
+/// The command tail has a length byte and ends with a carriage return
+const MAX_COMMAND_TAIL: usize = 126;
+
+/// How the bytes of a program file are laid out
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum ProgramFormat {
+    /// Loaded as is at address 0, where execution starts
+    #[default]
+    Raw,
+    /// A DOS .com, loaded at 0x100 after a PSP
+    Com,
+    /// A DOS MZ .exe, relocated to the paragraph after a PSP
+    Exe,
+}
+
+impl FromStr for ProgramFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "raw" => Ok(ProgramFormat::Raw),
+            "com" => Ok(ProgramFormat::Com),
+            "exe" => Ok(ProgramFormat::Exe),
+            _ => Err(format!(
+                "unknown program format '{}', expected raw, com or exe",
+                s
+            )),
+        }
+    }
+}
+
+/// How to load a program file
+#[derive(Debug, PartialEq, Clone)]
+pub struct LoadOptions {
+    pub format: ProgramFormat,
+    /// Where a DOS program's PSP goes
+    pub segment: u16,
+    /// The command line after the program name, for the PSP
+    pub args: String,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        Self {
+            format: ProgramFormat::default(),
+            segment: DEFAULT_LOAD_SEGMENT,
+            args: String::new(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum LoadError {
+    /// The program doesn't fit in the memory it's loaded into
+    ProgramTooBig { size: usize, max: usize },
+    /// The command tail is longer than the 126 bytes the PSP holds
+    CommandTailTooLong(usize),
+    /// An .exe that doesn't start with `MZ`
+    NotAnExe,
+    /// The file is shorter than the 28 byte MZ header
+    TruncatedHeader(usize),
+    /// The count of bytes in the last 512 byte page is 512 or more
+    InvalidLastPage(u16),
+    /// The header says the file is longer than it is
+    TruncatedImage { size: usize, expected: usize },
+    /// The header says it's shorter than its own fixed part
+    HeaderTooSmall(usize),
+    /// The header doesn't fit in the part of the file the header says is
+    /// loaded
+    HeaderTooBig { header: usize, size: usize },
+    /// The relocation table runs past the end of the file
+    RelocationsOutOfRange { offset: u16, count: u16 },
+    /// A relocation points outside the image
+    RelocationOutsideImage { segment: u16, offset: u16 },
+    /// The image and the extra memory it needs don't fit below 640K, in
+    /// paragraphs
+    OutOfMemory { needed: u32, available: u32 },
+    /// cs:ip, relative to the start of the image, is past its end
+    EntryOutsideImage { cs: u16, ip: u16, size: usize },
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoadError::ProgramTooBig { size, max } => write!(
+                f,
+                "program is {:#x} bytes, but at most {:#x} fit",
+                size, max
+            ),
+            LoadError::CommandTailTooLong(len) => write!(
+                f,
+                "command tail is {} bytes, but at most {} fit",
+                len, MAX_COMMAND_TAIL
+            ),
+            LoadError::NotAnExe => write!(f, "not an exe, it doesn't start with MZ"),
+            LoadError::TruncatedHeader(size) => write!(
+                f,
+                "file is {} bytes, too short for the 28 byte MZ header",
+                size
+            ),
+            LoadError::InvalidLastPage(bytes) => write!(
+                f,
+                "MZ header says the last page has {} bytes, but pages are 512",
+                bytes
+            ),
+            LoadError::TruncatedImage { size, expected } => write!(
+                f,
+                "file is {} bytes, but the MZ header says it's {}",
+                size, expected
+            ),
+            LoadError::HeaderTooSmall(header) => {
+                write!(f, "MZ header is {} bytes, less than its fixed 28", header)
+            }
+            LoadError::HeaderTooBig { header, size } => write!(
+                f,
+                "MZ header is {} bytes, more than the {} bytes loaded",
+                header, size
+            ),
+            LoadError::RelocationsOutOfRange { offset, count } => write!(
+                f,
+                "relocation table of {} entries at {:#x} runs past the end of the file",
+                count, offset
+            ),
+            LoadError::RelocationOutsideImage { segment, offset } => write!(
+                f,
+                "relocation at {:04x}:{:04x} is outside the image",
+                segment, offset
+            ),
+            LoadError::OutOfMemory { needed, available } => write!(
+                f,
+                "program needs {:#x} paragraphs of memory, but only {:#x} are free",
+                needed, available
+            ),
+            LoadError::EntryOutsideImage { cs, ip, size } => write!(
+                f,
+                "entry point {:04x}:{:04x} is outside the {} byte image",
+                cs, ip, size
+            ),
+        }
+    }
+}
+
+impl SimState {
+    /// Loads a program file the way `options` say
+    pub fn load(bytes: &[u8], options: &LoadOptions) -> Result<Self, LoadError> {
+        match options.format {
+            ProgramFormat::Raw => {
+                SimState::try_new(bytes.to_vec()).map_err(|_| LoadError::ProgramTooBig {
+                    size: bytes.len(),
+                    max: 0x10000,
+                })
+            }
+            ProgramFormat::Com => SimState::load_com(bytes, options.segment, &options.args),
+            ProgramFormat::Exe => SimState::load_exe(bytes, options.segment, &options.args),
+        }
+    }
+}
+
+/// A minimal PSP: an int 20h at 0 for a ret to land on, `end`, the segment
+/// past the program's memory, at 2 and the command tail at 0x80. Like DOS,
+/// the tail starts with the space after the program name.
+fn psp(end: u16, args: &str) -> Result<[u8; PSP_SIZE as usize], LoadError> {
+    let tail = match args {
+        "" => String::new(),
+        args => format!(" {}", args),
+    };
+    if tail.len() > MAX_COMMAND_TAIL {
+        return Err(LoadError::CommandTailTooLong(tail.len()));
+    }
+    let mut psp = [0; PSP_SIZE as usize];
+    psp[..2].copy_from_slice(&[0xCD, 0x20]);
+    psp[2..4].copy_from_slice(&end.to_le_bytes());
+    psp[COMMAND_TAIL] = tail.len() as u8;
+    psp[COMMAND_TAIL + 1..][..tail.len()].copy_from_slice(tail.as_bytes());
+    psp[COMMAND_TAIL + 1 + tail.len()] = b'\r';
+    Ok(psp)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sim::load::{psp, LoadError, ProgramFormat};
+
+    #[test]
+    fn test_psp() {
+        let prefix = psp(0xA000, "a.txt /v").unwrap();
+        assert_eq!(prefix[..4], [0xCD, 0x20, 0x00, 0xA0]);
+        assert_eq!(prefix[0x80], 9);
+        assert_eq!(&prefix[0x81..0x8B], b" a.txt /v\r");
+
+        assert_eq!(psp(0xA000, "").unwrap()[0x80..0x82], [0, b'\r']);
+        assert_eq!(
+            psp(0xA000, &"x".repeat(126)).unwrap_err().to_string(),
+            "command tail is 127 bytes, but at most 126 fit"
+        );
+        assert_eq!(
+            psp(0xA000, &"x".repeat(125)).map(|prefix| prefix[0xFF]),
+            Ok(b'\r')
+        );
+        assert_eq!(
+            psp(0xA000, &"x".repeat(200)),
+            Err(LoadError::CommandTailTooLong(201))
+        );
+        assert_eq!("COM".parse(), Ok(ProgramFormat::Com));
+        assert!("elf".parse::<ProgramFormat>().is_err());
+    }
+}
@@ -1,208 +1,576 @@
-use std::{fmt::Display, path::PathBuf};
+use std::{
+    cell::{Cell, RefCell},
+    fmt::Display,
+    path::PathBuf,
+};
 
 use crate::decoder::{
-    instr::{decode_instr, Instr},
+    instr::Instr,
     loc::{eac::EffectiveAddress, eac_mode::EffectiveAddressMode, Location, Size},
-    mov::{MoveInstr, BP, BX, DI, SI},
+    mov::{MoveInstr, AX, BP, BX, CX, DI, DX, SI, SP},
+    prefix::Segment,
     state::Decoder,
+    xchg::XchgInstr,
 };
 
-use self::flags::Flags;
+use self::{
+    bus::{Biu, TimingMode, Transfers},
+    error::SimError,
+    flags::Flags,
+    history::History,
+    limits::{Limits, LoopDetector},
+    observer::{Observer, TracePrinter},
+    step::{Breakpoint, StopReason},
+    timing::Cpu,
+    trace::MemWrite,
+    watch::{WatchHit, Watchpoint},
+};
 
+pub mod bus;
+pub mod condition;
+pub mod error;
 pub mod flags;
+pub mod history;
 pub mod jmp;
+pub mod limits;
+pub mod load;
+pub mod observer;
 pub mod op_kind;
+pub mod snapshot;
+pub mod step;
+pub mod string;
+pub mod timing;
+pub mod trace;
+pub mod watch;
+
+/// The 8086's 20 bit address space
+pub const MEMORY_SIZE: usize = 0x100000;
+
+/// The segment registers, named the way the decoder writes them
+pub const SEGMENT_REGISTERS: [&str; 4] = ["cs", "ds", "es", "ss"];
 
 pub struct SimState {
     registers: [u16; 8],
+    /// es, cs, ss and ds, in the order of their encoding
+    segments: [u16; 4],
     ip: u16,
     flags: Flags,
-    instr_len: u8,
+    instr_len: usize,
+    /// Where the loaded program ends in the code segment
     program_size: usize,
-    memory: [u8; 0xFFFF],
+    memory: Vec<u8>,
+    /// The segment override prefix of the instruction being executed
+    segment_override: Option<Segment>,
+    /// Memory written by the instruction being executed
+    writes: Vec<MemWrite>,
+    halted: bool,
+    /// The program ended itself with int 20h
+    exited: bool,
+    breakpoints: Vec<Breakpoint>,
+    watchpoints: Vec<Watchpoint>,
+    /// The first watched byte the instruction being executed touched
+    watch_hit: Cell<Option<WatchHit>>,
+    limits: Limits,
+    steps: u64,
+    /// Estimated clocks taken so far
+    cycles: u64,
+    loops: LoopDetector,
+    history: History,
+    cpu: Cpu,
+    timing: TimingMode,
+    biu: Biu,
+    transfers: Cell<Transfers>,
+    /// Borrowed while calling back, reads made by an observer aren't reported
+    observers: RefCell<Vec<Box<dyn Observer>>>,
 }
 
-impl SimState {
-    pub fn new(src: Vec<u8>) -> Self {
-        let mut memory = [0; 0xFFFF];
-        let program_size = src.len();
-        memory[..program_size].copy_from_slice(&src);
+impl Default for SimState {
+    /// A machine with no program, with every register 0
+    fn default() -> Self {
         Self {
             registers: [0; 8],
+            segments: [0; 4],
             flags: Flags::default(),
-            memory,
+            memory: vec![0; MEMORY_SIZE],
             instr_len: 0,
-            program_size,
+            program_size: 0,
             ip: 0,
+            segment_override: None,
+            writes: vec![],
+            halted: false,
+            exited: false,
+            breakpoints: vec![],
+            watchpoints: vec![],
+            watch_hit: Cell::default(),
+            limits: Limits::default(),
+            steps: 0,
+            cycles: 0,
+            loops: LoopDetector::default(),
+            history: History::default(),
+            cpu: Cpu::default(),
+            timing: TimingMode::default(),
+            biu: Biu::default(),
+            transfers: Cell::default(),
+            observers: RefCell::default(),
+        }
+    }
+}
+
+impl SimState {
+    /// [SimState::try_new] for programs known to fit
+    #[cfg(test)]
+    pub(crate) fn new(src: Vec<u8>) -> Self {
+        Self::try_new(src).unwrap()
+    }
+
+    /// Loads `src` at address 0 with every segment register 0, so the
+    /// program sees a single flat 64K segment
+    pub fn try_new(src: Vec<u8>) -> Result<Self, SimError> {
+        if src.len() > 0x10000 {
+            return Err(SimError::MemoryOutOfRange(src.len() - 1));
+        }
+        let mut state = Self::default();
+        state.memory[..src.len()].copy_from_slice(&src);
+        state.program_size = src.len();
+        Ok(state)
+    }
+
+    pub fn ip(&self) -> u16 {
+        self.ip
+    }
+
+    /// Moves execution to `ip`, emptying the prefetch queue like a jump
+    pub fn set_ip(&mut self, ip: u16) {
+        self.ip = ip;
+        self.biu.flush();
+    }
+
+    /// Whether the program ended itself with int 20h
+    pub fn exited(&self) -> bool {
+        self.exited
+    }
+
+    pub fn flags(&self) -> Flags {
+        self.flags
+    }
+
+    pub fn set_flags(&mut self, flags: Flags) {
+        self.flags = flags;
+    }
+
+    pub fn segment(&self, segment: Segment) -> u16 {
+        self.segments[segment as usize]
+    }
+
+    pub fn set_segment(&mut self, segment: Segment, value: u16) {
+        self.segments[segment as usize] = value;
+    }
+
+    /// Where `offset` in `segment` is in the address space, wrapping around
+    /// the end of it like the 8086 does
+    pub fn physical(&self, segment: Segment, offset: u16) -> u32 {
+        physical_address(self.segment(segment), offset)
+    }
+
+    /// The whole address space, looked at without notifying observers
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    /// The 64K a segment register points at, wrapping around the end of
+    /// memory
+    pub fn segment_memory(&self, segment: Segment) -> Vec<u8> {
+        (0..=u16::MAX)
+            .map(|offset| self.memory[self.physical(segment, offset) as usize])
+            .collect()
+    }
+
+    /// Copies `bytes` in from the physical address `addr`, wrapping around
+    /// the end of memory. Unlike a store by an instruction, observers and
+    /// watchpoints don't see it.
+    pub fn set_memory(&mut self, addr: u32, bytes: &[u8]) {
+        for (offset, byte) in bytes.iter().enumerate() {
+            self.memory[(addr as usize + offset) % MEMORY_SIZE] = *byte;
+        }
+        self.loops.memory_changed();
+    }
+
+    pub fn cpu(&self) -> Cpu {
+        self.cpu
+    }
+
+    /// Sets the model whose bus timing the clock estimates follow
+    pub fn set_cpu(&mut self, cpu: Cpu) {
+        self.cpu = cpu;
+    }
+
+    pub fn timing(&self) -> TimingMode {
+        self.timing
+    }
+
+    /// Sets whether clock estimates include waiting for the instruction
+    /// queue, which starts out empty
+    pub fn set_timing(&mut self, timing: TimingMode) {
+        self.timing = timing;
+        self.biu.flush();
+    }
+
+    pub fn add_observer(&mut self, observer: Box<dyn Observer>) {
+        self.observers.get_mut().push(observer);
+    }
+
+    /// Calls `f` with each observer and the current state
+    fn notify(&self, mut f: impl FnMut(&mut dyn Observer, &SimState)) {
+        for observer in self.observers.borrow_mut().iter_mut() {
+            f(observer.as_mut(), self);
         }
     }
 
+    /// Panics if `name` isn't a 16 bit register or ip
     pub fn get_register_16(&self, name: &str) -> u16 {
-        match name {
-            "ax" => self.registers[0],
-            "bx" => self.registers[1],
-            "cx" => self.registers[2],
-            "dx" => self.registers[3],
-            "si" => self.registers[4],
-            "di" => self.registers[5],
-            "bp" => self.registers[6],
-            "sp" => self.registers[7],
-            "ip" => self.ip,
-            _ => panic!("Unknown register: {}", name),
+        self.try_get_register_16(name)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_get_register_16(&self, name: &str) -> Result<u16, SimError> {
+        if name == "ip" {
+            return Ok(self.ip);
+        }
+        if let Some((_, segment)) = segment_register(name) {
+            return Ok(self.segment(segment));
         }
+        let (_, index) = register_16(name)?;
+        Ok(self.registers[index])
     }
 
+    /// Panics if `name` isn't a 16 bit register
     pub fn set_register_16(&mut self, name: &str, value: u16) {
-        match name {
-            "ax" => self.registers[0] = value,
-            "bx" => self.registers[1] = value,
-            "cx" => self.registers[2] = value,
-            "dx" => self.registers[3] = value,
-            "si" => self.registers[4] = value,
-            "di" => self.registers[5] = value,
-            "bp" => self.registers[6] = value,
-            "sp" => self.registers[7] = value,
-            _ => panic!("Unknown register: {}", name),
+        self.try_set_register_16(name, value)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_set_register_16(&mut self, name: &str, value: u16) -> Result<(), SimError> {
+        if let Some((reg, segment)) = segment_register(name) {
+            let before = self.segment(segment);
+            self.set_segment(segment, value);
+            for observer in self.observers.get_mut() {
+                observer.register_write(reg, before, value);
+            }
+            return Ok(());
+        }
+        let (reg, index) = register_16(name)?;
+        let before = std::mem::replace(&mut self.registers[index], value);
+        for observer in self.observers.get_mut() {
+            observer.register_write(reg, before, value);
         }
+        Ok(())
     }
 
-    #[allow(dead_code)]
+    #[cfg(test)]
     fn get_register_8(&self, name: &str) -> u8 {
-        match name {
-            "al" => self.registers[0] as u8,
-            "ah" => (self.registers[0] >> 8) as u8,
-            "bl" => self.registers[1] as u8,
-            "bh" => (self.registers[1] >> 8) as u8,
-            "cl" => self.registers[2] as u8,
-            "ch" => (self.registers[2] >> 8) as u8,
-            "dl" => self.registers[3] as u8,
-            "dh" => (self.registers[3] >> 8) as u8,
-            _ => panic!("Unknown register: {}", name),
-        }
+        self.try_get_register_8(name)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_get_register_8(&self, name: &str) -> Result<u8, SimError> {
+        let (reg, high) = register_8(name)?;
+        let value = self.get_register_16(reg);
+        Ok(if high {
+            (value >> 8) as u8
+        } else {
+            value as u8
+        })
     }
 
+    #[cfg(test)]
     fn set_register_8(&mut self, name: &str, value: u8) {
+        self.try_set_register_8(name, value)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_set_register_8(&mut self, name: &str, value: u8) -> Result<(), SimError> {
         let value = value as u16;
-        match name {
-            "al" => self.registers[0] = (self.registers[0] & 0xFF00) | value,
-            "ah" => self.registers[0] = (self.registers[0] & 0x00FF) | (value << 8),
-            "bl" => self.registers[1] = (self.registers[1] & 0xFF00) | value,
-            "bh" => self.registers[1] = (self.registers[1] & 0x00FF) | (value << 8),
-            "cl" => self.registers[2] = (self.registers[2] & 0xFF00) | value,
-            "ch" => self.registers[2] = (self.registers[2] & 0x00FF) | (value << 8),
-            "dl" => self.registers[3] = (self.registers[3] & 0xFF00) | value,
-            "dh" => self.registers[3] = (self.registers[3] & 0x00FF) | (value << 8),
-            _ => panic!("Unknown register: {}", name),
-        }
+        let (reg, high) = register_8(name)?;
+        let current = self.get_register_16(reg);
+        let value = if high {
+            (current & 0x00FF) | (value << 8)
+        } else {
+            (current & 0xFF00) | value
+        };
+        self.try_set_register_16(reg, value)
     }
 
-    pub fn execute(&mut self, instr: &Instr) {
+    /// Runs `instr` as if it had been decoded at ip. Unlike a step, anything
+    /// changed before an error is left changed.
+    pub fn execute(&mut self, instr: &Instr) -> Result<(), SimError> {
         match instr {
-            Instr::Mov(mov) => self.execute_mov(mov),
-            Instr::Op(op) => op.execute(self),
+            Instr::Mov(mov) => self.execute_mov(mov)?,
+            Instr::Op(op) => op.execute(self)?,
             Instr::Je(offset) => self.execute_je(*offset),
             Instr::Jne(offset) => self.execute_jne(*offset),
+            Instr::Jmp(offset) => self.execute_jmp(*offset as i16),
+            Instr::JmpNear(offset) => self.execute_jmp(*offset),
+            Instr::Call(offset) => self.execute_call(*offset),
+            Instr::Ret => self.execute_ret(),
+            Instr::Hlt => self.halted = true,
+            Instr::Int(vector) => self.execute_int(*vector)?,
+            Instr::Xchg(xchg) => self.execute_xchg(xchg)?,
+            Instr::Str(string) => self.execute_string(string)?,
+            Instr::Nop => {}
+            Instr::Prefixed(prefixed) => {
+                self.segment_override = prefixed.segment();
+                // Nothing else uses the bus, so lock changes nothing
+                let result = match (prefixed.rep(), prefixed.instr.as_ref()) {
+                    (Some(rep), Instr::Str(string)) => self.execute_rep(rep, string),
+                    (_, instr) => self.execute(instr),
+                };
+                self.segment_override = None;
+                result?
+            }
+        }
+        Ok(())
+    }
+
+    /// Only int 20h, DOS's program terminate, is simulated. There's no
+    /// interrupt vector table or iret to run other handlers with.
+    fn execute_int(&mut self, vector: u8) -> Result<(), SimError> {
+        for observer in self.observers.get_mut() {
+            observer.interrupt(vector);
+        }
+        match vector {
+            0x20 => {
+                self.exited = true;
+                Ok(())
+            }
+            _ => Err(SimError::UnhandledInterrupt(vector)),
         }
     }
 
-    fn execute_mov(&mut self, mov: &MoveInstr) {
+    fn execute_mov(&mut self, mov: &MoveInstr) -> Result<(), SimError> {
         match (mov.dest.implied_size(), mov.src.implied_size()) {
             (None, None) | (Some(Size::Word), _) | (_, Some(Size::Word)) => {
-                self.set_value_word(&mov.dest, self.get_value_word(&mov.src))
+                self.set_value_word(&mov.dest, self.get_value_word(&mov.src)?)
             }
             (Some(Size::Byte), _) | (_, Some(Size::Byte)) => {
-                self.set_value_byte(&mov.dest, self.get_value_byte(&mov.src))
+                self.set_value_byte(&mov.dest, self.get_value_byte(&mov.src)?)
             }
         }
     }
 
-    pub fn write_memory(&self, file: &PathBuf) {
-        std::fs::write(file, self.memory).unwrap();
+    fn execute_xchg(&mut self, xchg: &XchgInstr) -> Result<(), SimError> {
+        let size = xchg
+            .dest
+            .implied_size()
+            .or(xchg.src.implied_size())
+            .unwrap_or(Size::Word);
+        let dest = self.get_value(&xchg.dest, size)?;
+        let src = self.get_value(&xchg.src, size)?;
+        self.set_value(&xchg.dest, src, size)?;
+        self.set_value(&xchg.src, dest, size)
     }
 
-    pub fn run(&mut self) {
-        while self.has_more() {
-            let instr = decode_instr(self);
-            self.advance();
-            self.execute(&instr);
+    pub fn get_value(&self, loc: &Location, size: Size) -> Result<u16, SimError> {
+        match size {
+            Size::Byte => self.get_value_byte(loc).map(u16::from),
+            Size::Word => self.get_value_word(loc),
         }
     }
 
-    pub fn run_trace(&mut self) {
-        while self.has_more() {
-            let instr = decode_instr(self);
-            println!("{}", instr);
-            self.advance();
-            self.execute(&instr);
+    pub fn set_value(&mut self, loc: &Location, value: u16, size: Size) -> Result<(), SimError> {
+        match size {
+            Size::Byte => self.set_value_byte(loc, value as u8),
+            Size::Word => self.set_value_word(loc, value),
         }
     }
 
-    pub fn get_value_byte(&self, loc: &Location) -> u8 {
+    pub fn push_word(&mut self, value: u16) {
+        let sp = self.get_register_16(SP).wrapping_sub(2);
+        self.set_register_16(SP, sp);
+        self.write_word(Segment::Ss, sp, value);
+    }
+
+    pub fn pop_word(&mut self) -> u16 {
+        let sp = self.get_register_16(SP);
+        let value = self.read_word(Segment::Ss, sp);
+        self.set_register_16(SP, sp.wrapping_add(2));
+        value
+    }
+
+    /// Writes the 64K from ds:0, wrapping around the end of memory. For a
+    /// .com, where every segment is the same, that's all the program can
+    /// address, but anything an .exe keeps in other segments is left out.
+    pub fn write_memory(&self, file: &PathBuf) {
+        std::fs::write(file, self.segment_memory(Segment::Ds)).unwrap();
+    }
+
+    /// Steps until the simulator stops, returning why
+    pub fn run(&mut self) -> Result<StopReason, SimError> {
+        loop {
+            if let Some(stop) = self.step()?.stop {
+                return Ok(stop);
+            }
+        }
+    }
+
+    /// Runs with a [TracePrinter] observing until the simulator stops
+    pub fn run_trace(&mut self) -> Result<StopReason, SimError> {
+        self.add_observer(Box::new(TracePrinter));
+        let result = self.run();
+        self.observers.get_mut().pop();
+        result
+    }
+
+    pub fn get_value_byte(&self, loc: &Location) -> Result<u8, SimError> {
         match loc {
-            Location::Reg(reg) => self.get_register_8(reg),
-            Location::Immediate8(value) => *value,
-            Location::Immediate16(value) => panic!("Expected byte, got word: {}", value),
-            Location::Mem(addr) => self.memory[*addr as usize],
+            Location::Reg(reg) => self.try_get_register_8(reg),
+            Location::Immediate8(value) => Ok(*value),
+            Location::Immediate16(value) => Err(SimError::InvalidOperand(format!(
+                "expected byte, got word {}",
+                value
+            ))),
+            Location::Mem(addr) => Ok(self.read_byte(self.data_segment(None), *addr)),
             Location::Eac(eac) => {
-                let addr = self.get_addr(eac);
-                self.memory[addr as usize]
+                Ok(self.read_byte(self.data_segment(Some(eac)), self.get_addr(eac)))
             }
         }
     }
 
-    pub fn set_value_byte(&mut self, loc: &Location, value: u8) {
+    pub fn set_value_byte(&mut self, loc: &Location, value: u8) -> Result<(), SimError> {
         match loc {
-            Location::Reg(reg) => self.set_register_8(reg, value),
-            Location::Immediate8(_) => panic!("Cannot set value to immediate"),
-            Location::Immediate16(_) => panic!("Expected byte, got word: {}", value),
-            Location::Mem(addr) => {
-                println!("Setting {} to mem: {:02x}", addr, value);
-                self.memory[*addr as usize] = value
+            Location::Reg(reg) => self.try_set_register_8(reg, value)?,
+            Location::Immediate8(_) | Location::Immediate16(_) => {
+                return Err(SimError::InvalidOperand(
+                    "cannot set value to immediate".to_string(),
+                ))
             }
+            Location::Mem(addr) => self.write_byte(self.data_segment(None), *addr, value),
             Location::Eac(eac) => {
                 let addr = self.get_addr(eac);
-                println!("Setting {} to mem: {:02x}", addr, value);
-                self.memory[addr as usize] = value;
+                self.write_byte(self.data_segment(Some(eac)), addr, value);
             }
         }
+        Ok(())
     }
 
-    pub fn get_value_word(&self, loc: &Location) -> u16 {
+    pub fn get_value_word(&self, loc: &Location) -> Result<u16, SimError> {
         match loc {
-            Location::Reg(reg) => self.get_register_16(reg),
-            Location::Immediate8(value) => *value as u16,
-            Location::Immediate16(value) => *value,
-            Location::Mem(addr) => {
-                let low = self.memory[*addr as usize] as u16;
-                let high = self.memory[*addr as usize + 1] as u16;
-                high << 8 | low
-            }
+            Location::Reg(reg) => self.try_get_register_16(reg),
+            Location::Immediate8(value) => Ok(*value as u16),
+            Location::Immediate16(value) => Ok(*value),
+            Location::Mem(addr) => Ok(self.read_word(self.data_segment(None), *addr)),
             Location::Eac(eac) => {
-                let addr = self.get_addr(eac);
-                let low = self.memory[addr as usize] as u16;
-                let high = self.memory[addr as usize + 1] as u16;
-                high << 8 | low
+                Ok(self.read_word(self.data_segment(Some(eac)), self.get_addr(eac)))
             }
         }
     }
 
-    pub fn set_value_word(&mut self, loc: &Location, value: u16) {
+    pub fn set_value_word(&mut self, loc: &Location, value: u16) -> Result<(), SimError> {
         match loc {
-            Location::Reg(reg) => self.set_register_16(reg, value),
-            Location::Immediate8(_) => panic!("Cannot set value to immediate"),
-            Location::Immediate16(_) => panic!("Expected byte, got word: {}", value),
-            Location::Mem(addr) => {
-                println!("Setting {} to mem: {:04x}", addr, value);
-                self.memory[*addr as usize] = value as u8;
-                self.memory[*addr as usize + 1] = (value >> 8) as u8;
+            Location::Reg(reg) => self.try_set_register_16(reg, value)?,
+            Location::Immediate8(_) | Location::Immediate16(_) => {
+                return Err(SimError::InvalidOperand(
+                    "cannot set value to immediate".to_string(),
+                ))
             }
+            Location::Mem(addr) => self.write_word(self.data_segment(None), *addr, value),
             Location::Eac(eac) => {
                 let addr = self.get_addr(eac);
-                println!("Setting {} to mem: {:04x}", addr, value);
-                self.memory[addr as usize] = value as u8;
-                self.memory[addr as usize + 1] = (value >> 8) as u8;
+                self.write_word(self.data_segment(Some(eac)), addr, value);
             }
         }
+        Ok(())
+    }
+
+    /// Counts a word transfer, unless an observer is the one reading. Its
+    /// bytes are counted as they are read or written.
+    fn word_transfer(&self, addr: u16) {
+        if self.observers.try_borrow_mut().is_ok() {
+            let mut transfers = self.transfers.get();
+            transfers.words += 1;
+            transfers.slow_words += self.cpu.word_penalty(addr) as u32;
+            self.transfers.set(transfers);
+        }
+    }
+
+    fn byte_transfer(&self) {
+        let mut transfers = self.transfers.get();
+        transfers.bytes += 1;
+        self.transfers.set(transfers);
+    }
+
+    /// The segment a data access uses: the override if there is one,
+    /// otherwise ss for addresses based on bp and ds for everything else
+    fn data_segment(&self, eac: Option<&EffectiveAddress>) -> Segment {
+        let stack = eac.is_some_and(|eac| {
+            matches!(
+                eac.mode(),
+                EffectiveAddressMode::Bp | EffectiveAddressMode::BpSi | EffectiveAddressMode::BpDi
+            )
+        });
+        match self.segment_override {
+            Some(segment) => segment,
+            None if stack => Segment::Ss,
+            None => Segment::Ds,
+        }
+    }
+
+    fn read(&self, segment: Segment, offset: u16, size: Size) -> u16 {
+        match size {
+            Size::Byte => self.read_byte(segment, offset) as u16,
+            Size::Word => self.read_word(segment, offset),
+        }
+    }
+
+    fn write(&mut self, segment: Segment, offset: u16, value: u16, size: Size) {
+        match size {
+            Size::Byte => self.write_byte(segment, offset, value as u8),
+            Size::Word => self.write_word(segment, offset, value),
+        }
+    }
+
+    fn read_byte(&self, segment: Segment, offset: u16) -> u8 {
+        let addr = self.physical(segment, offset);
+        let value = self.memory[addr as usize];
+        if let Ok(mut observers) = self.observers.try_borrow_mut() {
+            self.byte_transfer();
+            self.watch(addr, false, value, value);
+            for observer in observers.iter_mut() {
+                observer.memory_read(addr, value);
+            }
+        }
+        value
+    }
+
+    // Words wrap around the end of their segment
+    fn read_word(&self, segment: Segment, offset: u16) -> u16 {
+        self.word_transfer(offset);
+        let low = self.read_byte(segment, offset) as u16;
+        let high = self.read_byte(segment, offset.wrapping_add(1)) as u16;
+        high << 8 | low
+    }
+
+    fn write_byte(&mut self, segment: Segment, offset: u16, value: u8) {
+        let addr = self.physical(segment, offset);
+        let before = std::mem::replace(&mut self.memory[addr as usize], value);
+        self.byte_transfer();
+        self.watch(addr, true, before, value);
+        if before != value {
+            self.loops.memory_changed();
+        }
+        for observer in self.observers.get_mut() {
+            observer.memory_write(addr, before, value);
+        }
+        self.writes.push(MemWrite {
+            addr,
+            before,
+            after: value,
+        });
+    }
+
+    fn write_word(&mut self, segment: Segment, offset: u16, value: u16) {
+        self.word_transfer(offset);
+        self.write_byte(segment, offset, value as u8);
+        self.write_byte(segment, offset.wrapping_add(1), (value >> 8) as u8);
     }
 
     fn get_addr(&self, eac: &EffectiveAddress) -> u16 {
@@ -249,15 +617,17 @@ impl SimState {
 
 impl Decoder for SimState {
     fn has_more(&self) -> bool {
-        self.ip < self.program_size as u16
+        (self.ip as usize) < self.program_size
     }
 
+    // Instruction fetches wrap around the end of the code segment like ip
+    // does
     fn get_byte(&self, offset: usize) -> u8 {
-        self.memory[self.ip as usize + offset]
+        self.memory[self.physical(Segment::Cs, self.ip.wrapping_add(offset as u16)) as usize]
     }
 
     fn add_len(&mut self, len: usize) {
-        self.instr_len += len as u8;
+        self.instr_len += len;
     }
 
     fn next(&mut self) -> bool {
@@ -266,15 +636,66 @@ impl Decoder for SimState {
     }
 
     fn get_instr_len(&self) -> usize {
-        self.instr_len as usize
+        self.instr_len
     }
 
     fn advance(&mut self) {
-        self.ip += self.instr_len as u16;
+        self.ip = self.ip.wrapping_add(self.instr_len as u16);
         self.instr_len = 0;
     }
 }
 
+/// Where `offset` in the segment starting at paragraph `segment` is in the
+/// address space, wrapping around the end of it
+pub fn physical_address(segment: u16, offset: u16) -> u32 {
+    (((segment as u32) << 4) + offset as u32) % MEMORY_SIZE as u32
+}
+
+fn register_16(name: &str) -> Result<(&'static str, usize), SimError> {
+    match name {
+        "ax" => Ok((AX, 0)),
+        "bx" => Ok((BX, 1)),
+        "cx" => Ok((CX, 2)),
+        "dx" => Ok((DX, 3)),
+        "si" => Ok((SI, 4)),
+        "di" => Ok((DI, 5)),
+        "bp" => Ok((BP, 6)),
+        "sp" => Ok((SP, 7)),
+        _ => Err(SimError::InvalidOperand(format!(
+            "unknown register '{}'",
+            name
+        ))),
+    }
+}
+
+fn segment_register(name: &str) -> Option<(&'static str, Segment)> {
+    match name {
+        "es" => Some(("es", Segment::Es)),
+        "cs" => Some(("cs", Segment::Cs)),
+        "ss" => Some(("ss", Segment::Ss)),
+        "ds" => Some(("ds", Segment::Ds)),
+        _ => None,
+    }
+}
+
+/// The 16 bit register `name` is part of and whether it's the high byte
+fn register_8(name: &str) -> Result<(&'static str, bool), SimError> {
+    match name {
+        "al" => Ok((AX, false)),
+        "ah" => Ok((AX, true)),
+        "bl" => Ok((BX, false)),
+        "bh" => Ok((BX, true)),
+        "cl" => Ok((CX, false)),
+        "ch" => Ok((CX, true)),
+        "dl" => Ok((DX, false)),
+        "dh" => Ok((DX, true)),
+        _ => Err(SimError::InvalidOperand(format!(
+            "unknown register '{}'",
+            name
+        ))),
+    }
+}
+
 pub fn is_byte(reg: &str) -> bool {
     matches!(reg, "al" | "ah" | "bl" | "bh" | "cl" | "ch" | "dl" | "dh")
 }
@@ -294,7 +715,7 @@ impl Display for SimState {
 
 #[cfg(test)]
 mod test {
-    use crate::sim::SimState;
+    use crate::{decoder::prefix::Segment, sim::SimState};
 
     #[test]
     fn test_register_16() {
@@ -321,21 +742,21 @@ mod test {
     #[test]
     fn test_mov_imm_to_reg_lower() {
         let mut state = SimState::new(vec![0b10110011, 0b1100100]);
-        state.run();
+        state.run().unwrap();
         assert_eq!(state.get_register_8("bl"), 100);
     }
 
     #[test]
     fn test_mov_imm_to_reg_higher() {
         let mut state = SimState::new(vec![0b10110111, 0b1100100]);
-        state.run();
+        state.run().unwrap();
         assert_eq!(state.get_register_8("bh"), 100);
     }
 
     #[test]
     fn test_mov_imm_to_reg_16bit() {
         let mut state = SimState::new(vec![0b10111011, 0b1100100, 0b0]);
-        state.run();
+        state.run().unwrap();
         assert_eq!(state.get_register_16("bx"), 100);
     }
 
@@ -343,7 +764,7 @@ mod test {
     fn test_mov_reg_high_to_reg_low() {
         let mut state = SimState::new(vec![0b10001000, 0b11010101]);
         state.set_register_8("dl", 100);
-        state.run();
+        state.run().unwrap();
         assert_eq!(state.get_register_8("ch"), 100);
     }
 
@@ -351,7 +772,7 @@ mod test {
     fn test_mov_reg_low_to_reg_high() {
         let mut state = SimState::new(vec![0b10001000, 0b11101010]);
         state.set_register_8("ch", 100);
-        state.run();
+        state.run().unwrap();
         assert_eq!(state.get_register_8("dl"), 100);
     }
 
@@ -359,22 +780,108 @@ mod test {
     fn mov_reg_to_reg() {
         let mut state = SimState::new(vec![0b10001001, 0b11000001]);
         state.set_register_16("ax", 1234);
-        state.run();
+        state.run().unwrap();
         assert_eq!(state.get_register_16("cx"), 1234);
     }
 
+    #[test]
+    fn test_xchg() {
+        // xchg ax, cx; xchg [bx], dl
+        let mut state = SimState::new(vec![0b10010001, 0b10000110, 0b10111]);
+        state.set_register_16("ax", 1);
+        state.set_register_16("cx", 2);
+        state.set_register_16("bx", 0x100);
+        state.set_register_8("dl", 3);
+        state.memory[0x100] = 4;
+        state.run().unwrap();
+
+        assert_eq!(state.get_register_16("ax"), 2);
+        assert_eq!(state.get_register_16("cx"), 1);
+        assert_eq!(state.get_register_8("dl"), 4);
+        assert_eq!(state.memory[0x100], 3);
+    }
+
+    #[test]
+    fn test_ip_wraps() {
+        let mut state = SimState::new(vec![0x90; 0x10000]);
+        state.set_ip(0xFFFF);
+        state.step().unwrap();
+        assert_eq!(state.ip(), 0);
+
+        // es repeated 300 times, then nop
+        let mut program = vec![0x26; 300];
+        program.push(0x90);
+        let mut state = SimState::new(program);
+        state.step().unwrap();
+        assert_eq!(state.ip(), 301);
+    }
+
+    #[test]
+    fn test_run_trace_removes_printer() {
+        let mut state = SimState::new(vec![0b10110011, 0b1100100]);
+        state.run_trace().unwrap();
+        assert_eq!(state.observers.borrow().len(), 0);
+    }
+
+    #[test]
+    fn test_write_memory() {
+        let mut state = SimState::new(vec![]);
+        state.set_segment(Segment::Ds, 0xF800);
+        state.set_memory(0xF8000, &[1, 2]);
+        state.set_memory(0x7FFF, &[3]);
+        state.set_memory(0x8000, &[4]);
+        let file = std::env::temp_dir().join(format!("write_memory_{}.bin", std::process::id()));
+        state.write_memory(&file);
+        let bytes = std::fs::read(&file).unwrap();
+        std::fs::remove_file(&file).unwrap();
+
+        // ds:0 to ds:FFFF, wrapping from the top of memory to 0
+        assert_eq!(bytes.len(), 0x10000);
+        assert_eq!(bytes[..2], [1, 2]);
+        assert_eq!(bytes[0xFFFF], 3);
+    }
+
+    #[test]
+    fn test_segments() {
+        // mov [0x10], ax; mov [bp], ax; mov es:[0x10], ax; stosb; call $+3
+        let mut state = SimState::new(vec![
+            0xA3, 0x10, 0x00, 0x89, 0x46, 0x00, 0x26, 0xA3, 0x10, 0x00, 0xAA, 0xE8, 0x00, 0x00,
+        ]);
+        state.set_register_16("ds", 0x100);
+        state.set_register_16("ss", 0x200);
+        state.set_register_16("es", 0x300);
+        state.set_register_16("ax", 0x1234);
+        state.set_register_16("di", 0x20);
+        state.set_register_16("sp", 0x40);
+        state.run().unwrap();
+
+        assert_eq!(state.memory[0x1010..0x1012], [0x34, 0x12]);
+        assert_eq!(state.memory[0x2000..0x2002], [0x34, 0x12]);
+        assert_eq!(state.memory[0x3010..0x3012], [0x34, 0x12]);
+        assert_eq!(state.memory[0x3020], 0x34);
+        assert_eq!(state.memory[0x203E..0x2040], [0x0E, 0x00]);
+        assert_eq!(state.physical(Segment::Es, 0xFFFF), 0x12FFF);
+        state.set_register_16("cs", 0xFFFF);
+        assert_eq!(state.physical(Segment::Cs, 0x20), 0x10);
+    }
+
+    #[test]
+    fn test_push_pop_wraps() {
+        let mut state = SimState::new(vec![]);
+        state.push_word(0x1234);
+        assert_eq!(state.get_register_16("sp"), 0xFFFE);
+        assert_eq!(state.memory[0xFFFE], 0x34);
+        assert_eq!(state.memory[0xFFFF], 0x12);
+        assert_eq!(state.pop_word(), 0x1234);
+        assert_eq!(state.get_register_16("sp"), 0);
+    }
+
     #[test]
     fn test_state_display() {
-        let state = SimState {
-            registers: [
-                0x1234, 0x5678, 0x9ABC, 0xDEF0, 0x1357, 0x2468, 0xACE0, 0xBEEF,
-            ],
-            flags: Default::default(),
-            memory: [0; 0xFFFF],
-            instr_len: 0,
-            program_size: 0,
-            ip: 0,
-        };
+        let mut state = SimState::new(vec![]);
+        state.registers = [
+            0x1234, 0x5678, 0x9ABC, 0xDEF0, 0x1357, 0x2468, 0xACE0, 0xBEEF,
+        ];
         let expected =
             "ax: 1234\nbx: 5678\ncx: 9abc\ndx: def0\nsp: beef\nbp: ace0\nsi: 1357\ndi: 2468\n";
         assert_eq!(format!("{}", state), expected);
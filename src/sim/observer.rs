@@ -0,0 +1,142 @@
+use crate::decoder::instr::Instr;
+
+use super::{trace::TraceStep, SimState};
+
+/// Hooks called as the simulator runs, so tools can watch execution without
+/// changing it. Every method does nothing by default.
+pub trait Observer {
+    /// Called once `instr` has been decoded at `state`'s ip, before it runs
+    fn before_instr(&mut self, _state: &SimState, _instr: &Instr) {}
+
+    /// Called once the instruction has run, with everything it changed
+    fn after_instr(&mut self, _state: &SimState, _step: &TraceStep) {}
+
+    /// Called for every write to a 16 bit register, including through its
+    /// 8 bit halves
+    fn register_write(&mut self, _reg: &'static str, _before: u16, _after: u16) {}
+
+    /// Called for every byte of data read from memory, but not for
+    /// instruction fetches or reads made by observers. Addresses are
+    /// physical.
+    fn memory_read(&mut self, _addr: u32, _value: u8) {}
+
+    fn memory_write(&mut self, _addr: u32, _before: u8, _after: u8) {}
+
+    /// Called for every int, before it runs
+    fn interrupt(&mut self, _vector: u8) {}
+}
+
+/// Prints a reference-format line for every instruction
+pub struct TracePrinter;
+
+impl Observer for TracePrinter {
+    fn after_instr(&mut self, _state: &SimState, step: &TraceStep) {
+        println!("{}", step);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{cell::RefCell, rc::Rc};
+
+    use crate::{
+        decoder::{instr::Instr, loc::Location},
+        sim::{observer::Observer, step::StopReason, trace::TraceStep, SimState},
+    };
+
+    struct Recorder(Rc<RefCell<Vec<String>>>);
+
+    impl Observer for Recorder {
+        fn before_instr(&mut self, state: &SimState, instr: &Instr) {
+            // Reads made from inside a hook aren't reported back
+            let first = state.get_value_word(&Location::Mem(0));
+            self.0
+                .borrow_mut()
+                .push(format!("before {} {:#x}", instr, first.unwrap()));
+        }
+
+        fn after_instr(&mut self, _state: &SimState, step: &TraceStep) {
+            self.0
+                .borrow_mut()
+                .push(format!("after {:#x}", step.ip_after));
+        }
+
+        fn register_write(&mut self, reg: &'static str, before: u16, after: u16) {
+            self.0
+                .borrow_mut()
+                .push(format!("{} {:#x}->{:#x}", reg, before, after));
+        }
+
+        fn memory_read(&mut self, addr: u32, value: u8) {
+            self.0
+                .borrow_mut()
+                .push(format!("read [{:#x}] {:#x}", addr, value));
+        }
+
+        fn memory_write(&mut self, addr: u32, before: u8, after: u8) {
+            self.0
+                .borrow_mut()
+                .push(format!("write [{:#x}] {:#x}->{:#x}", addr, before, after));
+        }
+
+        fn interrupt(&mut self, vector: u8) {
+            self.0.borrow_mut().push(format!("int {:#x}", vector));
+        }
+    }
+
+    #[test]
+    fn test_observer_callbacks() {
+        let events = Rc::new(RefCell::new(vec![]));
+        // mov ch, 1; mov [1000], cx; mov dx, [1000]
+        let mut state = SimState::new(vec![
+            0b10110101, 0b1, 0b10001001, 0b1110, 0b11101000, 0b11, 0b10001011, 0b10110, 0b11101000,
+            0b11,
+        ]);
+        state.add_observer(Box::new(Recorder(events.clone())));
+        state.run().unwrap();
+
+        assert_eq!(
+            *events.borrow(),
+            vec![
+                "before mov ch, 1 0x1b5",
+                "cx 0x0->0x100",
+                "after 0x2",
+                "before mov [1000], cx 0x1b5",
+                "write [0x3e8] 0x0->0x0",
+                "write [0x3e9] 0x0->0x1",
+                "after 0x6",
+                "before mov dx, [1000] 0x1b5",
+                "read [0x3e8] 0x0",
+                "read [0x3e9] 0x1",
+                "dx 0x0->0x100",
+                "after 0xa",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_observer_interrupt() {
+        let events = Rc::new(RefCell::new(vec![]));
+        // int 20h
+        let mut state = SimState::new(vec![0b11001101, 0x20]);
+        state.add_observer(Box::new(Recorder(events.clone())));
+        assert_eq!(state.run(), Ok(StopReason::Exit));
+
+        assert_eq!(
+            *events.borrow(),
+            vec!["before int 32 0x20cd", "int 0x20", "after 0x2"]
+        );
+    }
+
+    #[test]
+    fn test_observer_segment_write() {
+        let events = Rc::new(RefCell::new(vec![]));
+        let mut state = SimState::new(vec![]);
+        state.add_observer(Box::new(Recorder(events.clone())));
+        // Segment registers are only set from outside, like by a debugger
+        state.set_register_16("ds", 0x1234);
+        state.set_register_16("ax", 0x5678);
+
+        assert_eq!(*events.borrow(), vec!["ds 0x0->0x1234", "ax 0x0->0x5678"]);
+    }
+}
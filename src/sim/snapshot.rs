@@ -0,0 +1,211 @@
+use std::fmt::Display;
+
+use super::{
+    flags::Flags, limits::LoopDetector, register_16, trace::REGISTERS, SimState, SEGMENT_REGISTERS,
+};
+
+const MAGIC: &[u8; 4] = b"R86S";
+
+/// Bumped whenever the layout changes, older snapshots are rejected
+pub const SNAPSHOT_VERSION: u16 = 2;
+
+#[derive(Debug, PartialEq)]
+pub enum SnapshotError {
+    /// The file doesn't start with the snapshot magic bytes
+    NotASnapshot,
+    UnsupportedVersion(u16),
+    /// The file ends before the whole machine state was read
+    Truncated,
+    /// The loaded program is recorded as bigger than its 64K segment
+    InvalidProgramSize(u32),
+}
+
+impl Display for SnapshotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SnapshotError::NotASnapshot => write!(f, "not a snapshot"),
+            SnapshotError::UnsupportedVersion(version) => write!(
+                f,
+                "snapshot version {} isn't supported, expected {}",
+                version, SNAPSHOT_VERSION
+            ),
+            SnapshotError::Truncated => write!(f, "snapshot is truncated"),
+            SnapshotError::InvalidProgramSize(size) => {
+                write!(
+                    f,
+                    "snapshot program size {:#x} is bigger than a segment",
+                    size
+                )
+            }
+        }
+    }
+}
+
+/// Reads the little endian fields of a snapshot in order
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], SnapshotError> {
+        if self.bytes.len() < len {
+            return Err(SnapshotError::Truncated);
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, SnapshotError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, SnapshotError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, SnapshotError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, SnapshotError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+impl SimState {
+    /// The machine state as a versioned binary snapshot: the magic `R86S`,
+    /// the version, then little endian ax, bx, cx, dx, sp, bp, si, di, cs,
+    /// ds, es, ss, ip, flags, whether halted, whether the program exited,
+    /// the program size, steps, cycles and all 1MB of memory. There are no
+    /// devices yet.
+    ///
+    /// Breakpoints, watchpoints, limits, observers and the timing model are
+    /// how the simulator is being run rather than machine state, so aren't
+    /// saved.
+    pub fn snapshot(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(SNAPSHOT_VERSION.to_le_bytes());
+        for reg in REGISTERS.iter().chain(&SEGMENT_REGISTERS) {
+            bytes.extend(self.get_register_16(reg).to_le_bytes());
+        }
+        bytes.extend(self.ip.to_le_bytes());
+        bytes.extend(self.flags.bits().to_le_bytes());
+        bytes.push(self.halted as u8);
+        bytes.push(self.exited as u8);
+        bytes.extend((self.program_size as u32).to_le_bytes());
+        bytes.extend(self.steps.to_le_bytes());
+        bytes.extend(self.cycles.to_le_bytes());
+        bytes.extend_from_slice(&self.memory);
+        bytes
+    }
+
+    /// Replaces the machine state with a [SimState::snapshot], leaving it
+    /// unchanged if the snapshot can't be read. The history to step back
+    /// through is forgotten.
+    pub fn restore(&mut self, snapshot: &[u8]) -> Result<(), SnapshotError> {
+        let mut reader = Reader { bytes: snapshot };
+        if reader.take(MAGIC.len()).ok() != Some(MAGIC.as_slice()) {
+            return Err(SnapshotError::NotASnapshot);
+        }
+        let version = reader.u16()?;
+        if version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+        let mut registers = self.registers;
+        for reg in REGISTERS {
+            let (_, index) = register_16(reg).unwrap();
+            registers[index] = reader.u16()?;
+        }
+        let mut segments = [0; 4];
+        for segment in &mut segments {
+            *segment = reader.u16()?;
+        }
+        let ip = reader.u16()?;
+        let flags = Flags::from_bits(reader.u16()?);
+        let halted = reader.u8()? != 0;
+        let exited = reader.u8()? != 0;
+        let program_size = reader.u32()?;
+        if program_size > 0x10000 {
+            return Err(SnapshotError::InvalidProgramSize(program_size));
+        }
+        let steps = reader.u64()?;
+        let cycles = reader.u64()?;
+        let memory = reader.take(self.memory.len())?;
+
+        self.registers = registers;
+        for (reg, value) in SEGMENT_REGISTERS.iter().zip(segments) {
+            self.set_register_16(reg, value);
+        }
+        self.set_ip(ip);
+        self.flags = flags;
+        self.halted = halted;
+        self.exited = exited;
+        self.program_size = program_size as usize;
+        self.steps = steps;
+        self.cycles = cycles;
+        self.memory.copy_from_slice(memory);
+        self.instr_len = 0;
+        self.loops = LoopDetector::default();
+        self.history.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sim::{
+        snapshot::{SnapshotError, SNAPSHOT_VERSION},
+        SimState,
+    };
+
+    // mov cx, 3; mov [0x100], cx; sub cx, 1; jne $-7; hlt
+    const PROGRAM: [u8; 13] = [
+        0xB9, 0x03, 0x00, 0x89, 0x0E, 0x00, 0x01, 0x83, 0xE9, 0x01, 0x75, 0xF7, 0xF4,
+    ];
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let mut whole = SimState::new(PROGRAM.to_vec());
+        whole.run().unwrap();
+
+        let mut first = SimState::new(PROGRAM.to_vec());
+        for _ in 0..5 {
+            first.step().unwrap();
+        }
+        let snapshot = first.snapshot();
+        let mut resumed = SimState::new(vec![]);
+        resumed.restore(&snapshot).unwrap();
+        assert_eq!(resumed.snapshot(), snapshot);
+        resumed.run().unwrap();
+
+        assert_eq!(resumed.snapshot(), whole.snapshot());
+        assert_eq!(resumed.steps(), whole.steps());
+        assert_eq!(resumed.memory()[0x100], 1);
+    }
+
+    #[test]
+    fn test_snapshot_errors() {
+        let mut state = SimState::new(PROGRAM.to_vec());
+        let snapshot = state.snapshot();
+
+        assert_eq!(state.restore(b"MZ"), Err(SnapshotError::NotASnapshot));
+        assert_eq!(
+            state.restore(&snapshot[..snapshot.len() - 1]),
+            Err(SnapshotError::Truncated)
+        );
+        let mut newer = snapshot.clone();
+        newer[4..6].copy_from_slice(&(SNAPSHOT_VERSION + 1).to_le_bytes());
+        assert_eq!(
+            state.restore(&newer),
+            Err(SnapshotError::UnsupportedVersion(SNAPSHOT_VERSION + 1))
+        );
+        let mut oversized = snapshot.clone();
+        oversized[36..40].copy_from_slice(&0x10001u32.to_le_bytes());
+        assert_eq!(
+            state.restore(&oversized).unwrap_err().to_string(),
+            "snapshot program size 0x10001 is bigger than a segment"
+        );
+        assert_eq!(state.snapshot(), snapshot);
+    }
+}
@@ -0,0 +1,231 @@
+use std::fmt::Display;
+
+use crate::decoder::state::Decoder;
+
+use super::{condition::Condition, error::SimError, trace::TraceStep, watch::WatchHit, SimState};
+
+/// Why the simulator stopped without an error
+#[derive(Debug, PartialEq, Clone)]
+pub enum StopReason {
+    /// A hlt was executed
+    Halt,
+    /// The program terminated itself with int 20h
+    Exit,
+    /// ip has run past the end of the loaded program
+    EndOfProgram,
+    /// ip reached an address with a breakpoint set, and its condition held
+    Breakpoint(u16),
+    /// The instruction just executed touched watched memory
+    Watchpoint(WatchHit),
+    /// The maximum number of instructions were executed
+    StepLimit(u64),
+    CycleLimit(u64),
+    /// The whole machine state repeated with ip at this address, so the
+    /// program can never stop
+    Loop(u16),
+    /// Stepping back ran out of recorded history
+    StartOfHistory,
+}
+
+impl StopReason {
+    /// Whether the program was stopped by a limit rather than running to
+    /// the end
+    pub fn is_limit(&self) -> bool {
+        matches!(
+            self,
+            StopReason::StepLimit(_) | StopReason::CycleLimit(_) | StopReason::Loop(_)
+        )
+    }
+}
+
+impl Display for StopReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StopReason::Halt => write!(f, "halted"),
+            StopReason::Exit => write!(f, "program exited"),
+            StopReason::EndOfProgram => write!(f, "end of program"),
+            StopReason::Breakpoint(addr) => write!(f, "breakpoint at {:#x}", addr),
+            StopReason::Watchpoint(hit) => write!(f, "{}", hit),
+            StopReason::StepLimit(max) => write!(f, "step limit of {} reached", max),
+            StopReason::CycleLimit(max) => write!(f, "cycle limit of {} reached", max),
+            StopReason::Loop(addr) => write!(f, "infinite loop at {:#x}", addr),
+            StopReason::StartOfHistory => write!(f, "start of recorded history"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Breakpoint {
+    pub addr: u16,
+    /// Only stop if this holds when ip reaches `addr`
+    pub condition: Option<Condition>,
+}
+
+/// Written as the debugger command that sets it, e.g.
+/// `break 0x0012 if cx == 64`
+impl Display for Breakpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "break {:#06x}", self.addr)?;
+        if let Some(condition) = &self.condition {
+            write!(f, " if {}", condition)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct StepResult {
+    /// The instruction that ran and what it changed, if one ran
+    pub executed: Option<TraceStep>,
+    /// Set if the simulator stopped, either instead of running an
+    /// instruction or straight after it
+    pub stop: Option<StopReason>,
+}
+
+impl SimState {
+    /// Decodes and executes a single instruction
+    pub fn step(&mut self) -> Result<StepResult, SimError> {
+        if self.halted {
+            return Err(SimError::Halted);
+        }
+        if self.exited {
+            return Err(SimError::Exited);
+        }
+        if !self.has_more() {
+            return Ok(StepResult {
+                executed: None,
+                stop: Some(StopReason::EndOfProgram),
+            });
+        }
+        let step = self.trace_step()?;
+        self.steps += 1;
+        let stop = if let Some(hit) = self.watch_hit.take() {
+            Some(StopReason::Watchpoint(WatchHit {
+                instr_addr: step.addr,
+                ..hit
+            }))
+        } else if self.halted {
+            Some(StopReason::Halt)
+        } else if self.exited {
+            Some(StopReason::Exit)
+        } else if !self.has_more() {
+            Some(StopReason::EndOfProgram)
+        } else {
+            self.check_limits().or_else(|| {
+                self.breakpoint_hit()
+                    .then_some(StopReason::Breakpoint(self.ip))
+            })
+        };
+        Ok(StepResult {
+            executed: Some(step),
+            stop,
+        })
+    }
+
+    /// Whether there's a breakpoint at ip whose condition holds
+    pub(crate) fn breakpoint_hit(&self) -> bool {
+        self.breakpoints.iter().any(|breakpoint| {
+            breakpoint.addr == self.ip
+                && breakpoint
+                    .condition
+                    .as_ref()
+                    .is_none_or(|condition| condition.holds(self))
+        })
+    }
+
+    /// Stops execution when ip reaches `addr`, but not if it's already there
+    pub fn add_breakpoint(&mut self, addr: u16) {
+        self.add_conditional_breakpoint(addr, None);
+    }
+
+    /// Replaces any breakpoint already at `addr`
+    pub fn add_conditional_breakpoint(&mut self, addr: u16, condition: Option<Condition>) {
+        self.remove_breakpoint(addr);
+        self.breakpoints.push(Breakpoint { addr, condition });
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    /// Returns whether there was a breakpoint at `addr`
+    pub fn remove_breakpoint(&mut self, addr: u16) -> bool {
+        let len = self.breakpoints.len();
+        self.breakpoints
+            .retain(|breakpoint| breakpoint.addr != addr);
+        self.breakpoints.len() != len
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        decoder::{instr::Instr, mov::CX},
+        sim::{
+            error::SimError,
+            step::{StepResult, StopReason},
+            SimState,
+        },
+    };
+
+    #[test]
+    fn test_step() {
+        // mov cx, 1; sub cx, 1; jne $-3; je $+2
+        let mut state = SimState::new(vec![
+            0b10111001, 0b1, 0b0, 0b10000011, 0b11101001, 0b1, 0b1110101, 0b11111011, 0b1110100,
+            0b0,
+        ]);
+        let first = state.step().unwrap();
+        assert_eq!(first.stop, None);
+        let first = first.executed.unwrap();
+        assert_eq!(first.addr, 0);
+        assert_eq!(first.registers[0].after, 1);
+        assert_eq!(first.branch, None);
+
+        state.step().unwrap();
+        let jne = state.step().unwrap().executed.unwrap();
+        assert_eq!(jne.instr, Instr::Jne(-5));
+        assert_eq!(jne.branch, Some(false));
+
+        let last = state.step().unwrap();
+        assert_eq!(last.executed.unwrap().branch, Some(true));
+        assert_eq!(last.stop, Some(StopReason::EndOfProgram));
+        assert_eq!(
+            state.step(),
+            Ok(StepResult {
+                executed: None,
+                stop: Some(StopReason::EndOfProgram)
+            })
+        );
+    }
+
+    #[test]
+    fn test_step_halt_and_breakpoint() {
+        // mov cx, 1; hlt; mov cx, 2
+        let mut state = SimState::new(vec![0b10111001, 0b1, 0b0, 0b11110100, 0b10111001, 0b10, 0]);
+        state.add_breakpoint(3);
+        assert_eq!(state.run(), Ok(StopReason::Breakpoint(3)));
+        assert_eq!(state.run(), Ok(StopReason::Halt));
+        assert_eq!(state.step(), Err(SimError::Halted));
+        assert_eq!(state.get_register_16(CX), 1);
+        assert!(state.remove_breakpoint(3));
+        assert!(!state.remove_breakpoint(3));
+    }
+
+    #[test]
+    fn test_conditional_breakpoint() {
+        // mov cx, 3; sub cx, 1; jne $-3
+        let mut state = SimState::new(vec![
+            0b10111001, 0b11, 0b0, 0b10000011, 0b11101001, 0b1, 0b1110101, 0b11111011,
+        ]);
+        state.add_conditional_breakpoint(3, Some("cx == 1 && !ZF".parse().unwrap()));
+
+        assert_eq!(state.run(), Ok(StopReason::Breakpoint(3)));
+        assert_eq!(state.get_register_16(CX), 1);
+        assert_eq!(
+            state.breakpoints()[0].to_string(),
+            "break 0x0003 if cx == 1 && !ZF"
+        );
+        assert_eq!(state.run(), Ok(StopReason::EndOfProgram));
+    }
+}
@@ -0,0 +1,174 @@
+use crate::decoder::{
+    loc::{Location, Size},
+    mov::{AL, AX, CX, DI, SI},
+    op::OpKind,
+    prefix::{Rep, Segment},
+    string::{StringInstr, StringKind},
+};
+
+use super::{error::SimError, SimState};
+
+impl SimState {
+    /// Repeats `string` cx times, stopping early for cmps/scas once the
+    /// comparison no longer matches the prefix
+    pub fn execute_rep(&mut self, rep: Rep, string: &StringInstr) -> Result<(), SimError> {
+        while self.get_register_16(CX) != 0 {
+            self.execute_string(string)?;
+            let cx = self.get_register_16(CX).wrapping_sub(1);
+            self.set_register_16(CX, cx);
+            if string.kind.compares() && self.flags.zero != (rep == Rep::Repe) {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    // The direction flag isn't modelled, so the pointers always count up
+    pub fn execute_string(&mut self, string: &StringInstr) -> Result<(), SimError> {
+        let (acc, step) = match string.size {
+            Size::Byte => (AL, 1),
+            Size::Word => (AX, 2),
+        };
+        // The source can be overridden but the destination is always es:di
+        let source = self.data_segment(None);
+        let si = self.get_register_16(SI);
+        let di = self.get_register_16(DI);
+        let acc = Location::Reg(acc);
+        match string.kind {
+            StringKind::Movs => {
+                let value = self.read(source, si, string.size);
+                self.write(Segment::Es, di, value, string.size)
+            }
+            StringKind::Cmps => {
+                let first = self.read(source, si, string.size);
+                let second = self.read(Segment::Es, di, string.size);
+                self.compare(first, second, string.size)
+            }
+            StringKind::Stos => {
+                let value = self.get_value(&acc, string.size)?;
+                self.write(Segment::Es, di, value, string.size)
+            }
+            StringKind::Lods => {
+                let value = self.read(source, si, string.size);
+                self.set_value(&acc, value, string.size)?
+            }
+            StringKind::Scas => {
+                let first = self.get_value(&acc, string.size)?;
+                let second = self.read(Segment::Es, di, string.size);
+                self.compare(first, second, string.size)
+            }
+        }
+        if matches!(
+            string.kind,
+            StringKind::Movs | StringKind::Cmps | StringKind::Lods
+        ) {
+            let si = self.get_register_16(SI).wrapping_add(step);
+            self.set_register_16(SI, si);
+        }
+        if matches!(
+            string.kind,
+            StringKind::Movs | StringKind::Cmps | StringKind::Stos | StringKind::Scas
+        ) {
+            let di = self.get_register_16(DI).wrapping_add(step);
+            self.set_register_16(DI, di);
+        }
+        Ok(())
+    }
+
+    fn compare(&mut self, first: u16, second: u16, size: Size) {
+        match size {
+            Size::Byte => {
+                OpKind::Cmp.execute_byte(self, first as u8, second as u8);
+            }
+            Size::Word => {
+                OpKind::Cmp.execute_word(self, first, second);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        decoder::{
+            mov::{AL, CX, DI, SI},
+            prefix::Segment,
+        },
+        sim::SimState,
+    };
+
+    /// ds at 0x10000 and es at 0x20000, so the source and destination can't
+    /// be mixed up
+    fn segmented(program: Vec<u8>) -> SimState {
+        let mut state = SimState::new(program);
+        state.set_segment(Segment::Ds, 0x1000);
+        state.set_segment(Segment::Es, 0x2000);
+        state
+    }
+
+    #[test]
+    fn test_rep_movsb() {
+        // rep movsb
+        let mut state = segmented(vec![0b11110011, 0b10100100]);
+        state.memory[0x10100..0x10104].copy_from_slice(&[1, 2, 3, 4]);
+        state.set_register_16(SI, 0x100);
+        state.set_register_16(DI, 0x200);
+        state.set_register_16(CX, 3);
+        state.run().unwrap();
+
+        assert_eq!(state.memory[0x20200..0x20204], [1, 2, 3, 0]);
+        assert_eq!(state.memory[0x10200..0x10204], [0, 0, 0, 0]);
+        assert_eq!(state.get_register_16(CX), 0);
+        assert_eq!(state.get_register_16(SI), 0x103);
+        assert_eq!(state.get_register_16(DI), 0x203);
+    }
+
+    #[test]
+    fn test_repne_scasw() {
+        // repne scasw looking for 0x1234, which is only in es
+        let mut state = segmented(vec![0b11110010, 0b10101111]);
+        state.memory[0x20100..0x20106].copy_from_slice(&[0, 0, 0x34, 0x12, 0, 0]);
+        state.memory[0x10100..0x10102].copy_from_slice(&[0x34, 0x12]);
+        state.set_register_16("ax", 0x1234);
+        state.set_register_16(DI, 0x100);
+        state.set_register_16(CX, 10);
+        state.run().unwrap();
+
+        assert!(state.flags.zero);
+        assert_eq!(state.get_register_16(DI), 0x104);
+        assert_eq!(state.get_register_16(CX), 8);
+    }
+
+    #[test]
+    fn test_stos_lods() {
+        // stosw; lodsb
+        let mut state = segmented(vec![0b10101011, 0b10101100]);
+        state.memory[0x10101] = 0x77;
+        state.set_register_16("ax", 0xABCD);
+        state.set_register_16(DI, 0x100);
+        state.set_register_16(SI, 0x101);
+        state.run().unwrap();
+
+        assert_eq!(state.memory[0x20100..0x20102], [0xCD, 0xAB]);
+        assert_eq!(state.get_register_16("ax"), 0xAB77);
+        assert_eq!(state.get_register_16(DI), 0x102);
+        assert_eq!(state.get_register_16(SI), 0x102);
+    }
+
+    #[test]
+    fn test_string_segment_overrides() {
+        // cs movsb; ds stosb
+        let mut state = segmented(vec![0b00101110, 0b10100100, 0b00111110, 0b10101010]);
+        state.memory[0x50] = 0x99;
+        state.memory[0x10050] = 0x11;
+        state.set_register_16(SI, 0x50);
+        state.set_register_16(DI, 0x10);
+        state.set_register_8(AL, 0x42);
+        state.run().unwrap();
+
+        // The source comes from the override, the destination is always es
+        assert_eq!(state.memory[0x20010..0x20012], [0x99, 0x42]);
+        assert_eq!(state.memory[0x10010..0x10012], [0, 0]);
+        assert_eq!(state.get_register_16(DI), 0x12);
+    }
+}
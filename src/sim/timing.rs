@@ -0,0 +1,284 @@
+use std::str::FromStr;
+
+use crate::decoder::{
+    common::Encoding,
+    instr::Instr,
+    loc::{eac::EffectiveAddress, eac_mode::EffectiveAddressMode, Location},
+    op::OpKind,
+    string::StringKind,
+};
+
+/// The processor model, which changes how long word transfers take and how
+/// the instruction queue fills
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Cpu {
+    /// 16 bit bus, words at odd addresses take two bus cycles
+    #[default]
+    I8086,
+    /// 8 bit bus, every word takes two bus cycles
+    I8088,
+}
+
+impl Cpu {
+    /// Whether a word transfer at `addr` takes an extra 4 clocks
+    pub fn word_penalty(&self, addr: u16) -> bool {
+        match self {
+            Cpu::I8086 => addr & 1 == 1,
+            Cpu::I8088 => true,
+        }
+    }
+
+    /// Bytes the instruction queue holds
+    pub fn queue_size(&self) -> u32 {
+        match self {
+            Cpu::I8086 => 6,
+            Cpu::I8088 => 4,
+        }
+    }
+
+    /// Bytes fetched into the queue per bus cycle
+    pub fn fetch_width(&self) -> u32 {
+        match self {
+            Cpu::I8086 => 2,
+            Cpu::I8088 => 1,
+        }
+    }
+}
+
+impl FromStr for Cpu {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "8086" => Ok(Cpu::I8086),
+            "8088" => Ok(Cpu::I8088),
+            _ => Err(format!("unknown cpu '{}', expected 8086 or 8088", s)),
+        }
+    }
+}
+
+/// An instruction's estimated clock count from the 8086 timing tables
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Clocks {
+    pub base: u32,
+    /// Effective address calculation, including any segment override
+    pub ea: u32,
+    /// Extra bus cycles for word transfers, see [Cpu]
+    pub penalty: u32,
+    /// Time spent waiting for the instruction to be fetched or for the bus,
+    /// only counted in [TimingMode::Prefetch](super::bus::TimingMode::Prefetch)
+    pub prefetch: u32,
+}
+
+impl Clocks {
+    fn new(base: u32, ea: u32) -> Self {
+        Self {
+            base,
+            ea,
+            penalty: 0,
+            prefetch: 0,
+        }
+    }
+
+    pub fn total(&self) -> u32 {
+        self.base + self.ea + self.penalty + self.prefetch
+    }
+}
+
+/// Estimates the clocks `instr` took, where `taken` is whether a conditional
+/// jump was taken and `reps` how many times a rep prefix repeated
+pub fn estimate_clocks(instr: &Instr, taken: Option<bool>, reps: u16) -> Clocks {
+    match instr {
+        Instr::Mov(mov) => match (&mov.dest, &mov.src) {
+            _ if mov.encoding == Encoding::Acc => Clocks::new(10, 0),
+            (Location::Reg(_), Location::Reg(_)) => Clocks::new(2, 0),
+            (Location::Reg(_), Location::Immediate8(_) | Location::Immediate16(_)) => {
+                Clocks::new(4, 0)
+            }
+            (Location::Reg(_), mem) => Clocks::new(8, ea_clocks(mem)),
+            (mem, Location::Reg(_)) => Clocks::new(9, ea_clocks(mem)),
+            (mem, _) => Clocks::new(10, ea_clocks(mem)),
+        },
+        Instr::Op(op) => {
+            let cmp = op.kind == OpKind::Cmp;
+            match (&op.dest, &op.src) {
+                (Location::Reg(_), Location::Reg(_)) => Clocks::new(3, 0),
+                (Location::Reg(_), Location::Immediate8(_) | Location::Immediate16(_)) => {
+                    Clocks::new(4, 0)
+                }
+                (Location::Reg(_), mem) => Clocks::new(9, ea_clocks(mem)),
+                (mem, Location::Reg(_)) => Clocks::new(if cmp { 9 } else { 16 }, ea_clocks(mem)),
+                (mem, _) => Clocks::new(if cmp { 10 } else { 17 }, ea_clocks(mem)),
+            }
+        }
+        Instr::Je(_) | Instr::Jne(_) if taken == Some(true) => Clocks::new(16, 0),
+        Instr::Je(_) | Instr::Jne(_) => Clocks::new(4, 0),
+        Instr::Jmp(_) | Instr::JmpNear(_) => Clocks::new(15, 0),
+        Instr::Call(_) => Clocks::new(19, 0),
+        Instr::Ret => Clocks::new(8, 0),
+        Instr::Hlt => Clocks::new(2, 0),
+        Instr::Int(_) => Clocks::new(51, 0),
+        Instr::Nop => Clocks::new(3, 0),
+        Instr::Xchg(xchg) => match (&xchg.dest, &xchg.src) {
+            _ if xchg.encoding == Encoding::AccWithReg => Clocks::new(3, 0),
+            (Location::Reg(_), Location::Reg(_)) => Clocks::new(4, 0),
+            (Location::Reg(_), mem) | (mem, _) => Clocks::new(17, ea_clocks(mem)),
+        },
+        Instr::Str(string) => {
+            let base = match string.kind {
+                StringKind::Movs => 18,
+                StringKind::Cmps => 22,
+                StringKind::Stos => 11,
+                StringKind::Lods => 12,
+                StringKind::Scas => 15,
+            };
+            Clocks::new(base, 0)
+        }
+        Instr::Prefixed(prefixed) => {
+            let mut clocks = match (prefixed.rep(), prefixed.instr.as_ref()) {
+                (Some(_), Instr::Str(string)) => {
+                    let per_rep = match string.kind {
+                        StringKind::Movs => 17,
+                        StringKind::Cmps => 22,
+                        StringKind::Stos => 10,
+                        StringKind::Lods => 13,
+                        StringKind::Scas => 15,
+                    };
+                    Clocks::new(9 + per_rep * reps as u32, 0)
+                }
+                (_, instr) => estimate_clocks(instr, taken, reps),
+            };
+            if prefixed.segment().is_some() && clocks.ea > 0 {
+                clocks.ea += 2;
+            }
+            if prefixed.lock() {
+                clocks.base += 2;
+            }
+            clocks
+        }
+    }
+}
+
+/// The effective address calculation time for a memory operand
+pub fn ea_clocks(loc: &Location) -> u32 {
+    let eac = match loc {
+        Location::Mem(_) => return 6,
+        Location::Eac(eac) => eac,
+        _ => return 0,
+    };
+    let has_disp = !matches!(eac, EffectiveAddress::Mode(_));
+    match (eac.mode(), has_disp) {
+        (
+            EffectiveAddressMode::Bx
+            | EffectiveAddressMode::Bp
+            | EffectiveAddressMode::Si
+            | EffectiveAddressMode::Di,
+            false,
+        ) => 5,
+        (
+            EffectiveAddressMode::Bx
+            | EffectiveAddressMode::Bp
+            | EffectiveAddressMode::Si
+            | EffectiveAddressMode::Di,
+            true,
+        ) => 9,
+        (EffectiveAddressMode::BpDi | EffectiveAddressMode::BxSi, false) => 7,
+        (EffectiveAddressMode::BpSi | EffectiveAddressMode::BxDi, false) => 8,
+        (EffectiveAddressMode::BpDi | EffectiveAddressMode::BxSi, true) => 11,
+        (EffectiveAddressMode::BpSi | EffectiveAddressMode::BxDi, true) => 12,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        decoder::{decode, loc::Location, mov::CX},
+        sim::{
+            timing::{ea_clocks, estimate_clocks, Clocks, Cpu},
+            SimState,
+        },
+    };
+
+    fn clocks(bytes: Vec<u8>) -> Vec<u32> {
+        decode(bytes)
+            .iter()
+            .map(|instr| estimate_clocks(instr, None, 0).total())
+            .collect()
+    }
+
+    #[test]
+    fn test_mov_clocks() {
+        // mov bx, cx; mov cx, 12; mov dx, [bp + si]; mov [bx + di + 4], ax; mov word [1000], 1;
+        // mov ax, [1000]
+        assert_eq!(
+            clocks(vec![
+                0x89, 0xCB, 0xB9, 0x0C, 0x00, 0x8B, 0x12, 0x89, 0x41, 0x04, 0xC7, 0x06, 0xE8, 0x03,
+                0x01, 0x00, 0xA1, 0xE8, 0x03,
+            ]),
+            vec![2, 4, 16, 21, 16, 10]
+        );
+    }
+
+    #[test]
+    fn test_op_clocks() {
+        // add bx, cx; add cx, [bx]; add [bp + 2], dx; cmp [bp + 2], dx; sub word [bx], 1;
+        // cmp word [bx], 1
+        assert_eq!(
+            clocks(vec![
+                0x01, 0xCB, 0x03, 0x0F, 0x01, 0x56, 0x02, 0x39, 0x56, 0x02, 0x83, 0x2F, 0x01, 0x83,
+                0x3F, 0x01,
+            ]),
+            vec![3, 14, 25, 18, 22, 15]
+        );
+    }
+
+    #[test]
+    fn test_ea_and_override_clocks() {
+        let [plain, es] = [vec![0x8B, 0x40, 0x04], vec![0x26, 0x8B, 0x40, 0x04]]
+            .map(|bytes| estimate_clocks(&decode(bytes)[0], None, 0));
+
+        assert_eq!(plain, Clocks::new(8, 11));
+        assert_eq!(es, Clocks::new(8, 13));
+        assert_eq!(ea_clocks(&Location::Reg(CX)), 0);
+    }
+
+    #[test]
+    fn test_run_clocks() {
+        // mov cx, 3; rep stosb; jne $+2 (taken); je $+2 (not taken)
+        let mut state = SimState::new(vec![0xB9, 0x03, 0x00, 0xF3, 0xAA, 0x75, 0x00, 0x74, 0x00]);
+        let steps = (0..4)
+            .map(|_| state.trace_step().unwrap().clocks.total())
+            .collect::<Vec<_>>();
+
+        assert_eq!(steps, vec![4, 9 + 3 * 10, 16, 4]);
+        assert_eq!(state.cycles(), 63);
+    }
+
+    #[test]
+    fn test_transfer_penalties() {
+        // mov [1000], cx; mov [1001], cx; add [1000], cx
+        let program = vec![
+            0x89, 0x0E, 0xE8, 0x03, 0x89, 0x0E, 0xE9, 0x03, 0x01, 0x0E, 0xE8, 0x03,
+        ];
+        let run = |cpu| {
+            let mut state = SimState::new(program.clone());
+            state.set_cpu(cpu);
+            (0..3)
+                .map(|_| state.trace_step().unwrap().clocks.penalty)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(run(Cpu::I8086), vec![0, 4, 0]);
+        assert_eq!(run(Cpu::I8088), vec![4, 4, 8]);
+
+        let mut state = SimState::new(program);
+        state.set_cpu(Cpu::I8088);
+        assert_eq!(
+            state.trace_step().unwrap().to_string(),
+            "mov [1000], cx ; Clocks: +19 = 19 (9 + 6ea + 4p) | [0x3e8]:0x0->0x0 \
+             [0x3e9]:0x0->0x0 ip:0x0->0x4"
+        );
+        assert_eq!("8088".parse(), Ok(Cpu::I8088));
+        assert!("8087".parse::<Cpu>().is_err());
+    }
+}
@@ -0,0 +1,430 @@
+use std::fmt::Display;
+
+use crate::{
+    decoder::{
+        instr::{try_decode_instr, Instr},
+        json::instr_json,
+        mov::{AX, BP, BX, CX, DI, DX, SI, SP},
+        state::Decoder,
+    },
+    json::Json,
+};
+
+use super::{
+    bus::{TimingMode, BUS_CYCLE},
+    error::SimError,
+    flags::Flags,
+    history::Undo,
+    timing::{estimate_clocks, Clocks},
+    SimState, SEGMENT_REGISTERS,
+};
+
+/// The registers in the order they are shown
+pub const REGISTERS: [&str; 8] = [AX, BX, CX, DX, SP, BP, SI, DI];
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct RegisterChange {
+    pub reg: &'static str,
+    pub before: u16,
+    pub after: u16,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct MemWrite {
+    /// The physical address
+    pub addr: u32,
+    pub before: u8,
+    pub after: u8,
+}
+
+/// What executing a single instruction changed
+#[derive(Debug, PartialEq)]
+pub struct TraceStep {
+    pub addr: u16,
+    pub bytes: Vec<u8>,
+    pub instr: Instr,
+    pub registers: Vec<RegisterChange>,
+    pub flags_before: Flags,
+    pub flags_after: Flags,
+    pub ip_after: u16,
+    pub writes: Vec<MemWrite>,
+    /// Whether a jump, call or ret transferred control, `None` for anything
+    /// else
+    pub branch: Option<bool>,
+    pub clocks: Clocks,
+    /// The clocks taken by the whole run up to and including this step
+    pub cycles: u64,
+}
+
+impl SimState {
+    /// Executes the next instruction, recording what it changed
+    pub fn trace_step(&mut self) -> Result<TraceStep, SimError> {
+        let Some(instr) = try_decode_instr(self) else {
+            self.instr_len = 0;
+            return Err(SimError::UnknownInstruction {
+                addr: self.ip,
+                byte: self.get_byte(0),
+            });
+        };
+        self.execute_decoded(instr)
+    }
+
+    /// Executes `instr`, which has just been decoded at ip. On an error
+    /// anything the instruction changed before failing is put back, leaving
+    /// ip pointing at it.
+    fn execute_decoded(&mut self, instr: Instr) -> Result<TraceStep, SimError> {
+        let addr = self.ip;
+        let registers = REGISTERS.map(|reg| self.get_register_16(reg));
+        let segments = self.segments;
+        let flags_before = self.flags;
+        self.writes.clear();
+        self.transfers.take();
+        self.watch_hit.take();
+
+        let bytes = (0..self.get_instr_len())
+            .map(|offset| self.get_byte(offset))
+            .collect::<Vec<_>>();
+        self.notify(|observer, state| observer.before_instr(state, &instr));
+        self.advance();
+        let result = self.execute(&instr);
+        let undo = (result.is_err() || self.history.is_recording()).then(|| Undo {
+            ip: addr,
+            registers,
+            segments,
+            flags: flags_before,
+            steps: self.steps,
+            cycles: self.cycles,
+            writes: self.writes.clone(),
+        });
+        if let Err(err) = result {
+            self.writes.clear();
+            self.revert(undo.unwrap());
+            return Err(err);
+        }
+
+        let branch = branch_taken(&instr, &self.flags);
+        let cx = self.get_register_16(CX);
+        let mut clocks = estimate_clocks(&instr, branch, registers[2].wrapping_sub(cx));
+        let transfers = self.transfers.get();
+        clocks.penalty = BUS_CYCLE * transfers.slow_words;
+        if self.timing == TimingMode::Prefetch {
+            let exec = clocks.total();
+            clocks.prefetch = self.biu.take(self.cpu, bytes.len() as u32);
+            clocks.prefetch += self
+                .biu
+                .execute(self.cpu, exec, clocks.ea, transfers.bus_cycles());
+            if branch == Some(true) {
+                self.biu.flush();
+            }
+        }
+        self.cycles += clocks.total() as u64;
+
+        let registers = REGISTERS
+            .iter()
+            .zip(registers)
+            .filter_map(|(reg, before)| {
+                let after = self.get_register_16(reg);
+                (before != after).then_some(RegisterChange { reg, before, after })
+            })
+            .collect();
+        let step = TraceStep {
+            addr,
+            bytes,
+            registers,
+            flags_before,
+            flags_after: self.flags,
+            ip_after: self.ip,
+            writes: std::mem::take(&mut self.writes),
+            branch,
+            clocks,
+            cycles: self.cycles,
+            instr,
+        };
+        if let Some(undo) = undo {
+            self.history.record(undo);
+        }
+        self.notify(|observer, state| observer.after_instr(state, &step));
+        Ok(step)
+    }
+
+    pub fn state_json(&self) -> Json {
+        let registers = REGISTERS
+            .iter()
+            .map(|reg| (reg.to_string(), self.get_register_16(reg).into()))
+            .collect();
+        let segments = SEGMENT_REGISTERS
+            .iter()
+            .map(|reg| (reg.to_string(), self.get_register_16(reg).into()))
+            .collect();
+        Json::object([
+            ("registers", Json::Object(registers)),
+            ("segments", Json::Object(segments)),
+            ("flags", self.flags.to_json()),
+            ("ip", self.ip.into()),
+            ("cycles", self.cycles.into()),
+        ])
+    }
+}
+
+// Jumps don't change flags, so the flags after show which way it went
+fn branch_taken(instr: &Instr, flags: &Flags) -> Option<bool> {
+    match instr {
+        Instr::Je(_) => Some(flags.zero),
+        Instr::Jne(_) => Some(!flags.zero),
+        Instr::Jmp(_) | Instr::JmpNear(_) | Instr::Call(_) | Instr::Ret => Some(true),
+        Instr::Prefixed(prefixed) => branch_taken(&prefixed.instr, flags),
+        _ => None,
+    }
+}
+
+impl TraceStep {
+    pub fn to_json(&self) -> Json {
+        let next = self.addr as usize + self.bytes.len();
+        let mut fields = vec![
+            ("address".to_string(), self.addr.into()),
+            ("bytes".to_string(), self.bytes.clone().into()),
+            ("text".to_string(), self.instr.to_string().into()),
+        ];
+        if let Json::Object(instr) = instr_json(&self.instr, next) {
+            fields.extend(instr);
+        }
+        let registers = self
+            .registers
+            .iter()
+            .map(|change| {
+                Json::object([
+                    ("reg", change.reg.into()),
+                    ("before", change.before.into()),
+                    ("after", change.after.into()),
+                ])
+            })
+            .collect();
+        let flags = self
+            .flags_before
+            .values()
+            .into_iter()
+            .zip(self.flags_after.values())
+            .filter(|((_, before), (_, after))| before != after)
+            .map(|((flag, before), (_, after))| {
+                Json::object([
+                    ("flag", flag.into()),
+                    ("before", before.into()),
+                    ("after", after.into()),
+                ])
+            })
+            .collect();
+        let writes = self
+            .writes
+            .iter()
+            .map(|write| {
+                Json::object([
+                    ("address", write.addr.into()),
+                    ("before", write.before.into()),
+                    ("after", write.after.into()),
+                ])
+            })
+            .collect();
+        fields.extend([
+            ("registers".to_string(), Json::Array(registers)),
+            ("flags".to_string(), Json::Array(flags)),
+            (
+                "ip".to_string(),
+                Json::object([
+                    ("before", self.addr.into()),
+                    ("after", self.ip_after.into()),
+                ]),
+            ),
+            ("memory".to_string(), Json::Array(writes)),
+            ("branch".to_string(), self.branch.into()),
+            (
+                "clocks".to_string(),
+                Json::object([
+                    ("base", self.clocks.base.into()),
+                    ("ea", self.clocks.ea.into()),
+                    ("penalty", self.clocks.penalty.into()),
+                    ("prefetch", self.clocks.prefetch.into()),
+                    ("total", self.clocks.total().into()),
+                ]),
+            ),
+        ]);
+        Json::Object(fields)
+    }
+}
+
+/// Formats the step like the Computer Enhance reference simulator, e.g.
+/// `add cx, [bx] ; Clocks: +14 = 47 (9 + 5ea) | cx:0x3->0x4 ip:0x10->0x12`
+impl Display for TraceStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ; Clocks: +{} = {}",
+            self.instr,
+            self.clocks.total(),
+            self.cycles
+        )?;
+        if self.clocks.ea > 0 || self.clocks.penalty > 0 || self.clocks.prefetch > 0 {
+            write!(f, " ({}", self.clocks.base)?;
+            if self.clocks.ea > 0 {
+                write!(f, " + {}ea", self.clocks.ea)?;
+            }
+            if self.clocks.penalty > 0 {
+                write!(f, " + {}p", self.clocks.penalty)?;
+            }
+            if self.clocks.prefetch > 0 {
+                write!(f, " + {}q", self.clocks.prefetch)?;
+            }
+            write!(f, ")")?;
+        }
+        write!(f, " |")?;
+        for change in &self.registers {
+            write!(
+                f,
+                " {}:{:#x}->{:#x}",
+                change.reg, change.before, change.after
+            )?;
+        }
+        for write in &self.writes {
+            write!(
+                f,
+                " [{:#x}]:{:#x}->{:#x}",
+                write.addr, write.before, write.after
+            )?;
+        }
+        write!(f, " ip:{:#x}->{:#x}", self.addr, self.ip_after)?;
+        if self.flags_before != self.flags_after {
+            write!(
+                f,
+                " flags:{}->{}",
+                self.flags_before.letters(),
+                self.flags_after.letters()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        decoder::{common::Encoding, instr::Instr, loc::Location, mov::CX, xchg::XchgInstr},
+        sim::{
+            error::SimError,
+            trace::{MemWrite, RegisterChange},
+            SimState,
+        },
+    };
+
+    #[test]
+    fn test_trace_step() {
+        // mov cx, 1; sub cx, 1; mov [1000], cx
+        let mut state = SimState::new(vec![
+            0b10111001, 0b1, 0b0, 0b10000011, 0b11101001, 0b1, 0b10001001, 0b1110, 0b11101000, 0b11,
+        ]);
+        let first = state.trace_step().unwrap();
+        assert_eq!(
+            first.registers,
+            vec![RegisterChange {
+                reg: CX,
+                before: 0,
+                after: 1
+            }]
+        );
+        assert_eq!(first.bytes, vec![0b10111001, 0b1, 0b0]);
+
+        let second = state.trace_step().unwrap();
+        assert!(!second.flags_before.zero);
+        assert!(second.flags_after.zero);
+
+        let third = state.trace_step().unwrap();
+        assert!(third.registers.is_empty());
+        assert_eq!(
+            third.writes,
+            vec![
+                MemWrite {
+                    addr: 1000,
+                    before: 0,
+                    after: 0
+                },
+                MemWrite {
+                    addr: 1001,
+                    before: 0,
+                    after: 0
+                }
+            ]
+        );
+        assert_eq!(third.ip_after, 10);
+    }
+
+    #[test]
+    fn test_trace_display() {
+        // mov cx, 3; add cx, 1; sub cx, 4; mov [1000], cx
+        let mut state = SimState::new(vec![
+            0b10111001, 0b11, 0b0, 0b10000011, 0b11000001, 0b1, 0b10000011, 0b11101001, 0b100,
+            0b10001001, 0b1110, 0b11101000, 0b11,
+        ]);
+        state.trace_step().unwrap();
+
+        assert_eq!(
+            state.trace_step().unwrap().to_string(),
+            "add cx, 1 ; Clocks: +4 = 8 | cx:0x3->0x4 ip:0x3->0x6"
+        );
+        assert_eq!(
+            state.trace_step().unwrap().to_string(),
+            "sub cx, 4 ; Clocks: +4 = 12 | cx:0x4->0x0 ip:0x6->0x9 flags:->Z"
+        );
+        state.set_register_16(CX, 0x1234);
+        assert_eq!(
+            state.trace_step().unwrap().to_string(),
+            "mov [1000], cx ; Clocks: +15 = 27 (9 + 6ea) | [0x3e8]:0x0->0x34 [0x3e9]:0x0->0x12 \
+             ip:0x9->0xd"
+        );
+    }
+
+    #[test]
+    fn test_trace_json() {
+        // mov cx, 1; sub cx, 1
+        let mut state = SimState::new(vec![0b10111001, 0b1, 0b0, 0b10000011, 0b11101001, 0b1]);
+        state.trace_step().unwrap();
+
+        assert_eq!(
+            state.trace_step().unwrap().to_json().to_string(),
+            "{\"address\":3,\"bytes\":[131,233,1],\"text\":\"sub cx, 1\",\"mnemonic\":\"sub\",\
+             \"prefixes\":[],\"operands\":[{\"kind\":\"Reg\",\"reg\":\"cx\"},\
+             {\"kind\":\"Immediate16\",\"value\":1}],\
+             \"registers\":[{\"reg\":\"cx\",\"before\":1,\"after\":0}],\
+             \"flags\":[{\"flag\":\"zero\",\"before\":false,\"after\":true}],\
+             \"ip\":{\"before\":3,\"after\":6},\"memory\":[],\"branch\":null,\
+             \"clocks\":{\"base\":4,\"ea\":0,\"penalty\":0,\"prefetch\":0,\"total\":4}}"
+        );
+        assert_eq!(
+            state.state_json().to_string(),
+            "{\"registers\":{\"ax\":0,\"bx\":0,\"cx\":0,\"dx\":0,\"sp\":0,\"bp\":0,\"si\":0,\
+             \"di\":0},\"segments\":{\"cs\":0,\"ds\":0,\"es\":0,\"ss\":0},\"flags\":{\"zero\":true,\"sign\":false},\"ip\":6,\"cycles\":8}"
+        );
+    }
+
+    #[test]
+    fn test_failed_step_changes_nothing() {
+        let mut state = SimState::new(vec![0x90; 4]);
+        state.set_register_16(CX, 7);
+        // The first half of each exchange works, then writing the immediate
+        // fails
+        for dest in [Location::Reg(CX), Location::Mem(0x10)] {
+            let instr = Instr::Xchg(XchgInstr {
+                dest,
+                src: Location::Immediate16(5),
+                encoding: Encoding::RmToFromReg { d: true },
+            });
+            assert_eq!(
+                state.execute_decoded(instr),
+                Err(SimError::InvalidOperand(
+                    "cannot set value to immediate".to_string()
+                ))
+            );
+        }
+        assert_eq!(state.get_register_16(CX), 7);
+        assert_eq!(state.memory()[0x10], 0);
+        assert_eq!(state.ip(), 0);
+        assert!(state.trace_step().unwrap().writes.is_empty());
+    }
+}
@@ -0,0 +1,248 @@
+use std::{fmt::Display, ops::RangeInclusive};
+
+use super::{physical_address, SimState, MEMORY_SIZE};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum WatchKind {
+    Read,
+    Write,
+    /// Either a read or a write
+    Access,
+}
+
+/// Stops execution after an instruction reads or writes any of `len` bytes
+/// from `start`, a physical address. Like accesses, the bytes wrap around the
+/// end of their segment and of memory.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Watchpoint {
+    pub start: u32,
+    pub len: u32,
+    pub kind: WatchKind,
+    /// The watched physical addresses, split where they wrap
+    ranges: Vec<RangeInclusive<u32>>,
+}
+
+impl Watchpoint {
+    /// Watches `len` bytes from `offset` in the segment at paragraph
+    /// `segment`, going back to its start after offset 0xFFFF
+    pub fn in_segment(segment: u16, offset: u16, len: u16, kind: WatchKind) -> Self {
+        let addrs = (0..len.max(1)).map(|i| physical_address(segment, offset.wrapping_add(i)));
+        Self::from_addrs(addrs, kind)
+    }
+
+    /// Watches `len` bytes from the physical address `start`, going back to
+    /// 0 at the end of memory
+    pub fn physical(start: u32, len: u32, kind: WatchKind) -> Self {
+        let size = MEMORY_SIZE as u32;
+        let start = start % size;
+        let addrs = (0..len.clamp(1, size)).map(|i| (start + i) % size);
+        Self::from_addrs(addrs, kind)
+    }
+
+    fn from_addrs(addrs: impl Iterator<Item = u32>, kind: WatchKind) -> Self {
+        let mut ranges: Vec<RangeInclusive<u32>> = vec![];
+        let mut len = 0;
+        for addr in addrs {
+            len += 1;
+            match ranges.last_mut() {
+                Some(range) if range.end() + 1 == addr => *range = *range.start()..=addr,
+                _ => ranges.push(addr..=addr),
+            }
+        }
+        Self {
+            start: *ranges[0].start(),
+            len,
+            kind,
+            ranges,
+        }
+    }
+
+    /// The physical address of the last watched byte
+    pub fn end(&self) -> u32 {
+        *self.ranges[self.ranges.len() - 1].end()
+    }
+
+    pub fn contains(&self, addr: u32) -> bool {
+        self.ranges.iter().any(|range| range.contains(&addr))
+    }
+
+    fn matches(&self, addr: u32, write: bool) -> bool {
+        let kind = match self.kind {
+            WatchKind::Read => !write,
+            WatchKind::Write => write,
+            WatchKind::Access => true,
+        };
+        kind && self.contains(addr)
+    }
+}
+
+/// Written as the debugger command that sets it, e.g. `rwatch 0x0100 4`
+impl Display for Watchpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let command = match self.kind {
+            WatchKind::Read => "rwatch",
+            WatchKind::Write => "watch",
+            WatchKind::Access => "awatch",
+        };
+        write!(f, "{} {:#06x}", command, self.start)?;
+        if self.len != 1 {
+            write!(f, " {}", self.len)?;
+        }
+        Ok(())
+    }
+}
+
+/// The first watched byte an instruction touched
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct WatchHit {
+    pub addr: u32,
+    pub write: bool,
+    /// The same as `after` for a read
+    pub before: u8,
+    pub after: u8,
+    /// Where the instruction responsible starts
+    pub instr_addr: u16,
+}
+
+impl Display for WatchHit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.write {
+            write!(
+                f,
+                "watchpoint {:#x} written by instruction at {:#x}: {:#x}->{:#x}",
+                self.addr, self.instr_addr, self.before, self.after
+            )
+        } else {
+            write!(
+                f,
+                "watchpoint {:#x} read by instruction at {:#x}: {:#x}",
+                self.addr, self.instr_addr, self.after
+            )
+        }
+    }
+}
+
+impl SimState {
+    /// Replaces any watchpoint already starting at the same address
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.remove_watchpoint(watchpoint.start);
+        self.watchpoints.push(watchpoint);
+    }
+
+    /// Returns whether there was a watchpoint starting at `start`
+    pub fn remove_watchpoint(&mut self, start: u32) -> bool {
+        let len = self.watchpoints.len();
+        self.watchpoints
+            .retain(|watchpoint| watchpoint.start != start);
+        self.watchpoints.len() != len
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    /// Remembers the first watched byte the current instruction touches
+    pub(crate) fn watch(&self, addr: u32, write: bool, before: u8, after: u8) {
+        if self.watch_hit.get().is_some()
+            || !self
+                .watchpoints
+                .iter()
+                .any(|watchpoint| watchpoint.matches(addr, write))
+        {
+            return;
+        }
+        self.watch_hit.set(Some(WatchHit {
+            addr,
+            write,
+            before,
+            after,
+            // Filled in by step once the instruction has run
+            instr_addr: 0,
+        }));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        decoder::prefix::Segment,
+        sim::{
+            step::StopReason,
+            watch::{WatchHit, WatchKind, Watchpoint},
+            SimState,
+        },
+    };
+
+    #[test]
+    fn test_watchpoints() {
+        // mov cx, 3; mov [0x101], cx; add cx, [0x100]; mov [0x200], cx
+        let mut state = SimState::new(vec![
+            0xB9, 0x03, 0x00, 0x89, 0x0E, 0x01, 0x01, 0x03, 0x0E, 0x00, 0x01, 0x89, 0x0E, 0x00,
+            0x02,
+        ]);
+        state.add_watchpoint(Watchpoint::physical(0x100, 2, WatchKind::Write));
+        state.add_watchpoint(Watchpoint::physical(0x200, 1, WatchKind::Read));
+
+        let hit = WatchHit {
+            addr: 0x101,
+            write: true,
+            before: 0,
+            after: 3,
+            instr_addr: 3,
+        };
+        assert_eq!(state.run(), Ok(StopReason::Watchpoint(hit)));
+        assert_eq!(state.ip(), 7);
+        assert_eq!(
+            hit.to_string(),
+            "watchpoint 0x101 written by instruction at 0x3: 0x0->0x3"
+        );
+
+        // The read of 0x100 and the write to 0x200 don't match
+        assert_eq!(state.run(), Ok(StopReason::EndOfProgram));
+
+        state.add_watchpoint(Watchpoint::physical(0x100, 2, WatchKind::Access));
+        state.set_ip(7);
+        let Ok(StopReason::Watchpoint(hit)) = state.run() else {
+            panic!("expected a watchpoint")
+        };
+        assert_eq!((hit.addr, hit.write, hit.after), (0x100, false, 0));
+        assert_eq!(state.watchpoints().len(), 2);
+        assert_eq!(state.watchpoints()[1].to_string(), "awatch 0x0100 2");
+        assert!(state.remove_watchpoint(0x200));
+    }
+
+    #[test]
+    fn test_watchpoint_wraps() {
+        // The second byte is back at the start of the segment
+        let watchpoint = Watchpoint::in_segment(0x1000, 0xFFFF, 2, WatchKind::Write);
+        assert!(watchpoint.contains(0x1FFFF));
+        assert!(watchpoint.contains(0x10000));
+        assert!(!watchpoint.contains(0x20000));
+        assert_eq!(watchpoint.end(), 0x10000);
+        assert_eq!(watchpoint.to_string(), "watch 0x1ffff 2");
+
+        let watchpoint = Watchpoint::physical(0xFFFFF, 2, WatchKind::Read);
+        assert!(watchpoint.contains(0xFFFFF));
+        assert!(watchpoint.contains(0));
+        assert!(Watchpoint::in_segment(0xFFFF, 0x10, 1, WatchKind::Read).contains(0));
+
+        // mov [0xFFFF], cx, whose high byte goes to offset 0
+        let mut state = SimState::new(vec![0x89, 0x0E, 0xFF, 0xFF]);
+        state.set_register_16("cx", 0x1234);
+        state.set_segment(Segment::Ds, 0x1000);
+        state.add_watchpoint(Watchpoint::in_segment(0x1000, 0xFFFF, 2, WatchKind::Write));
+        let Ok(StopReason::Watchpoint(hit)) = state.run() else {
+            panic!("expected a watchpoint")
+        };
+        assert_eq!((hit.addr, hit.after), (0x1FFFF, 0x34));
+        assert_eq!(state.memory()[0x10000], 0x12);
+
+        state.add_watchpoint(Watchpoint::in_segment(0x1000, 0, 1, WatchKind::Write));
+        state.remove_watchpoint(0x1FFFF);
+        state.set_ip(0);
+        let Ok(StopReason::Watchpoint(hit)) = state.run() else {
+            panic!("expected a watchpoint")
+        };
+        assert_eq!(hit.addr, 0x10000);
+    }
+}
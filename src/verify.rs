@@ -0,0 +1,176 @@
+use std::fmt::Display;
+
+use crate::{
+    assembler::{assemble, AssembleError},
+    decoder::{decode_items, Item},
+};
+
+#[derive(Debug, PartialEq)]
+pub enum VerifyError {
+    /// The disassembly could not be reassembled
+    Assemble { err: AssembleError, text: String },
+    /// The reassembled bytes differ from the original from `offset` on
+    Mismatch {
+        offset: usize,
+        expected: Option<u8>,
+        found: Option<u8>,
+        text: String,
+    },
+}
+
+impl Display for VerifyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VerifyError::Assemble { err, text } => {
+                write!(f, "failed to reassemble '{}': {}", text, err.message)
+            }
+            VerifyError::Mismatch {
+                offset,
+                expected,
+                found,
+                text,
+            } => write!(
+                f,
+                "mismatch at {:04X}: expected {}, found {} in '{}'",
+                offset,
+                fmt_byte(*expected),
+                fmt_byte(*found),
+                text
+            ),
+        }
+    }
+}
+
+fn fmt_byte(byte: Option<u8>) -> String {
+    match byte {
+        Some(byte) => format!("{:02X}", byte),
+        None => "end of program".to_string(),
+    }
+}
+
+pub fn disassemble_text(items: &[Item]) -> String {
+    let mut text = "bits 16\n".to_string();
+    for item in items {
+        text.push_str(&item.to_string());
+        text.push('\n');
+    }
+    text
+}
+
+/// Disassembles `bytes`, reassembles the text and checks the result is identical.
+/// Returns the number of lines verified.
+pub fn verify(bytes: &[u8]) -> Result<usize, VerifyError> {
+    let items = decode_items(bytes.to_vec());
+    let text = disassemble_text(&items);
+    let reassembled = assemble(&text).map_err(|err| VerifyError::Assemble {
+        // Line 1 is the bits header
+        text: text
+            .lines()
+            .nth(err.line - 1)
+            .unwrap_or_default()
+            .to_string(),
+        err,
+    })?;
+
+    let offset = bytes
+        .iter()
+        .zip(&reassembled)
+        .position(|(expected, found)| expected != found)
+        .or_else(|| (bytes.len() != reassembled.len()).then(|| bytes.len().min(reassembled.len())));
+
+    match offset {
+        None => Ok(items.len()),
+        Some(offset) => {
+            let item = items
+                .iter()
+                .find(|item| offset < item.addr() + item.byte_len())
+                .or(items.last());
+            Err(VerifyError::Mismatch {
+                offset,
+                expected: bytes.get(offset).copied(),
+                found: reassembled.get(offset).copied(),
+                text: item.map(|item| item.to_string()).unwrap_or_default(),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        assembler::assemble,
+        verify::{verify, VerifyError},
+    };
+
+    #[test]
+    fn test_verify_listings() {
+        for entry in std::fs::read_dir(".").unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_some_and(|ext| ext == "asm") {
+                let src = std::fs::read_to_string(&path).unwrap();
+                let bytes = assemble(&src).unwrap();
+                assert!(verify(&bytes).is_ok(), "{} failed", path.display());
+            }
+        }
+    }
+
+    #[test]
+    fn test_verify_binary() {
+        let bytes = std::fs::read("test").unwrap();
+        assert_eq!(verify(&bytes), Ok(10));
+    }
+
+    #[test]
+    fn test_verify_all_forms() {
+        let bytes = assemble(
+            "mov cx, bx\nmov dl, [bx + si]\nmov [bp + di - 300], cx\nmov ax, [2555]\n\
+             mov [15], al\nmov cl, -12\nmov [bx + di], byte 7\nmov word [1000], 1\n\
+             add bx, [bx + si]\nsub [bp], al\nadd al, 34\nadd ax, 1000\ncmp ax, -2\n\
+             sub bl, 1\ncmp word [4834], 29\nadd byte [bx], 34\nsub dx, 1000\n\
+             start:\nje start\njne $+40",
+        )
+        .unwrap();
+        assert_eq!(verify(&bytes), Ok(19));
+    }
+
+    #[test]
+    fn test_verify_unknown_bytes() {
+        let bytes = vec![0b11111111, 0b10110001, 0b1100, 0b1000, 0b10001001];
+        assert_eq!(verify(&bytes), Ok(4));
+    }
+
+    #[test]
+    fn test_verify_reports_first_difference() {
+        // mov cx, bx with the direction bit set, which NASM never emits
+        let bytes = vec![0b10001001, 0b11011001, 0b10001011, 0b11001011];
+        let err = verify(&bytes).unwrap_err();
+        assert_eq!(
+            err,
+            VerifyError::Mismatch {
+                offset: 2,
+                expected: Some(0b10001011),
+                found: Some(0b10001001),
+                text: "mov cx, bx".to_string(),
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "mismatch at 0002: expected 8B, found 89 in 'mov cx, bx'"
+        );
+    }
+
+    #[test]
+    fn test_verify_reports_shorter_form() {
+        // add ax, 1 using the accumulator form, which reassembles shorter
+        let bytes = vec![0b101, 0b1, 0b0];
+        assert_eq!(
+            verify(&bytes),
+            Err(VerifyError::Mismatch {
+                offset: 0,
+                expected: Some(0b101),
+                found: Some(0b10000011),
+                text: "add ax, 1".to_string(),
+            })
+        );
+    }
+}